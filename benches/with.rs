@@ -0,0 +1,30 @@
+//! Overhead of the `std` implementation.
+//!
+//! Run with `cargo bench --features std,bench`.
+
+use std::sync::OnceLock;
+use std::time::Instant;
+
+fn nanos() -> u32 {
+    static START: OnceLock<Instant> = OnceLock::new();
+    START.get_or_init(Instant::now).elapsed().as_nanos() as u32
+}
+
+fn main() {
+    // Warm up, and take the best of a few runs.
+    let mut best = critical_section::bench::run(nanos, 100_000);
+    for _ in 0..10 {
+        let report = critical_section::bench::run(nanos, 100_000);
+        best.with = best.with.min(report.with);
+        best.nested_with = best.nested_with.min(report.nested_with);
+        best.enter = best.enter.min(report.enter);
+        best.with_fn = best.with_fn.min(report.with_fn);
+        best.with_fn_arg = best.with_fn_arg.min(report.with_fn_arg);
+    }
+
+    println!("with:        {:>4} ns", best.with);
+    println!("nested with: {:>4} ns", best.nested_with);
+    println!("enter:       {:>4} ns", best.enter);
+    println!("with_fn:     {:>4} ns", best.with_fn);
+    println!("with_fn_arg: {:>4} ns", best.with_fn_arg);
+}
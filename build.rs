@@ -1,9 +1,179 @@
 use std::env;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::PathBuf;
+
+/// Cargo features enabling a built-in critical section implementation, which each define the
+/// `extern` symbols and select a restore state, so at most one of them can be enabled.
+///
+/// `multicore-spinlock` only provides an implementation for `set_impl!`, but it selects a
+/// restore state all the same.
+const IMPLS: &[&str] = &[
+    "std",
+    "cortex-m",
+    "riscv-single-hart",
+    "avr",
+    "msp430",
+    "xtensa",
+    "single-threaded",
+    "freertos",
+    "zephyr",
+    "extern-fns",
+    "dynamic-impl",
+    "multicore-spinlock",
+    "trusted-single-context",
+    "fallback-panic",
+];
+
+/// `restore-state-*` Cargo features, with the `RawRestoreState` type each selects.
+const RESTORE_STATES: &[(&str, &str)] = &[
+    ("restore-state-none", "()"),
+    ("restore-state-bool", "bool"),
+    ("restore-state-u8", "u8"),
+    ("restore-state-u16", "u16"),
+    ("restore-state-u32", "u32"),
+    ("restore-state-u64", "u64"),
+    ("restore-state-usize", "usize"),
+    ("restore-state-opaque", "OpaqueRestoreState"),
+];
+
+/// `barriers-*` Cargo features, of which at most one can be enabled.
+const BARRIERS: &[&str] = &["barriers-compiler", "barriers-full"];
 
 fn main() {
     let target = env::var("TARGET").unwrap();
 
+    println!("cargo:rustc-check-cfg=cfg(cortex_m)");
+    println!("cargo:rustc-check-cfg=cfg(has_basepri)");
+    println!("cargo:rustc-check-cfg=cfg(loom)");
+    println!("cargo:rustc-check-cfg=cfg(no_atomic_load_store)");
+
     if target.starts_with("thumbv") {
         println!("cargo:rustc-cfg=cortex_m");
     }
+
+    if target.starts_with("thumbv") {
+        let manifest_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+        println!(
+            "cargo:rustc-link-arg-examples=-T{}/examples/bench-embedded.x",
+            manifest_dir
+        );
+    }
+
+    // ARMv6-M and ARMv8-M Baseline have no BASEPRI register.
+    if target.starts_with("thumbv7m")
+        || target.starts_with("thumbv7em")
+        || target.starts_with("thumbv8m.main")
+    {
+        println!("cargo:rustc-cfg=has_basepri");
+    }
+
+    // `AtomicUsize` is missing on AVR, which only has 8-bit atomic loads and stores. This can't
+    // be detected with `cfg(target_has_atomic)`, which requires compare-and-swap.
+    if target.starts_with("avr") {
+        println!("cargo:rustc-cfg=no_atomic_load_store");
+    }
+
+    let out = PathBuf::from(env::var("OUT_DIR").unwrap()).join("features.rs");
+    fs::write(out, features()).unwrap();
+}
+
+fn enabled(features: &[&'static str]) -> Vec<&'static str> {
+    features
+        .iter()
+        .copied()
+        .filter(|feature| {
+            let var = format!("CARGO_FEATURE_{}", feature.to_uppercase().replace('-', "_"));
+            env::var_os(var).is_some()
+        })
+        .collect()
+}
+
+fn list(features: &[&str]) -> String {
+    let quoted: Vec<_> = features.iter().map(|f| format!("`{}`", f)).collect();
+    match quoted.split_last() {
+        Some((last, [])) => last.clone(),
+        Some((last, rest)) => format!("{} and {}", rest.join(", "), last),
+        None => String::new(),
+    }
+}
+
+/// Generates the code included by `lib.rs`: `compile_error!`s for invalid combinations of
+/// Cargo features, and the `RawRestoreStateInner` type alias.
+///
+/// Checking here rather than with `cfg`s there names exactly the features that conflict, and
+/// reports a single error for each problem.
+fn features() -> String {
+    let mut checks = String::new();
+    let impls = enabled(IMPLS);
+    let names: Vec<_> = RESTORE_STATES.iter().map(|(feature, _)| *feature).collect();
+    let restore_states = enabled(&names);
+
+    // With conflicting features, pick any type, the build fails anyway.
+    let restore_state = RESTORE_STATES
+        .iter()
+        .find(|(feature, _)| restore_states.first() == Some(feature))
+        .map_or("()", |(_, ty)| ty);
+    writeln!(checks, "type RawRestoreStateInner = {};", restore_state).unwrap();
+
+    if impls.len() > 1 {
+        writeln!(
+            checks,
+            "compile_error!({:?});",
+            format!(
+                "Only one built-in critical section implementation can be enabled, but these \
+                 Cargo features are: {}. They may be enabled by different crates, run \
+                 `cargo tree -e features -i critical-section` to find out which.",
+                list(&impls)
+            )
+        )
+        .unwrap();
+    } else if restore_states.len() > 1 {
+        // Each implementation sets its own restore state, so this is only reported if it's not
+        // caused by the conflict above.
+        writeln!(
+            checks,
+            "compile_error!({:?});",
+            format!(
+                "At most one `restore-state-*` Cargo feature can be enabled, but these are: {}. \
+                 Only the crate providing the critical section implementation may set one.",
+                list(&restore_states)
+            )
+        )
+        .unwrap();
+    }
+
+    let barriers = enabled(BARRIERS);
+    if barriers.len() > 1 {
+        writeln!(
+            checks,
+            "compile_error!({:?});",
+            format!(
+                "At most one `barriers-*` Cargo feature can be enabled, but these are: {}.",
+                list(&barriers)
+            )
+        )
+        .unwrap();
+    }
+
+    if impls.is_empty() && env::var_os("CARGO_FEATURE_REQUIRE_IMPL").is_some() {
+        let suggested: Vec<_> = IMPLS
+            .iter()
+            .copied()
+            .filter(|feature| !matches!(*feature, "multicore-spinlock" | "trusted-single-context"))
+            .collect();
+        writeln!(
+            checks,
+            "compile_error!({:?});",
+            format!(
+                "The `require-impl` Cargo feature is enabled, but no built-in critical section \
+                 implementation is. Enable one of the {} Cargo features, or disable \
+                 `require-impl` if the implementation comes from another crate.",
+                list(&suggested).replacen(" and ", " or ", 1)
+            )
+        )
+        .unwrap();
+    }
+
+    checks
 }
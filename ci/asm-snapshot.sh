@@ -0,0 +1,37 @@
+#!/bin/sh
+# Checks the assembly of the critical section fast paths against the snapshots in `ci/asm`, so
+# changes to inlining and forwarding can't silently make them slower. The fast paths are the
+# `snapshot_*` functions of the `bench-embedded` example, and the instances of `with_fn` and
+# `with_fn_arg` they call.
+#
+# Run with `--bless` to update the snapshots after an intended change. The code generated also
+# changes between compiler versions, so the snapshots are only checked on stable in CI.
+
+set -eu
+cd "$(dirname "$0")/.."
+
+status=0
+for target in thumbv6m-none-eabi thumbv7m-none-eabi; do
+    rm -f target/$target/release/examples/bench_embedded-*.s
+    # Cargo doesn't rebuild a fresh example, and then emits no assembly.
+    touch examples/bench-embedded.rs
+    cargo rustc --quiet --release --example bench-embedded --features cortex-m,bench \
+        --target $target -- --emit asm
+    # Keep the instructions of those functions, without directives, comments, label numbers
+    # and symbol hashes.
+    awk '
+        /^snapshot_[a-z_]*:/ || /^_ZN16critical_section[0-9]+with_fn/ { keep = 1 }
+        keep && /^\.Lfunc_end/ { keep = 0; print ""; next }
+        keep && !/^\t\./ && !/^\t@/ { print }
+    ' target/$target/release/examples/bench_embedded-*.s |
+        sed -E 's/\.LBB[0-9]+_([0-9]+)/.LBB_\1/g; s/\.LCPI[0-9]+_/.LCPI_/g; s/17h[0-9a-f]{16}E/E/g' \
+        > target/$target.s
+
+    if [ "${1:-}" = "--bless" ]; then
+        cp target/$target.s ci/asm/$target.s
+    elif ! diff -u ci/asm/$target.s target/$target.s; then
+        echo "The fast path changed for $target. If intended, run ci/asm-snapshot.sh --bless." >&2
+        status=1
+    fi
+done
+exit $status
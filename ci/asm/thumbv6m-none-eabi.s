@@ -0,0 +1,192 @@
+_ZN16critical_section11with_fn_argE:
+	push	{r4, r5, r6, r7, lr}
+	add	r7, sp, #12
+	sub	sp, #4
+	str	r1, [sp]
+	mov	r6, r0
+	bl	_critical_section_1_0_acquire
+	mov	r4, r0
+	ldr	r1, .LCPI_0
+	ldr	r0, [r1]
+	adds	r5, r0, #1
+	str	r5, [r1]
+	ldr	r1, .LCPI_1
+	ldr	r2, [r1]
+	cmp	r5, r2
+	bls	.LBB_2
+	str	r5, [r1]
+.LBB_2:
+	cmp	r0, #0
+	bne	.LBB_4
+	ldr	r0, .LCPI_2
+	str	r4, [r0]
+.LBB_4:
+	mov	r0, r6
+	ldr	r1, [sp]
+	blx	r1
+	mov	r6, r0
+	ldr	r1, .LCPI_0
+	ldr	r0, [r1]
+	cmp	r0, r5
+	beq	.LBB_7
+	cmp	r0, #0
+	beq	.LBB_10
+	bl	_critical_section_1_0_acquire
+	mov	r4, r0
+	bl	_critical_section_1_0_release
+	ldr	r1, .LCPI_0
+	ldr	r5, [r1]
+.LBB_7:
+	subs	r0, r5, #1
+	str	r0, [r1]
+	bne	.LBB_9
+	ldr	r0, .LCPI_2
+	ldr	r4, [r0]
+.LBB_9:
+	mov	r0, r4
+	bl	_critical_section_1_0_release
+	mov	r0, r6
+	add	sp, #4
+	pop	{r4, r5, r6, r7, pc}
+.LBB_10:
+	ldr	r0, .LCPI_3
+	movs	r1, #51
+	ldr	r2, .LCPI_4
+	bl	_RNvNtCseold96UKqg_4core9panicking5panic
+.LCPI_0:
+.LCPI_1:
+.LCPI_2:
+.LCPI_3:
+.LCPI_4:
+
+_ZN16critical_section7with_fnE:
+	push	{r4, r5, r6, r7, lr}
+	add	r7, sp, #12
+	sub	sp, #4
+	mov	r5, r0
+	bl	_critical_section_1_0_acquire
+	mov	r4, r0
+	ldr	r1, .LCPI_0
+	ldr	r0, [r1]
+	adds	r6, r0, #1
+	str	r6, [r1]
+	ldr	r1, .LCPI_1
+	ldr	r2, [r1]
+	cmp	r6, r2
+	bls	.LBB_2
+	str	r6, [r1]
+.LBB_2:
+	cmp	r0, #0
+	bne	.LBB_4
+	ldr	r0, .LCPI_2
+	str	r4, [r0]
+.LBB_4:
+	blx	r5
+	mov	r5, r0
+	ldr	r1, .LCPI_0
+	ldr	r0, [r1]
+	cmp	r0, r6
+	beq	.LBB_7
+	cmp	r0, #0
+	beq	.LBB_10
+	bl	_critical_section_1_0_acquire
+	mov	r4, r0
+	bl	_critical_section_1_0_release
+	ldr	r1, .LCPI_0
+	ldr	r6, [r1]
+.LBB_7:
+	subs	r0, r6, #1
+	str	r0, [r1]
+	bne	.LBB_9
+	ldr	r0, .LCPI_2
+	ldr	r4, [r0]
+.LBB_9:
+	mov	r0, r4
+	bl	_critical_section_1_0_release
+	mov	r0, r5
+	add	sp, #4
+	pop	{r4, r5, r6, r7, pc}
+.LBB_10:
+	ldr	r0, .LCPI_3
+	movs	r1, #51
+	ldr	r2, .LCPI_4
+	bl	_RNvNtCseold96UKqg_4core9panicking5panic
+.LCPI_0:
+.LCPI_1:
+.LCPI_2:
+.LCPI_3:
+.LCPI_4:
+
+snapshot_enter:
+	push	{r4, r6, r7, lr}
+	add	r7, sp, #8
+	bl	_critical_section_1_0_acquire
+	ldr	r1, .LCPI_0
+	ldr	r1, [r1]
+	adds	r2, r1, #1
+	ldr	r3, .LCPI_1
+	ldr	r4, [r3]
+	cmp	r2, r4
+	bls	.LBB_2
+	str	r2, [r3]
+.LBB_2:
+	cmp	r1, #0
+	bne	.LBB_4
+	ldr	r1, .LCPI_2
+	str	r0, [r1]
+.LBB_4:
+	ldr	r1, .LCPI_3
+	movs	r2, #0
+	str	r2, [r1]
+	bl	_critical_section_1_0_release
+	pop	{r4, r6, r7, pc}
+.LCPI_0:
+.LCPI_1:
+.LCPI_2:
+.LCPI_3:
+
+snapshot_with:
+	push	{r4, r6, r7, lr}
+	add	r7, sp, #8
+	bl	_critical_section_1_0_acquire
+	ldr	r1, .LCPI_0
+	ldr	r1, [r1]
+	adds	r2, r1, #1
+	ldr	r3, .LCPI_1
+	ldr	r4, [r3]
+	cmp	r2, r4
+	bls	.LBB_2
+	str	r2, [r3]
+.LBB_2:
+	cmp	r1, #0
+	bne	.LBB_4
+	ldr	r1, .LCPI_2
+	str	r0, [r1]
+.LBB_4:
+	ldr	r1, .LCPI_3
+	ldr	r2, [r1]
+	adds	r2, r2, #1
+	str	r2, [r1]
+	bl	_critical_section_1_0_release
+	pop	{r4, r6, r7, pc}
+.LCPI_0:
+.LCPI_1:
+.LCPI_2:
+.LCPI_3:
+
+snapshot_with_fn:
+	push	{r7, lr}
+	add	r7, sp, #0
+	ldr	r0, .LCPI_0
+	bl	_ZN16critical_section7with_fnE
+	pop	{r7, pc}
+.LCPI_0:
+
+snapshot_with_fn_arg:
+	push	{r7, lr}
+	add	r7, sp, #0
+	ldr	r1, .LCPI_0
+	bl	_ZN16critical_section11with_fn_argE
+	pop	{r7, pc}
+.LCPI_0:
+
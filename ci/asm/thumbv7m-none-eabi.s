@@ -0,0 +1,173 @@
+_ZN16critical_section11with_fn_argE:
+	push	{r4, r5, r6, r7, lr}
+	add	r7, sp, #12
+	push.w	{r8, r9, r10}
+	mov	r10, r1
+	mov	r6, r0
+	bl	_critical_section_1_0_acquire
+	movw	r8, :lower16:_ZN16critical_section5state13NESTING_DEPTHE
+	movw	r1, :lower16:_ZN16critical_section5state9WATERMARKE
+	movt	r8, :upper16:_ZN16critical_section5state13NESTING_DEPTHE
+	mov	r4, r0
+	ldr.w	r0, [r8]
+	movt	r1, :upper16:_ZN16critical_section5state9WATERMARKE
+	ldr	r2, [r1]
+	movw	r9, :lower16:_ZN16critical_section5state9OUTERMOSTE
+	adds	r5, r0, #1
+	str.w	r5, [r8]
+	cmp	r5, r2
+	it	hi
+	strhi	r5, [r1]
+	cmp	r0, #0
+	mov	r0, r6
+	movt	r9, :upper16:_ZN16critical_section5state9OUTERMOSTE
+	it	eq
+	streq.w	r4, [r9]
+	blx	r10
+	mov	r6, r0
+	ldr.w	r0, [r8]
+	cmp	r0, r5
+	beq	.LBB_3
+	cbz	r0, .LBB_4
+	bl	_critical_section_1_0_acquire
+	mov	r4, r0
+	bl	_critical_section_1_0_release
+	ldr.w	r5, [r8]
+.LBB_3:
+	subs	r0, r5, #1
+	str.w	r0, [r8]
+	ldr.w	r0, [r9]
+	it	ne
+	movne	r0, r4
+	bl	_critical_section_1_0_release
+	mov	r0, r6
+	pop.w	{r8, r9, r10}
+	pop	{r4, r5, r6, r7, pc}
+.LBB_4:
+	movw	r0, :lower16:.Lanon.4c72fd1adbcbba1023902656c9949811.0
+	movw	r2, :lower16:.Lanon.4c72fd1adbcbba1023902656c9949811.2
+	movt	r0, :upper16:.Lanon.4c72fd1adbcbba1023902656c9949811.0
+	movt	r2, :upper16:.Lanon.4c72fd1adbcbba1023902656c9949811.2
+	movs	r1, #51
+	bl	_RNvNtCs2fZl91Oq0qp_4core9panicking5panic
+
+_ZN16critical_section7with_fnE:
+	push	{r4, r5, r6, r7, lr}
+	add	r7, sp, #12
+	push.w	{r8, r9, r11}
+	mov	r5, r0
+	bl	_critical_section_1_0_acquire
+	movw	r9, :lower16:_ZN16critical_section5state13NESTING_DEPTHE
+	movw	r1, :lower16:_ZN16critical_section5state9WATERMARKE
+	movt	r9, :upper16:_ZN16critical_section5state13NESTING_DEPTHE
+	mov	r4, r0
+	ldr.w	r0, [r9]
+	movt	r1, :upper16:_ZN16critical_section5state9WATERMARKE
+	ldr	r2, [r1]
+	movw	r8, :lower16:_ZN16critical_section5state9OUTERMOSTE
+	adds	r6, r0, #1
+	str.w	r6, [r9]
+	cmp	r6, r2
+	it	hi
+	strhi	r6, [r1]
+	cmp	r0, #0
+	movt	r8, :upper16:_ZN16critical_section5state9OUTERMOSTE
+	it	eq
+	streq.w	r4, [r8]
+	blx	r5
+	mov	r5, r0
+	ldr.w	r0, [r9]
+	cmp	r0, r6
+	beq	.LBB_3
+	cbz	r0, .LBB_4
+	bl	_critical_section_1_0_acquire
+	mov	r4, r0
+	bl	_critical_section_1_0_release
+	ldr.w	r6, [r9]
+.LBB_3:
+	subs	r0, r6, #1
+	str.w	r0, [r9]
+	ldr.w	r0, [r8]
+	it	ne
+	movne	r0, r4
+	bl	_critical_section_1_0_release
+	mov	r0, r5
+	pop.w	{r8, r9, r11}
+	pop	{r4, r5, r6, r7, pc}
+.LBB_4:
+	movw	r0, :lower16:.Lanon.4c72fd1adbcbba1023902656c9949811.0
+	movw	r2, :lower16:.Lanon.4c72fd1adbcbba1023902656c9949811.2
+	movt	r0, :upper16:.Lanon.4c72fd1adbcbba1023902656c9949811.0
+	movt	r2, :upper16:.Lanon.4c72fd1adbcbba1023902656c9949811.2
+	movs	r1, #51
+	bl	_RNvNtCs2fZl91Oq0qp_4core9panicking5panic
+
+snapshot_enter:
+	push	{r7, lr}
+	mov	r7, sp
+	bl	_critical_section_1_0_acquire
+	movw	r1, :lower16:_ZN16critical_section5state13NESTING_DEPTHE
+	movw	r3, :lower16:_ZN16critical_section5state9WATERMARKE
+	movt	r1, :upper16:_ZN16critical_section5state13NESTING_DEPTHE
+	movt	r3, :upper16:_ZN16critical_section5state9WATERMARKE
+	ldr	r1, [r1]
+	ldr	r2, [r3]
+	add.w	r12, r1, #1
+	cmp	r12, r2
+	it	hi
+	strhi.w	r12, [r3]
+	cmp	r1, #0
+	ittt	eq
+	movweq	r1, :lower16:_ZN16critical_section5state9OUTERMOSTE
+	movteq	r1, :upper16:_ZN16critical_section5state9OUTERMOSTE
+	streq	r0, [r1]
+	movw	r1, :lower16:_ZN14bench_embedded7COUNTERE.0
+	movs	r2, #0
+	movt	r1, :upper16:_ZN14bench_embedded7COUNTERE.0
+	str	r2, [r1]
+	pop.w	{r7, lr}
+	b	_critical_section_1_0_release
+
+snapshot_with:
+	push	{r7, lr}
+	mov	r7, sp
+	bl	_critical_section_1_0_acquire
+	movw	r1, :lower16:_ZN16critical_section5state13NESTING_DEPTHE
+	movw	r3, :lower16:_ZN16critical_section5state9WATERMARKE
+	movt	r1, :upper16:_ZN16critical_section5state13NESTING_DEPTHE
+	movt	r3, :upper16:_ZN16critical_section5state9WATERMARKE
+	ldr	r1, [r1]
+	ldr	r2, [r3]
+	add.w	r12, r1, #1
+	cmp	r12, r2
+	it	hi
+	strhi.w	r12, [r3]
+	cmp	r1, #0
+	ittt	eq
+	movweq	r1, :lower16:_ZN16critical_section5state9OUTERMOSTE
+	movteq	r1, :upper16:_ZN16critical_section5state9OUTERMOSTE
+	streq	r0, [r1]
+	movw	r1, :lower16:_ZN14bench_embedded7COUNTERE.0
+	movt	r1, :upper16:_ZN14bench_embedded7COUNTERE.0
+	ldr	r2, [r1]
+	adds	r2, #1
+	str	r2, [r1]
+	pop.w	{r7, lr}
+	b	_critical_section_1_0_release
+
+snapshot_with_fn:
+	push	{r7, lr}
+	mov	r7, sp
+	movw	r0, :lower16:_ZN14bench_embedded9incrementE
+	movt	r0, :upper16:_ZN14bench_embedded9incrementE
+	pop.w	{r7, lr}
+	b	_ZN16critical_section7with_fnE
+
+snapshot_with_fn_arg:
+	push	{r7, lr}
+	mov	r7, sp
+	movw	r1, :lower16:_ZN14bench_embedded3addE
+	movt	r1, :upper16:_ZN14bench_embedded3addE
+	pop.w	{r7, lr}
+	b	_ZN16critical_section11with_fn_argE
+
@@ -0,0 +1,171 @@
+//! Overhead of the `cortex-m` implementation, measured with SysTick.
+//!
+//! Build with:
+//!
+//! ```text
+//! cargo build --release --example bench-embedded --features cortex-m,bench --target thumbv7m-none-eabi
+//! ```
+//!
+//! The example is linked with `examples/bench-embedded.x`, for a chip with flash at
+//! `0x00000000` and RAM at `0x20000000`, such as the LM3S6965 emulated by QEMU. Adjust the
+//! `MEMORY` regions for other chips. After running, the results are in `BENCH_REPORT`, in CPU
+//! cycles per call, and the core halts on a breakpoint, so they can be read with a debugger:
+//!
+//! ```text
+//! (gdb) print BENCH_REPORT
+//! ```
+//!
+//! The `snapshot_*` functions are the fast paths checked by `ci/asm-snapshot.sh`, along with
+//! the instances of `with_fn` and `with_fn_arg` they call.
+
+#![no_std]
+#![no_main]
+
+use core::arch::asm;
+use core::cell::Cell;
+use core::ptr;
+
+use critical_section::bench::Report;
+use critical_section::{CriticalSection, Mutex};
+
+const SYST_CSR: *mut u32 = 0xe000_e010 as *mut u32;
+const SYST_RVR: *mut u32 = 0xe000_e014 as *mut u32;
+const SYST_CVR: *mut u32 = 0xe000_e018 as *mut u32;
+
+#[no_mangle]
+static mut BENCH_REPORT: Report = Report {
+    with: 0,
+    nested_with: 0,
+    enter: 0,
+    with_fn: 0,
+    with_fn_arg: 0,
+};
+
+static COUNTER: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+
+#[no_mangle]
+#[inline(never)]
+pub fn snapshot_with() {
+    critical_section::with(|cs| COUNTER.borrow(cs).set(COUNTER.borrow(cs).get() + 1));
+}
+
+#[no_mangle]
+#[inline(never)]
+pub fn snapshot_enter() {
+    let guard = critical_section::enter();
+    COUNTER.borrow(guard.token()).set(0);
+}
+
+fn increment(cs: CriticalSection) -> u32 {
+    let counter = COUNTER.borrow(cs);
+    counter.set(counter.get() + 1);
+    counter.get()
+}
+
+fn add(cs: CriticalSection, n: u32) -> u32 {
+    let counter = COUNTER.borrow(cs);
+    counter.set(counter.get() + n);
+    counter.get()
+}
+
+fn sub(cs: CriticalSection, n: u32) -> u32 {
+    let counter = COUNTER.borrow(cs);
+    counter.set(counter.get() - n);
+    counter.get()
+}
+
+fn reset_counter(cs: CriticalSection) -> u32 {
+    COUNTER.borrow(cs).replace(0)
+}
+
+#[no_mangle]
+#[inline(never)]
+pub fn snapshot_with_fn() -> u32 {
+    critical_section::with_fn(increment)
+}
+
+#[no_mangle]
+#[inline(never)]
+pub fn snapshot_with_fn_arg(n: u32) -> u32 {
+    critical_section::with_fn_arg(n, add)
+}
+
+/// SysTick counts down from `0xffffff`. Shifting it turns it into a wrapping `u32` counter
+/// counting up in steps of 256.
+fn timestamp() -> u32 {
+    (0xff_ffff - unsafe { ptr::read_volatile(SYST_CVR) }) << 8
+}
+
+fn main() -> ! {
+    unsafe {
+        ptr::write_volatile(SYST_RVR, 0xff_ffff);
+        ptr::write_volatile(SYST_CVR, 0);
+        // Enable, clocked by the processor clock.
+        ptr::write_volatile(SYST_CSR, 0b101);
+    }
+
+    // Keep each batch well below the 2^24 cycles SysTick takes to wrap around.
+    let report = critical_section::bench::run(timestamp, 1000);
+    let cycles = |ticks: u32| ticks / 256;
+    unsafe {
+        ptr::write_volatile(
+            ptr::addr_of_mut!(BENCH_REPORT),
+            Report {
+                with: cycles(report.with),
+                nested_with: cycles(report.nested_with),
+                enter: cycles(report.enter),
+                with_fn: cycles(report.with_fn),
+                with_fn_arg: cycles(report.with_fn_arg),
+            },
+        );
+    }
+
+    snapshot_with();
+    snapshot_enter();
+    snapshot_with_fn();
+    snapshot_with_fn_arg(2);
+    // Pass other functions too, so the instances of `with_fn` and `with_fn_arg` are shared
+    // like in an application, instead of specialized for their only caller.
+    critical_section::with_fn(reset_counter);
+    critical_section::with_fn_arg(2, sub);
+
+    loop {
+        unsafe { asm!("bkpt") };
+    }
+}
+
+extern "C" {
+    static mut _sbss: u32;
+    static mut _ebss: u32;
+    static mut _sdata: u32;
+    static mut _edata: u32;
+    static _sidata: u32;
+}
+
+#[no_mangle]
+unsafe extern "C" fn reset() -> ! {
+    let mut bss = ptr::addr_of_mut!(_sbss);
+    while bss < ptr::addr_of_mut!(_ebss) {
+        ptr::write_volatile(bss, 0);
+        bss = bss.add(1);
+    }
+    let mut data = ptr::addr_of_mut!(_sdata);
+    let mut init = ptr::addr_of!(_sidata);
+    while data < ptr::addr_of_mut!(_edata) {
+        ptr::write_volatile(data, ptr::read(init));
+        data = data.add(1);
+        init = init.add(1);
+    }
+    main()
+}
+
+#[link_section = ".vector_table.reset"]
+#[no_mangle]
+static RESET_VECTOR: unsafe extern "C" fn() -> ! = reset;
+
+#[panic_handler]
+fn panic(_: &core::panic::PanicInfo) -> ! {
+    loop {
+        unsafe { asm!("bkpt") };
+    }
+}
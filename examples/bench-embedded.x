@@ -0,0 +1,39 @@
+/* Minimal linker script for the `bench-embedded` example. */
+MEMORY
+{
+  FLASH : ORIGIN = 0x00000000, LENGTH = 256K
+  RAM : ORIGIN = 0x20000000, LENGTH = 64K
+}
+
+ENTRY(reset);
+
+SECTIONS
+{
+  .vector_table ORIGIN(FLASH) :
+  {
+    LONG(ORIGIN(RAM) + LENGTH(RAM));
+    KEEP(*(.vector_table.reset));
+  } > FLASH
+
+  .text : { *(.text .text.*); } > FLASH
+  .rodata : { *(.rodata .rodata.*); } > FLASH
+
+  .data : ALIGN(4)
+  {
+    _sdata = .;
+    *(.data .data.*);
+    . = ALIGN(4);
+    _edata = .;
+  } > RAM AT > FLASH
+  _sidata = LOADADDR(.data);
+
+  .bss (NOLOAD) : ALIGN(4)
+  {
+    _sbss = .;
+    *(.bss .bss.*);
+    . = ALIGN(4);
+    _ebss = .;
+  } > RAM
+
+  /DISCARD/ : { *(.ARM.exidx .ARM.exidx.*); }
+}
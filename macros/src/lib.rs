@@ -0,0 +1,163 @@
+//! Procedural macros for the `critical-section` crate.
+//!
+//! Don't depend on this crate directly. Enable the `macros` Cargo feature of
+//! `critical-section` instead, which re-exports these macros.
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use syn::parse::Nothing;
+use syn::{parse_macro_input, Error, Ident, ItemFn, ItemStatic, StaticMutability};
+
+/// Run the whole function in a critical section.
+///
+/// The critical section is entered before the function body runs, and released when it
+/// returns, like wrapping the body in `critical_section::scope!`. Pass a name to bind the
+/// token to it:
+///
+/// ```ignore
+/// use critical_section::Mutex;
+/// use std::cell::Cell;
+///
+/// static TICKS: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+///
+/// #[critical_section::critical_section(cs)]
+/// fn tick() -> u32 {
+///     let ticks = TICKS.borrow(cs);
+///     ticks.set(ticks.get() + 1);
+///     ticks.get()
+/// }
+/// ```
+///
+/// Async and const functions are rejected. The expansion refers to the `critical_section`
+/// crate by name, so it can't be renamed in `Cargo.toml`.
+#[proc_macro_attribute]
+pub fn critical_section(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let token = if attr.is_empty() {
+        None
+    } else {
+        Some(parse_macro_input!(attr as Ident))
+    };
+    let item = parse_macro_input!(item as ItemFn);
+
+    if let Some(asyncness) = item.sig.asyncness {
+        return Error::new_spanned(
+            asyncness,
+            "`#[critical_section]` can't be used on async functions, the critical section must \
+             not be held across `.await`",
+        )
+        .into_compile_error()
+        .into();
+    }
+    if let Some(constness) = item.sig.constness {
+        return Error::new_spanned(
+            constness,
+            "`#[critical_section]` can't be used on const functions",
+        )
+        .into_compile_error()
+        .into();
+    }
+
+    let ItemFn {
+        attrs,
+        vis,
+        sig,
+        block,
+    } = item;
+    // Not nameable from the function body.
+    let guard = Ident::new("__critical_section_guard", Span::mixed_site());
+    let token = token.map(|token| quote!(let #token = #guard.token();));
+    let stmts = &block.stmts;
+
+    quote! {
+        #(#attrs)*
+        #vis #sig {
+            let #guard = ::critical_section::enter();
+            #token
+            #(#stmts)*
+        }
+    }
+    .into()
+}
+
+/// Turn a `static` into one protected by the critical section, with accessor functions.
+///
+/// `#[protected] static NAME: T = value;` becomes a
+/// `static NAME: Mutex<RefCell<T>>`, along with two functions with the same visibility:
+///
+/// - `borrow_name(cs)` returns a `RefMut<T>` for the duration of the critical section, like
+///   `NAME.borrow_ref_mut(cs)`.
+/// - `with_name(f)` enters a critical section and runs `f` with a `&mut T`, like
+///   `NAME.lock_mut(f)`.
+///
+/// ```ignore
+/// use critical_section::protected;
+///
+/// #[protected]
+/// static RX_BUFFER: [u8; 64] = [0; 64];
+///
+/// fn on_rx_interrupt(byte: u8) {
+///     critical_section::with(|cs| borrow_rx_buffer(cs)[0] = byte);
+/// }
+///
+/// fn first_byte() -> u8 {
+///     with_rx_buffer(|buffer| buffer[0])
+/// }
+/// ```
+///
+/// `static mut` is rejected. Like `#[critical_section]`, the expansion refers to the
+/// `critical_section` crate by name.
+#[proc_macro_attribute]
+pub fn protected(attr: TokenStream, item: TokenStream) -> TokenStream {
+    parse_macro_input!(attr as Nothing);
+    let item = parse_macro_input!(item as ItemStatic);
+
+    if let StaticMutability::Mut(mutability) = item.mutability {
+        return Error::new_spanned(mutability, "`#[protected]` can't be used on `static mut`")
+            .into_compile_error()
+            .into();
+    }
+
+    let ItemStatic {
+        attrs,
+        vis,
+        ident,
+        ty,
+        expr,
+        ..
+    } = item;
+    let name = ident.to_string().to_lowercase();
+    let borrow = Ident::new(&format!("borrow_{}", name), ident.span());
+    let with = Ident::new(&format!("with_{}", name), ident.span());
+    let borrow_doc = format!(
+        "Borrows [`{}`] mutably for the duration of the critical section.",
+        ident
+    );
+    let with_doc = format!(
+        "Executes closure `f` with [`{}`] borrowed mutably, in a critical section entered just \
+         for it.",
+        ident
+    );
+
+    quote! {
+        #(#attrs)*
+        #vis static #ident: ::critical_section::Mutex<::core::cell::RefCell<#ty>> =
+            ::critical_section::Mutex::new(::core::cell::RefCell::new(#expr));
+
+        #[doc = #borrow_doc]
+        #[inline]
+        #[track_caller]
+        #vis fn #borrow<'cs>(
+            cs: ::critical_section::CriticalSection<'cs>,
+        ) -> ::core::cell::RefMut<'cs, #ty> {
+            #ident.borrow_ref_mut(cs)
+        }
+
+        #[doc = #with_doc]
+        #[inline]
+        #vis fn #with<R>(f: impl ::core::ops::FnOnce(&mut #ty) -> R) -> R {
+            #ident.lock_mut(f)
+        }
+    }
+    .into()
+}
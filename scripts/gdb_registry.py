@@ -0,0 +1,40 @@
+"""GDB command listing the mutexes in the critical-section debug registry.
+
+Load it with `source scripts/gdb_registry.py`, then run `cs-registry`. Requires an
+application built with the `debug-registry` feature of critical-section. See the
+`registry` module docs for the layout of the entries.
+"""
+
+import gdb
+
+
+class CsRegistry(gdb.Command):
+    """List the mutexes registered with critical_section::registry::register!."""
+
+    def __init__(self):
+        super().__init__("cs-registry", gdb.COMMAND_DATA)
+
+    def invoke(self, arg, from_tty):
+        inferior = gdb.selected_inferior()
+        word = gdb.lookup_type("void").pointer().sizeof
+        order = "little" if "little" in gdb.execute("show endian", to_string=True) else "big"
+
+        def read(address, size):
+            return int.from_bytes(bytes(inferior.read_memory(address, size)), order)
+
+        def read_str(address, length):
+            return bytes(inferior.read_memory(address, length)).decode("utf-8", "replace")
+
+        head = gdb.parse_and_eval("&CRITICAL_SECTION_REGISTRY")
+        entry = read(int(head.cast(gdb.lookup_type("long").unsigned)), word)
+        while entry:
+            words = [read(entry + i * word, word) for i in range(7)]
+            line = read(entry + 7 * word, 4)
+            name = read_str(words[1], words[2])
+            file = read_str(words[5], words[6])
+            print("{} at {:#x}, {} bytes, registered at {}:{}".format(
+                name, words[3], words[4], file, line))
+            entry = words[0]
+
+
+CsRegistry()
@@ -0,0 +1,189 @@
+//! Async primitives built on critical sections.
+//!
+//! These only use the critical section for short bookkeeping, and register a [`Waker`]
+//! instead of blocking. They work with any executor.
+
+use core::cell::{RefCell, UnsafeCell};
+use core::future::poll_fn;
+use core::marker::PhantomData;
+use core::ops::{Deref, DerefMut};
+use core::task::{Poll, Waker};
+
+/// An async mutex.
+///
+/// Unlike [`crate::Mutex`], this provides `&mut T` access, and the data can be held across
+/// `.await` points. Waiting tasks are woken when the mutex is unlocked.
+///
+/// Up to `WAITERS` waiting tasks, 4 by default, are registered, and all of them are woken on
+/// unlock, to race for the mutex again. Beyond that, a new waiting task takes the place of the
+/// oldest one, which is woken to register again, so they keep waking each other up until the
+/// mutex is unlocked: pick `WAITERS` to cover the tasks that can wait at the same time.
+///
+/// ```no_run
+/// use critical_section::asynch::Mutex;
+///
+/// static COUNTER: Mutex<u32> = Mutex::new(0);
+///
+/// async fn increment() {
+///     let mut counter = COUNTER.lock().await;
+///     *counter += 1;
+/// }
+/// ```
+pub struct Mutex<T: ?Sized, const WAITERS: usize = 4> {
+    state: crate::Mutex<RefCell<State<WAITERS>>>,
+    inner: UnsafeCell<T>,
+}
+
+struct State<const WAITERS: usize> {
+    locked: bool,
+    /// Registered waiting tasks, in the order they registered, followed by `None`s.
+    wakers: [Option<Waker>; WAITERS],
+}
+
+const NO_WAKER: Option<Waker> = None;
+
+impl<const WAITERS: usize> State<WAITERS> {
+    /// Register `waker`, returning the waker of the oldest waiting task if it had to make room.
+    fn register(&mut self, waker: &Waker) -> Option<Waker> {
+        if self.wakers.iter().flatten().any(|w| w.will_wake(waker)) {
+            return None;
+        }
+        if let Some(slot) = self.wakers.iter_mut().find(|slot| slot.is_none()) {
+            *slot = Some(waker.clone());
+            return None;
+        }
+        if WAITERS == 0 {
+            // Without room for any waker, the task has to poll again.
+            return Some(waker.clone());
+        }
+        let oldest = self.wakers[0].take();
+        self.wakers.rotate_left(1);
+        self.wakers[WAITERS - 1] = Some(waker.clone());
+        oldest
+    }
+}
+
+/// Error returned by [`Mutex::try_lock`] when the mutex is already locked.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TryLockError;
+
+impl core::fmt::Display for TryLockError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("mutex already locked")
+    }
+}
+
+unsafe impl<T: ?Sized + Send, const WAITERS: usize> Send for Mutex<T, WAITERS> {}
+unsafe impl<T: ?Sized + Send, const WAITERS: usize> Sync for Mutex<T, WAITERS> {}
+
+impl<T, const WAITERS: usize> Mutex<T, WAITERS> {
+    /// Creates a new mutex.
+    #[inline]
+    pub const fn new(value: T) -> Self {
+        Mutex {
+            state: crate::Mutex::new(RefCell::new(State {
+                locked: false,
+                wakers: [NO_WAKER; WAITERS],
+            })),
+            inner: UnsafeCell::new(value),
+        }
+    }
+
+    /// Unwraps the contained value, consuming the mutex.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.inner.into_inner()
+    }
+}
+
+impl<T: ?Sized, const WAITERS: usize> Mutex<T, WAITERS> {
+    /// Locks the mutex, waiting until it's available.
+    pub async fn lock(&self) -> MutexGuard<'_, T, WAITERS> {
+        poll_fn(|cx| {
+            let result = crate::with(|cs| {
+                let mut state = self.state.borrow_ref_mut(cs);
+                if state.locked {
+                    // A task that has to make room is woken instead of dropping its waker, so
+                    // it's never left waiting forever.
+                    Err(state.register(cx.waker()))
+                } else {
+                    state.locked = true;
+                    Ok(MutexGuard {
+                        mutex: self,
+                        _marker: PhantomData,
+                    })
+                }
+            });
+            match result {
+                Ok(guard) => Poll::Ready(guard),
+                Err(displaced) => {
+                    if let Some(waker) = displaced {
+                        waker.wake();
+                    }
+                    Poll::Pending
+                }
+            }
+        })
+        .await
+    }
+
+    /// Locks the mutex if it's available, without waiting.
+    pub fn try_lock(&self) -> Result<MutexGuard<'_, T, WAITERS>, TryLockError> {
+        crate::with(|cs| {
+            let mut state = self.state.borrow_ref_mut(cs);
+            if state.locked {
+                Err(TryLockError)
+            } else {
+                state.locked = true;
+                Ok(MutexGuard {
+                    mutex: self,
+                    _marker: PhantomData,
+                })
+            }
+        })
+    }
+
+    /// Gets a mutable reference to the contained value when the mutex is already uniquely borrowed.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.inner.get_mut()
+    }
+}
+
+/// Guard of a locked [`Mutex`], unlocking it when dropped.
+pub struct MutexGuard<'a, T: ?Sized, const WAITERS: usize = 4> {
+    mutex: &'a Mutex<T, WAITERS>,
+    // `&T` is handed out from a shared `&MutexGuard`, so this must only be `Sync` for `T: Sync`.
+    _marker: PhantomData<&'a mut T>,
+}
+
+impl<T: ?Sized, const WAITERS: usize> Drop for MutexGuard<'_, T, WAITERS> {
+    fn drop(&mut self) {
+        // All the waiting tasks are woken, rather than only the oldest one, since they may
+        // have given up waiting.
+        let wakers = crate::with(|cs| {
+            let mut state = self.mutex.state.borrow_ref_mut(cs);
+            state.locked = false;
+            core::mem::replace(&mut state.wakers, [NO_WAKER; WAITERS])
+        });
+        for waker in wakers.iter().flatten() {
+            waker.wake_by_ref();
+        }
+    }
+}
+
+impl<T: ?Sized, const WAITERS: usize> Deref for MutexGuard<'_, T, WAITERS> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        unsafe { &*self.mutex.inner.get() }
+    }
+}
+
+impl<T: ?Sized, const WAITERS: usize> DerefMut for MutexGuard<'_, T, WAITERS> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.mutex.inner.get() }
+    }
+}
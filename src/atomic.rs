@@ -0,0 +1,522 @@
+//! Atomic types implemented with the global critical section.
+//!
+//! These mirror the types in [`core::sync::atomic`], for targets without native atomic
+//! read-modify-write operations, such as Cortex-M0 or RISC-V without the A extension. Every
+//! operation runs inside a critical section, so they're atomic with respect to everything
+//! else using the critical section, including other cores if the implementation covers them.
+//!
+//! ```no_run
+//! use critical_section::atomic::{AtomicU32, Ordering};
+//!
+//! static COUNTER: AtomicU32 = AtomicU32::new(0);
+//!
+//! fn on_tick() {
+//!     COUNTER.fetch_add(1, Ordering::Relaxed);
+//! }
+//! ```
+//!
+//! The [`Ordering`] arguments are accepted for compatibility, and ignored: acquiring and
+//! releasing the critical section already orders memory at least as strongly as
+//! [`Ordering::SeqCst`]. Unlike `portable-atomic`, the types are never lock-free, even on
+//! targets with native atomics.
+//!
+//! Only accesses through these types are synchronized. Mixing them with native atomics
+//! accessing the same memory, for example through [`as_ptr`](AtomicU32::as_ptr), is not.
+
+use core::cell::UnsafeCell;
+use core::fmt;
+
+pub use core::sync::atomic::Ordering;
+
+use super::with;
+
+macro_rules! atomic_int {
+    ($(#[$attr:meta])* $atomic:ident, $int:ty) => {
+        $(#[$attr])*
+        #[repr(transparent)]
+        pub struct $atomic {
+            value: UnsafeCell<$int>,
+        }
+
+        // NOTE The value is only accessed inside the critical section.
+        unsafe impl Sync for $atomic {}
+
+        impl $atomic {
+            /// Creates a new atomic integer.
+            #[inline]
+            pub const fn new(value: $int) -> Self {
+                $atomic {
+                    value: UnsafeCell::new(value),
+                }
+            }
+
+            /// Returns a mutable reference to the value when the atomic is already uniquely
+            /// borrowed.
+            #[inline]
+            pub fn get_mut(&mut self) -> &mut $int {
+                self.value.get_mut()
+            }
+
+            /// Unwraps the value, consuming the atomic.
+            #[inline]
+            pub fn into_inner(self) -> $int {
+                self.value.into_inner()
+            }
+
+            /// Returns a raw pointer to the value.
+            #[inline]
+            pub const fn as_ptr(&self) -> *mut $int {
+                self.value.get()
+            }
+
+            #[inline]
+            fn update<R>(&self, f: impl FnOnce(&mut $int) -> R) -> R {
+                with(|_| f(unsafe { &mut *self.value.get() }))
+            }
+
+            /// Loads the value.
+            #[inline]
+            pub fn load(&self, _order: Ordering) -> $int {
+                self.update(|value| *value)
+            }
+
+            /// Stores `value`.
+            #[inline]
+            pub fn store(&self, value: $int, _order: Ordering) {
+                self.update(|v| *v = value)
+            }
+
+            /// Stores `value`, returning the previous value.
+            #[inline]
+            pub fn swap(&self, value: $int, _order: Ordering) -> $int {
+                self.update(|v| core::mem::replace(v, value))
+            }
+
+            /// Stores `new` if the current value is `current`.
+            ///
+            /// Returns the previous value, in `Ok` if it was replaced, and in `Err` if it
+            /// wasn't.
+            #[inline]
+            pub fn compare_exchange(
+                &self,
+                current: $int,
+                new: $int,
+                _success: Ordering,
+                _failure: Ordering,
+            ) -> Result<$int, $int> {
+                self.update(|v| {
+                    if *v == current {
+                        *v = new;
+                        Ok(current)
+                    } else {
+                        Err(*v)
+                    }
+                })
+            }
+
+            /// Same as [`compare_exchange`](Self::compare_exchange), which never fails
+            /// spuriously.
+            #[inline]
+            pub fn compare_exchange_weak(
+                &self,
+                current: $int,
+                new: $int,
+                success: Ordering,
+                failure: Ordering,
+            ) -> Result<$int, $int> {
+                self.compare_exchange(current, new, success, failure)
+            }
+
+            /// Adds to the current value, wrapping around on overflow, and returns the
+            /// previous value.
+            #[inline]
+            pub fn fetch_add(&self, value: $int, _order: Ordering) -> $int {
+                self.update(|v| core::mem::replace(v, v.wrapping_add(value)))
+            }
+
+            /// Subtracts from the current value, wrapping around on overflow, and returns the
+            /// previous value.
+            #[inline]
+            pub fn fetch_sub(&self, value: $int, _order: Ordering) -> $int {
+                self.update(|v| core::mem::replace(v, v.wrapping_sub(value)))
+            }
+
+            /// Bitwise "and" with the current value, returning the previous value.
+            #[inline]
+            pub fn fetch_and(&self, value: $int, _order: Ordering) -> $int {
+                self.update(|v| core::mem::replace(v, *v & value))
+            }
+
+            /// Bitwise "nand" with the current value, returning the previous value.
+            #[inline]
+            pub fn fetch_nand(&self, value: $int, _order: Ordering) -> $int {
+                self.update(|v| core::mem::replace(v, !(*v & value)))
+            }
+
+            /// Bitwise "or" with the current value, returning the previous value.
+            #[inline]
+            pub fn fetch_or(&self, value: $int, _order: Ordering) -> $int {
+                self.update(|v| core::mem::replace(v, *v | value))
+            }
+
+            /// Bitwise "xor" with the current value, returning the previous value.
+            #[inline]
+            pub fn fetch_xor(&self, value: $int, _order: Ordering) -> $int {
+                self.update(|v| core::mem::replace(v, *v ^ value))
+            }
+
+            /// Stores the maximum of the current value and `value`, returning the previous
+            /// value.
+            #[inline]
+            pub fn fetch_max(&self, value: $int, _order: Ordering) -> $int {
+                self.update(|v| core::mem::replace(v, (*v).max(value)))
+            }
+
+            /// Stores the minimum of the current value and `value`, returning the previous
+            /// value.
+            #[inline]
+            pub fn fetch_min(&self, value: $int, _order: Ordering) -> $int {
+                self.update(|v| core::mem::replace(v, (*v).min(value)))
+            }
+
+            /// Stores the value returned by `f`, if it returns `Some`, returning the previous
+            /// value.
+            ///
+            /// Unlike with native atomics, `f` is called exactly once, inside the critical
+            /// section.
+            #[inline]
+            pub fn fetch_update(
+                &self,
+                _set_order: Ordering,
+                _fetch_order: Ordering,
+                f: impl FnOnce($int) -> Option<$int>,
+            ) -> Result<$int, $int> {
+                self.update(|v| {
+                    let prev = *v;
+                    match f(prev) {
+                        Some(new) => {
+                            *v = new;
+                            Ok(prev)
+                        }
+                        None => Err(prev),
+                    }
+                })
+            }
+        }
+
+        impl Default for $atomic {
+            #[inline]
+            fn default() -> Self {
+                Self::new(Default::default())
+            }
+        }
+
+        impl From<$int> for $atomic {
+            #[inline]
+            fn from(value: $int) -> Self {
+                Self::new(value)
+            }
+        }
+
+        impl fmt::Debug for $atomic {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                self.load(Ordering::Relaxed).fmt(f)
+            }
+        }
+    };
+}
+
+atomic_int!(
+    /// An integer type which can be safely shared between threads, see the [module docs](self).
+    AtomicU8, u8
+);
+atomic_int!(
+    /// An integer type which can be safely shared between threads, see the [module docs](self).
+    AtomicI8, i8
+);
+atomic_int!(
+    /// An integer type which can be safely shared between threads, see the [module docs](self).
+    AtomicU16, u16
+);
+atomic_int!(
+    /// An integer type which can be safely shared between threads, see the [module docs](self).
+    AtomicI16, i16
+);
+atomic_int!(
+    /// An integer type which can be safely shared between threads, see the [module docs](self).
+    AtomicU32, u32
+);
+atomic_int!(
+    /// An integer type which can be safely shared between threads, see the [module docs](self).
+    AtomicI32, i32
+);
+atomic_int!(
+    /// An integer type which can be safely shared between threads, see the [module docs](self).
+    AtomicU64, u64
+);
+atomic_int!(
+    /// An integer type which can be safely shared between threads, see the [module docs](self).
+    AtomicI64, i64
+);
+atomic_int!(
+    /// An integer type which can be safely shared between threads, see the [module docs](self).
+    AtomicUsize, usize
+);
+atomic_int!(
+    /// An integer type which can be safely shared between threads, see the [module docs](self).
+    AtomicIsize, isize
+);
+
+/// A boolean type which can be safely shared between threads, see the [module docs](self).
+#[repr(transparent)]
+pub struct AtomicBool {
+    value: UnsafeCell<bool>,
+}
+
+// NOTE The value is only accessed inside the critical section.
+unsafe impl Sync for AtomicBool {}
+
+impl AtomicBool {
+    /// Creates a new atomic boolean.
+    #[inline]
+    pub const fn new(value: bool) -> Self {
+        AtomicBool {
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    /// Returns a mutable reference to the value when the atomic is already uniquely borrowed.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut bool {
+        self.value.get_mut()
+    }
+
+    /// Unwraps the value, consuming the atomic.
+    #[inline]
+    pub fn into_inner(self) -> bool {
+        self.value.into_inner()
+    }
+
+    /// Returns a raw pointer to the value.
+    #[inline]
+    pub const fn as_ptr(&self) -> *mut bool {
+        self.value.get()
+    }
+
+    #[inline]
+    fn update<R>(&self, f: impl FnOnce(&mut bool) -> R) -> R {
+        with(|_| f(unsafe { &mut *self.value.get() }))
+    }
+
+    /// Loads the value.
+    #[inline]
+    pub fn load(&self, _order: Ordering) -> bool {
+        self.update(|value| *value)
+    }
+
+    /// Stores `value`.
+    #[inline]
+    pub fn store(&self, value: bool, _order: Ordering) {
+        self.update(|v| *v = value)
+    }
+
+    /// Stores `value`, returning the previous value.
+    #[inline]
+    pub fn swap(&self, value: bool, _order: Ordering) -> bool {
+        self.update(|v| core::mem::replace(v, value))
+    }
+
+    /// Stores `new` if the current value is `current`.
+    ///
+    /// Returns the previous value, in `Ok` if it was replaced, and in `Err` if it wasn't.
+    #[inline]
+    pub fn compare_exchange(
+        &self,
+        current: bool,
+        new: bool,
+        _success: Ordering,
+        _failure: Ordering,
+    ) -> Result<bool, bool> {
+        self.update(|v| {
+            if *v == current {
+                *v = new;
+                Ok(current)
+            } else {
+                Err(*v)
+            }
+        })
+    }
+
+    /// Same as [`compare_exchange`](Self::compare_exchange), which never fails spuriously.
+    #[inline]
+    pub fn compare_exchange_weak(
+        &self,
+        current: bool,
+        new: bool,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<bool, bool> {
+        self.compare_exchange(current, new, success, failure)
+    }
+
+    /// Logical "and" with the current value, returning the previous value.
+    #[inline]
+    pub fn fetch_and(&self, value: bool, _order: Ordering) -> bool {
+        self.update(|v| core::mem::replace(v, *v & value))
+    }
+
+    /// Logical "nand" with the current value, returning the previous value.
+    #[inline]
+    pub fn fetch_nand(&self, value: bool, _order: Ordering) -> bool {
+        self.update(|v| core::mem::replace(v, !(*v & value)))
+    }
+
+    /// Logical "or" with the current value, returning the previous value.
+    #[inline]
+    pub fn fetch_or(&self, value: bool, _order: Ordering) -> bool {
+        self.update(|v| core::mem::replace(v, *v | value))
+    }
+
+    /// Logical "xor" with the current value, returning the previous value.
+    #[inline]
+    pub fn fetch_xor(&self, value: bool, _order: Ordering) -> bool {
+        self.update(|v| core::mem::replace(v, *v ^ value))
+    }
+
+    /// Logical "not" of the current value, returning the previous value.
+    #[inline]
+    pub fn fetch_not(&self, _order: Ordering) -> bool {
+        self.update(|v| core::mem::replace(v, !*v))
+    }
+}
+
+impl Default for AtomicBool {
+    #[inline]
+    fn default() -> Self {
+        Self::new(false)
+    }
+}
+
+impl From<bool> for AtomicBool {
+    #[inline]
+    fn from(value: bool) -> Self {
+        Self::new(value)
+    }
+}
+
+impl fmt::Debug for AtomicBool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.load(Ordering::Relaxed).fmt(f)
+    }
+}
+
+/// A raw pointer type which can be safely shared between threads, see the
+/// [module docs](self).
+#[repr(transparent)]
+pub struct AtomicPtr<T> {
+    value: UnsafeCell<*mut T>,
+}
+
+// NOTE The pointer is only accessed inside the critical section, like `core`'s `AtomicPtr`,
+// this places no bounds on `T`.
+unsafe impl<T> Send for AtomicPtr<T> {}
+unsafe impl<T> Sync for AtomicPtr<T> {}
+
+impl<T> AtomicPtr<T> {
+    /// Creates a new atomic pointer.
+    #[inline]
+    pub const fn new(value: *mut T) -> Self {
+        AtomicPtr {
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    /// Returns a mutable reference to the pointer when the atomic is already uniquely
+    /// borrowed.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut *mut T {
+        self.value.get_mut()
+    }
+
+    /// Unwraps the pointer, consuming the atomic.
+    #[inline]
+    pub fn into_inner(self) -> *mut T {
+        self.value.into_inner()
+    }
+
+    #[inline]
+    fn update<R>(&self, f: impl FnOnce(&mut *mut T) -> R) -> R {
+        with(|_| f(unsafe { &mut *self.value.get() }))
+    }
+
+    /// Loads the pointer.
+    #[inline]
+    pub fn load(&self, _order: Ordering) -> *mut T {
+        self.update(|value| *value)
+    }
+
+    /// Stores `value`.
+    #[inline]
+    pub fn store(&self, value: *mut T, _order: Ordering) {
+        self.update(|v| *v = value)
+    }
+
+    /// Stores `value`, returning the previous pointer.
+    #[inline]
+    pub fn swap(&self, value: *mut T, _order: Ordering) -> *mut T {
+        self.update(|v| core::mem::replace(v, value))
+    }
+
+    /// Stores `new` if the current pointer is `current`.
+    ///
+    /// Returns the previous pointer, in `Ok` if it was replaced, and in `Err` if it wasn't.
+    #[inline]
+    pub fn compare_exchange(
+        &self,
+        current: *mut T,
+        new: *mut T,
+        _success: Ordering,
+        _failure: Ordering,
+    ) -> Result<*mut T, *mut T> {
+        self.update(|v| {
+            if *v == current {
+                *v = new;
+                Ok(current)
+            } else {
+                Err(*v)
+            }
+        })
+    }
+
+    /// Same as [`compare_exchange`](Self::compare_exchange), which never fails spuriously.
+    #[inline]
+    pub fn compare_exchange_weak(
+        &self,
+        current: *mut T,
+        new: *mut T,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<*mut T, *mut T> {
+        self.compare_exchange(current, new, success, failure)
+    }
+}
+
+impl<T> Default for AtomicPtr<T> {
+    #[inline]
+    fn default() -> Self {
+        Self::new(core::ptr::null_mut())
+    }
+}
+
+impl<T> From<*mut T> for AtomicPtr<T> {
+    #[inline]
+    fn from(value: *mut T) -> Self {
+        Self::new(value)
+    }
+}
+
+impl<T> fmt::Debug for AtomicPtr<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.load(Ordering::Relaxed).fmt(f)
+    }
+}
@@ -0,0 +1,28 @@
+use core::arch::asm;
+
+/// I/O address of the status register.
+const SREG: u8 = 0x3F;
+/// Global interrupt enable bit of `SREG`.
+const SREG_I: u8 = 1 << 7;
+
+struct AvrCriticalSection;
+crate::set_impl!(AvrCriticalSection);
+
+unsafe impl crate::Impl for AvrCriticalSection {
+    #[inline(always)]
+    unsafe fn acquire() -> u8 {
+        let sreg: u8;
+        asm!("in {}, {sreg}", out(reg) sreg, sreg = const SREG, options(nomem, nostack, preserves_flags));
+        // No `nomem`, so this also acts as a compiler fence.
+        asm!("cli", options(nostack));
+        sreg
+    }
+
+    #[inline(always)]
+    unsafe fn release(sreg: u8) {
+        // Only re-enable interrupts if they were enabled before the critical section.
+        if sreg & SREG_I != 0 {
+            asm!("sei", options(nostack));
+        }
+    }
+}
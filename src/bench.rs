@@ -0,0 +1,63 @@
+//! Measuring the overhead of the critical section.
+//!
+//! This is used by the benchmarks in `benches/` and the `bench-embedded` example, and isn't
+//! part of the public API.
+
+use core::hint::black_box;
+
+use super::{enter, with, with_fn, with_fn_arg, CriticalSection};
+
+/// Overhead of each entry point, in timestamp ticks per call.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Report {
+    /// [`with`], entering the outermost critical section.
+    pub with: u32,
+    /// [`with`], nested in another critical section.
+    pub nested_with: u32,
+    /// [`enter`] and dropping the guard.
+    pub enter: u32,
+    /// [`with_fn`].
+    pub with_fn: u32,
+    /// [`with_fn_arg`].
+    pub with_fn_arg: u32,
+}
+
+/// Measures each entry point `batch` times in a row, and returns the average overhead per
+/// call, with the cost of the measuring loop subtracted.
+///
+/// `timestamp` should return a free-running counter, which may wrap around, but not more than
+/// once per batch.
+pub fn run(timestamp: fn() -> u32, batch: u32) -> Report {
+    let baseline = measure(timestamp, batch, || {});
+    let ticks = |f: &mut dyn FnMut()| measure(timestamp, batch, f).saturating_sub(baseline);
+
+    Report {
+        with: ticks(&mut || with(consume)),
+        nested_with: with(|_| ticks(&mut || with(consume))),
+        enter: ticks(&mut || consume(enter().token())),
+        with_fn: ticks(&mut || with_fn(consume)),
+        with_fn_arg: ticks(&mut || with_fn_arg(black_box(0u32), consume_arg)),
+    }
+}
+
+/// Returns the average time per call of `f`, in timestamp ticks.
+#[inline(never)]
+fn measure(timestamp: fn() -> u32, batch: u32, mut f: impl FnMut()) -> u32 {
+    let start = timestamp();
+    for _ in 0..batch {
+        f();
+    }
+    timestamp().wrapping_sub(start) / batch.max(1)
+}
+
+/// Keeps the compiler from optimizing the critical section away.
+#[inline(always)]
+fn consume(cs: CriticalSection) {
+    black_box(cs);
+}
+
+/// [`consume`], with an argument.
+#[inline(always)]
+fn consume_arg(cs: CriticalSection, arg: u32) {
+    black_box((cs, arg));
+}
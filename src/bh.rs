@@ -0,0 +1,116 @@
+//! Bottom halves: work scheduled from interrupt handlers and run later from the main loop.
+//!
+//! Interrupt handlers should do as little as possible with interrupts masked. This module
+//! splits the work like Linux soft-irqs: the interrupt handler (the top half) acknowledges the
+//! hardware and calls [`schedule`] inside its critical section, and [`poll`], called from the
+//! main loop or the idle task, runs the scheduled handlers (the bottom halves) with interrupts
+//! enabled.
+//!
+//! ```no_run
+//! use critical_section::bh;
+//!
+//! const RX: u8 = 0;
+//!
+//! fn process_rx() {
+//!     // Slow processing, running with interrupts enabled...
+//! }
+//!
+//! fn on_rx_interrupt() {
+//!     critical_section::with(|cs| {
+//!         // Acknowledge the interrupt...
+//!         bh::schedule(cs, RX);
+//!     });
+//! }
+//!
+//! fn main() {
+//!     bh::register(RX, process_rx);
+//!     loop {
+//!         bh::poll();
+//!         // Wait for an interrupt...
+//!     }
+//! }
+//! ```
+//!
+//! Each handler is identified by a number below [`MAX_HANDLERS`]. Scheduling a handler that is
+//! already pending does nothing, so it runs once for any number of `schedule` calls before it
+//! runs.
+
+use core::cell::{Cell, RefCell};
+
+use super::{with, CriticalSection, Mutex};
+
+/// Number of bottom half handlers that can be registered.
+pub const MAX_HANDLERS: usize = 32;
+
+type Handler = fn();
+
+static HANDLERS: Mutex<RefCell<[Option<Handler>; MAX_HANDLERS]>> =
+    Mutex::new(RefCell::new([None; MAX_HANDLERS]));
+/// Bit `n` is set when handler `n` is pending.
+static PENDING: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+
+#[inline]
+#[track_caller]
+fn bit(id: u8) -> u32 {
+    assert!(
+        usize::from(id) < MAX_HANDLERS,
+        "bottom half handler id out of range"
+    );
+    1 << id
+}
+
+/// Register `handler` as bottom half number `id`, replacing any previous one.
+///
+/// # Panics
+///
+/// Panics if `id` is not below [`MAX_HANDLERS`].
+#[track_caller]
+pub fn register(id: u8, handler: fn()) {
+    bit(id);
+    with(|cs| HANDLERS.borrow_ref_mut(cs)[usize::from(id)] = Some(handler));
+}
+
+/// Schedule bottom half number `id` to run on the next call to [`poll`].
+///
+/// # Panics
+///
+/// Panics if `id` is not below [`MAX_HANDLERS`].
+#[inline]
+#[track_caller]
+pub fn schedule(cs: CriticalSection, id: u8) {
+    let pending = PENDING.borrow(cs);
+    pending.set(pending.get() | bit(id));
+}
+
+/// Returns whether any bottom half is scheduled.
+#[inline]
+pub fn is_pending() -> bool {
+    with(|cs| PENDING.borrow(cs).get() != 0)
+}
+
+/// Run the scheduled bottom halves, returning whether any ran.
+///
+/// Handlers run outside of the critical section, lowest number first. Handlers scheduled
+/// while they run, including by the handlers themselves, are run before returning. Scheduled
+/// handlers that were never registered are skipped.
+///
+/// This must not be called inside a critical section, or the handlers would run with
+/// interrupts masked.
+pub fn poll() -> bool {
+    let mut ran = false;
+    loop {
+        let (pending, handlers) =
+            with(|cs| (PENDING.borrow(cs).replace(0), *HANDLERS.borrow_ref(cs)));
+        if pending == 0 {
+            return ran;
+        }
+        for (id, handler) in handlers.iter().enumerate() {
+            if pending & (1 << id) != 0 {
+                if let Some(handler) = handler {
+                    handler();
+                }
+            }
+        }
+        ran = true;
+    }
+}
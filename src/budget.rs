@@ -0,0 +1,151 @@
+use core::cell::Cell;
+
+use super::{enter, CriticalSection, Mutex};
+
+type OverrunHandler = fn(Overrun);
+type CycleCounter = fn() -> u32;
+
+/// Handler called when a critical section exceeds its budget.
+static ON_OVERRUN: Mutex<Cell<Option<OverrunHandler>>> = Mutex::new(Cell::new(None));
+/// Software cycle counter, used when the implementation has no hardware deadline.
+static CYCLE_COUNTER: Mutex<Cell<Option<CycleCounter>>> = Mutex::new(Cell::new(None));
+
+/// Details about a critical section that exceeded its budget, passed to the handler set with
+/// [`set_overrun_handler`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[non_exhaustive]
+pub struct Overrun {
+    /// The budget passed to [`with_budget`], in cycles.
+    pub budget: u32,
+    /// The cycles actually spent, if known. Hardware deadlines only report that the budget
+    /// was exceeded.
+    pub elapsed: Option<u32>,
+}
+
+/// Set the handler called when a critical section exceeds the budget given to [`with_budget`].
+///
+/// The handler runs inside the critical section, right before it's released.
+pub fn set_overrun_handler(handler: fn(Overrun)) {
+    crate::with(|cs| ON_OVERRUN.borrow(cs).set(Some(handler)));
+}
+
+/// Set a free-running cycle counter, used to check budgets when the critical section
+/// implementation has no hardware deadline.
+///
+/// The counter may wrap around. On Cortex-M, this is typically the DWT cycle counter.
+pub fn set_cycle_counter(counter: fn() -> u32) {
+    crate::with(|cs| CYCLE_COUNTER.borrow(cs).set(Some(counter)));
+}
+
+enum Deadline {
+    None,
+    Hardware,
+    Software { counter: CycleCounter, start: u32 },
+}
+
+/// Checks the deadline when dropped, still inside the critical section.
+struct Check {
+    budget: u32,
+    deadline: Deadline,
+}
+
+impl Drop for Check {
+    fn drop(&mut self) {
+        let overrun = match self.deadline {
+            Deadline::None => None,
+            Deadline::Hardware => unsafe { disarm_deadline() }.then_some(None),
+            Deadline::Software { counter, start } => {
+                let elapsed = counter().wrapping_sub(start);
+                (elapsed > self.budget).then_some(Some(elapsed))
+            }
+        };
+
+        if let Some(elapsed) = overrun {
+            let cs = unsafe { CriticalSection::new_unchecked() };
+            if let Some(handler) = ON_OVERRUN.borrow(cs).get() {
+                handler(Overrun {
+                    budget: self.budget,
+                    elapsed,
+                });
+            }
+        }
+    }
+}
+
+/// Execute closure `f` in a critical section that should not take longer than `cycles`.
+///
+/// If the critical section takes longer, the handler set with [`set_overrun_handler`] is
+/// called before releasing it. `f` is not interrupted, so this provides evidence that the
+/// critical section is bounded rather than enforcing it.
+///
+/// The deadline is armed through the optional
+/// [`Impl::arm_deadline`](crate::Impl::arm_deadline) hook, so implementations can use a
+/// hardware timer. If the implementation doesn't support that, the cycle counter set with
+/// [`set_cycle_counter`] is used instead. If neither is available, the budget isn't checked.
+///
+/// The budget only applies to the outermost critical section. When nested inside another
+/// critical section, this behaves like [`with`](crate::with), and the budget of the outer
+/// critical section covers `f`.
+///
+/// ```no_run
+/// use critical_section::{with_budget, Overrun};
+///
+/// fn on_overrun(overrun: Overrun) {
+///     // Record the overrun...
+/// #   let _ = overrun;
+/// }
+///
+/// critical_section::set_overrun_handler(on_overrun);
+///
+/// with_budget(500, |cs| {
+///     // Short work with interrupts disabled...
+/// #   let _ = cs;
+/// });
+/// ```
+#[inline]
+#[cfg_attr(
+    any(feature = "debug", feature = "defmt-trace", feature = "log-trace"),
+    track_caller
+)]
+pub fn with_budget<R>(cycles: u32, f: impl FnOnce(CriticalSection) -> R) -> R {
+    let guard = enter();
+    let deadline = if guard.depth > 1 {
+        Deadline::None
+    } else if unsafe { arm_deadline(cycles) } {
+        Deadline::Hardware
+    } else {
+        match CYCLE_COUNTER.borrow(guard.token()).get() {
+            Some(counter) => Deadline::Software {
+                counter,
+                start: counter(),
+            },
+            None => Deadline::None,
+        }
+    };
+
+    // Dropped before `guard`, so the deadline is checked inside the critical section.
+    let _check = Check {
+        budget: cycles,
+        deadline,
+    };
+    f(guard.token())
+}
+
+#[inline]
+unsafe fn arm_deadline(cycles: u32) -> bool {
+    extern "Rust" {
+        fn _critical_section_1_0_arm_deadline(cycles: u32) -> bool;
+    }
+
+    _critical_section_1_0_arm_deadline(cycles)
+}
+
+#[inline]
+unsafe fn disarm_deadline() -> bool {
+    extern "Rust" {
+        fn _critical_section_1_0_disarm_deadline() -> bool;
+    }
+
+    _critical_section_1_0_disarm_deadline()
+}
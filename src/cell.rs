@@ -0,0 +1,668 @@
+//! Cells whose access is gated by a critical section token.
+
+use core::cell::{Cell, UnsafeCell};
+use core::fmt;
+use core::mem;
+#[cfg(not(no_atomic_load_store))]
+use core::ptr;
+use core::sync::atomic::compiler_fence;
+use core::sync::atomic::Ordering;
+#[cfg(not(no_atomic_load_store))]
+use core::sync::atomic::{fence, AtomicUsize};
+
+use super::{CriticalSection, CsRead, CsWrite};
+
+/// A `Cell`-like container for `Copy` data shared between contexts.
+///
+/// This is a lighter alternative to `Mutex<Cell<T>>`: [`get`](SharedCell::get),
+/// [`set`](SharedCell::set) and [`update`](SharedCell::update) take a [`CriticalSection`]
+/// token directly, and compile down to plain loads and stores.
+///
+/// Like `Cell`, this never hands out references to the contained value, so nested critical
+/// sections and reentrant calls can't create aliasing references.
+///
+/// ```no_run
+/// use critical_section::{cell::SharedCell, CriticalSection};
+///
+/// static TICKS: SharedCell<u32> = SharedCell::new(0);
+///
+/// fn on_timer_interrupt(cs: CriticalSection) {
+///     TICKS.update(cs, |ticks| ticks.wrapping_add(1));
+/// }
+///
+/// // In the main loop.
+/// let ticks = critical_section::with(|cs| TICKS.get(cs));
+/// # let _ = ticks;
+/// ```
+#[derive(Debug)]
+pub struct SharedCell<T: Copy> {
+    value: UnsafeCell<T>,
+}
+
+// NOTE Same as for `Mutex`, the value moves between contexts, so it must be `Send`. It's only
+// accessed by copy inside the critical section, so it doesn't need to be `Sync`.
+unsafe impl<T: Copy + Send> Sync for SharedCell<T> {}
+
+impl<T: Copy> SharedCell<T> {
+    /// Creates a new cell containing `value`.
+    #[inline]
+    pub const fn new(value: T) -> Self {
+        SharedCell {
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    /// Returns a copy of the contained value.
+    #[inline]
+    pub fn get(&self, _cs: CriticalSection) -> T {
+        unsafe { *self.value.get() }
+    }
+
+    /// Sets the contained value.
+    #[inline]
+    pub fn set(&self, _cs: CriticalSection, value: T) {
+        unsafe { *self.value.get() = value }
+    }
+
+    /// Replaces the contained value with `value`, returning the old value.
+    #[inline]
+    pub fn replace(&self, cs: CriticalSection, value: T) -> T {
+        let old = self.get(cs);
+        self.set(cs, value);
+        old
+    }
+
+    /// Updates the contained value with `f`, returning the new value.
+    #[inline]
+    pub fn update(&self, cs: CriticalSection, f: impl FnOnce(T) -> T) -> T {
+        let new = f(self.get(cs));
+        self.set(cs, new);
+        new
+    }
+
+    /// Gets a mutable reference to the contained value when the cell is already uniquely
+    /// borrowed.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+
+    /// Unwraps the contained value, consuming the cell.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+}
+
+impl<T: Copy + Default> Default for SharedCell<T> {
+    #[inline]
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+/// A container for `Copy` data written in critical sections and read without one.
+///
+/// This is a sequence lock: [`write`](ReadMostly::write) needs the critical section, and
+/// bumps a sequence counter before and after writing, while [`read`](ReadMostly::read) copies
+/// the value without masking interrupts, and retries if the counter shows that a write
+/// happened in the meantime. This suits telemetry values updated from interrupt handlers and
+/// read often from the main loop.
+///
+/// ```no_run
+/// use critical_section::cell::ReadMostly;
+/// use critical_section::CriticalSection;
+///
+/// #[derive(Clone, Copy)]
+/// struct Sample {
+///     voltage: u16,
+///     current: u16,
+/// }
+///
+/// static LATEST: ReadMostly<Sample> = ReadMostly::new(Sample { voltage: 0, current: 0 });
+///
+/// fn on_adc_interrupt(cs: CriticalSection) {
+///     LATEST.write(cs, Sample { voltage: 3300, current: 120 });
+/// }
+///
+/// fn main_loop() {
+///     let sample = LATEST.read();
+///     // Both fields come from the same write.
+/// #   let _ = (sample.voltage, sample.current);
+/// }
+/// ```
+///
+/// Readers spin while a write is in progress on another core, and are delayed by writes that
+/// interrupt them, so writes should be short and not too frequent. On targets without atomic
+/// loads and stores, such as AVR, `read` enters the critical section instead.
+#[derive(Debug)]
+pub struct ReadMostly<T: Copy> {
+    /// Odd while a write is in progress.
+    #[cfg(not(no_atomic_load_store))]
+    seq: AtomicUsize,
+    value: UnsafeCell<T>,
+}
+
+// NOTE Same as for `SharedCell`, the value is only accessed by copy.
+unsafe impl<T: Copy + Send> Sync for ReadMostly<T> {}
+
+impl<T: Copy> ReadMostly<T> {
+    /// Creates a new container holding `value`.
+    #[inline]
+    pub const fn new(value: T) -> Self {
+        ReadMostly {
+            #[cfg(not(no_atomic_load_store))]
+            seq: AtomicUsize::new(0),
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    /// Returns a copy of the value, without entering the critical section.
+    #[inline]
+    pub fn read(&self) -> T {
+        #[cfg(not(no_atomic_load_store))]
+        loop {
+            let before = self.seq.load(Ordering::Acquire);
+            if before & 1 == 0 {
+                // This may race with a writer on another core. Like other sequence locks, the
+                // copy is only used if the counter shows that no write overlapped it, and the
+                // volatile read keeps the compiler from assuming anything about it.
+                let value = unsafe { ptr::read_volatile(self.value.get()) };
+                fence(Ordering::Acquire);
+                if self.seq.load(Ordering::Relaxed) == before {
+                    return value;
+                }
+            }
+            core::hint::spin_loop();
+        }
+
+        #[cfg(no_atomic_load_store)]
+        crate::with(|cs| self.get(cs))
+    }
+
+    /// Returns a copy of the value, in a critical section.
+    ///
+    /// Writers are excluded by the critical section, so this never retries.
+    #[inline]
+    pub fn get<'cs>(&self, _cs: impl Into<CsRead<'cs>>) -> T {
+        unsafe { *self.value.get() }
+    }
+
+    /// Replaces the value.
+    #[inline]
+    pub fn write<'cs>(&self, _cs: impl Into<CsWrite<'cs>>, value: T) {
+        // Other writers are excluded by the critical section, so the counter only needs loads
+        // and stores.
+        #[cfg(not(no_atomic_load_store))]
+        {
+            let seq = self.seq.load(Ordering::Relaxed);
+            self.seq.store(seq.wrapping_add(1), Ordering::Relaxed);
+            fence(Ordering::Release);
+            unsafe { ptr::write_volatile(self.value.get(), value) };
+            self.seq.store(seq.wrapping_add(2), Ordering::Release);
+        }
+
+        #[cfg(no_atomic_load_store)]
+        unsafe {
+            *self.value.get() = value
+        }
+    }
+
+    /// Updates the value with `f`, returning the new value.
+    #[inline]
+    pub fn update<'cs>(&self, cs: impl Into<CsWrite<'cs>>, f: impl FnOnce(T) -> T) -> T {
+        let cs = cs.into();
+        let new = f(self.get(cs));
+        self.write(cs, new);
+        new
+    }
+
+    /// Gets a mutable reference to the value when the container is already uniquely borrowed.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+
+    /// Unwraps the value, consuming the container.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+}
+
+impl<T: Copy + Default> Default for ReadMostly<T> {
+    #[inline]
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+enum Slot<T> {
+    Empty,
+    Full(T),
+    /// Temporarily taken out by [`TakeCell::with_taken`].
+    Lent,
+}
+
+/// A cell for moving a value between contexts, such as handing a peripheral to an interrupt
+/// handler.
+///
+/// The value is [`put`](TakeCell::put) in from one side, and [`take`](TakeCell::take)n
+/// out, or used in place with [`with_taken`](TakeCell::with_taken), from the other. Only one
+/// side holds the value at a time, checked at runtime.
+///
+/// ```no_run
+/// use critical_section::cell::TakeCell;
+///
+/// # struct Uart;
+/// # impl Uart { fn read(&mut self) -> u8 { 0 } }
+/// static UART: TakeCell<Uart> = TakeCell::new();
+///
+/// fn main() {
+///     let uart = Uart;
+///     critical_section::with(|cs| {
+///         if UART.put(cs, uart).is_err() {
+///             panic!("UART already handed over");
+///         }
+///     });
+///     // Enable the UART interrupt...
+/// }
+///
+/// fn on_uart_interrupt() {
+///     critical_section::with(|cs| {
+///         UART.with_taken(cs, |uart| uart.read());
+///     });
+/// }
+/// ```
+pub struct TakeCell<T> {
+    slot: UnsafeCell<Slot<T>>,
+}
+
+// NOTE The value moves between contexts, so it must be `Send`. It's only accessed by one
+// context at a time, so it doesn't need to be `Sync`.
+unsafe impl<T: Send> Sync for TakeCell<T> {}
+
+impl<T> TakeCell<T> {
+    /// Creates a new empty cell.
+    #[inline]
+    pub const fn new() -> Self {
+        TakeCell {
+            slot: UnsafeCell::new(Slot::Empty),
+        }
+    }
+
+    /// Creates a new cell containing `value`.
+    #[inline]
+    pub const fn new_with(value: T) -> Self {
+        TakeCell {
+            slot: UnsafeCell::new(Slot::Full(value)),
+        }
+    }
+
+    /// Puts `value` into the cell, or returns it in `Err` if the cell isn't empty.
+    ///
+    /// The cell isn't empty while the value is taken out by [`with_taken`](Self::with_taken).
+    #[inline]
+    pub fn put(&self, _cs: CriticalSection, value: T) -> Result<(), T> {
+        let slot = unsafe { &mut *self.slot.get() };
+        match slot {
+            Slot::Empty => {
+                *slot = Slot::Full(value);
+                Ok(())
+            }
+            Slot::Full(_) | Slot::Lent => Err(value),
+        }
+    }
+
+    /// Takes the value out of the cell, leaving it empty.
+    ///
+    /// Returns `None` if the cell is empty, or the value is taken out by
+    /// [`with_taken`](Self::with_taken).
+    #[inline]
+    pub fn take(&self, _cs: CriticalSection) -> Option<T> {
+        let slot = unsafe { &mut *self.slot.get() };
+        match mem::replace(slot, Slot::Empty) {
+            Slot::Full(value) => Some(value),
+            other => {
+                *slot = other;
+                None
+            }
+        }
+    }
+
+    /// Returns whether the cell holds a value, including while it's taken out by
+    /// [`with_taken`](Self::with_taken).
+    #[inline]
+    pub fn is_occupied(&self, _cs: CriticalSection) -> bool {
+        !matches!(unsafe { &*self.slot.get() }, Slot::Empty)
+    }
+
+    /// Calls `f` with a mutable reference to the value, or returns `None` if the cell is empty.
+    ///
+    /// The value is taken out of the cell while `f` runs, so calls to [`put`](Self::put),
+    /// [`take`](Self::take) and `with_taken` from inside `f` see it as unavailable. It's put
+    /// back afterwards, even if `f` panics.
+    #[inline]
+    pub fn with_taken<R>(&self, _cs: CriticalSection, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        struct PutBack<'a, T> {
+            cell: &'a TakeCell<T>,
+            value: Option<T>,
+        }
+
+        impl<T> Drop for PutBack<'_, T> {
+            #[inline]
+            fn drop(&mut self) {
+                if let Some(value) = self.value.take() {
+                    unsafe { *self.cell.slot.get() = Slot::Full(value) };
+                }
+            }
+        }
+
+        let slot = unsafe { &mut *self.slot.get() };
+        let value = match mem::replace(slot, Slot::Lent) {
+            Slot::Full(value) => value,
+            other => {
+                *slot = other;
+                return None;
+            }
+        };
+
+        let mut put_back = PutBack {
+            cell: self,
+            value: Some(value),
+        };
+        Some(f(put_back.value.as_mut().unwrap()))
+    }
+
+    /// Gets a mutable reference to the contained value when the cell is already uniquely
+    /// borrowed.
+    #[inline]
+    pub fn get_mut(&mut self) -> Option<&mut T> {
+        match self.slot.get_mut() {
+            Slot::Full(value) => Some(value),
+            Slot::Empty | Slot::Lent => None,
+        }
+    }
+
+    /// Unwraps the contained value, consuming the cell.
+    #[inline]
+    pub fn into_inner(self) -> Option<T> {
+        match self.slot.into_inner() {
+            Slot::Full(value) => Some(value),
+            Slot::Empty | Slot::Lent => None,
+        }
+    }
+}
+
+impl<T> Default for TakeCell<T> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Owner {
+    Cpu,
+    /// Borrowed by [`DmaGuard::access`].
+    CpuBorrowed,
+    Dma,
+}
+
+/// Error returned by [`DmaGuard`] methods called while the buffer is in the wrong state.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DmaError {
+    /// The buffer is owned by a DMA transfer, or already being accessed by the CPU.
+    Busy,
+    /// No DMA transfer was started.
+    NotStarted,
+}
+
+impl fmt::Display for DmaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DmaError::Busy => f.write_str("DMA buffer is busy"),
+            DmaError::NotStarted => f.write_str("no DMA transfer was started"),
+        }
+    }
+}
+
+/// A buffer handed back and forth between the CPU and a DMA transfer.
+///
+/// The buffer is owned either by the CPU, which can [`access`](DmaGuard::access) it, or by a
+/// DMA transfer, between [`start`](DmaGuard::start) and [`complete`](DmaGuard::complete).
+/// Which one owns it is checked in the critical section, so the CPU never touches the buffer
+/// while the DMA controller may be accessing it, even if the transfer is completed from an
+/// interrupt handler.
+///
+/// ```no_run
+/// use critical_section::cell::DmaGuard;
+///
+/// static TX_BUFFER: DmaGuard<[u8; 64]> = DmaGuard::new([0; 64]);
+///
+/// # fn start_dma(_: *mut u8, _: usize) {}
+/// fn send(data: &[u8]) {
+///     critical_section::with(|cs| {
+///         TX_BUFFER
+///             .access(cs, |buffer| buffer[..data.len()].copy_from_slice(data))
+///             .expect("transfer in progress");
+///         let buffer = TX_BUFFER.start(cs).unwrap();
+///         start_dma(buffer.cast(), data.len());
+///     });
+/// }
+///
+/// fn on_dma_complete_interrupt() {
+///     critical_section::with(|cs| TX_BUFFER.complete(cs).unwrap());
+/// }
+/// ```
+///
+/// `start` and `complete` include compiler fences, so buffer accesses aren't moved across
+/// them, but no hardware barriers or cache maintenance. Chips with data caches or write
+/// buffers in front of the DMA controller need those too.
+#[derive(Debug)]
+pub struct DmaGuard<T> {
+    owner: Cell<Owner>,
+    buffer: UnsafeCell<T>,
+}
+
+// NOTE Same as for `Mutex`, the buffer moves between contexts, so it must be `Send`. It's only
+// accessed by one context at a time, tracked by `owner`.
+unsafe impl<T: Send> Sync for DmaGuard<T> {}
+
+impl<T> DmaGuard<T> {
+    /// Creates a new buffer, owned by the CPU.
+    #[inline]
+    pub const fn new(buffer: T) -> Self {
+        DmaGuard {
+            owner: Cell::new(Owner::Cpu),
+            buffer: UnsafeCell::new(buffer),
+        }
+    }
+
+    /// Hands the buffer over to a DMA transfer, returning a pointer to it for configuring the
+    /// transfer.
+    ///
+    /// The buffer must be `'static`, so it outlives any transfer. Returns [`DmaError::Busy`] if
+    /// a transfer is already in progress, or if called from inside [`access`](Self::access).
+    #[inline]
+    pub fn start(&'static self, _cs: CriticalSection) -> Result<*mut T, DmaError> {
+        if self.owner.get() != Owner::Cpu {
+            return Err(DmaError::Busy);
+        }
+        self.owner.set(Owner::Dma);
+        // The CPU's writes to the buffer must happen before the transfer is started.
+        compiler_fence(Ordering::Release);
+        Ok(self.buffer.get())
+    }
+
+    /// Hands the buffer back to the CPU, once the DMA transfer has completed.
+    ///
+    /// Returns [`DmaError::NotStarted`] if no transfer is in progress.
+    #[inline]
+    pub fn complete(&self, _cs: CriticalSection) -> Result<(), DmaError> {
+        if self.owner.get() != Owner::Dma {
+            return Err(DmaError::NotStarted);
+        }
+        // The CPU's reads of the buffer must happen after the transfer completed.
+        compiler_fence(Ordering::Acquire);
+        self.owner.set(Owner::Cpu);
+        Ok(())
+    }
+
+    /// Returns whether the buffer is owned by a DMA transfer.
+    #[inline]
+    pub fn is_busy(&self, _cs: CriticalSection) -> bool {
+        self.owner.get() == Owner::Dma
+    }
+
+    /// Calls `f` with a mutable reference to the buffer, if it's owned by the CPU.
+    ///
+    /// Returns [`DmaError::Busy`] if a transfer is in progress, or if called from inside
+    /// another `access`.
+    #[inline]
+    pub fn access<R>(
+        &self,
+        _cs: CriticalSection,
+        f: impl FnOnce(&mut T) -> R,
+    ) -> Result<R, DmaError> {
+        struct Release<'a>(&'a Cell<Owner>);
+
+        impl Drop for Release<'_> {
+            #[inline]
+            fn drop(&mut self) {
+                self.0.set(Owner::Cpu);
+            }
+        }
+
+        if self.owner.get() != Owner::Cpu {
+            return Err(DmaError::Busy);
+        }
+        self.owner.set(Owner::CpuBorrowed);
+        let _release = Release(&self.owner);
+        Ok(f(unsafe { &mut *self.buffer.get() }))
+    }
+
+    /// Gets a mutable reference to the buffer when it's already uniquely borrowed.
+    ///
+    /// No transfer can be in progress, since [`start`](Self::start) needs a `'static`
+    /// reference.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.buffer.get_mut()
+    }
+
+    /// Unwraps the buffer, consuming the guard.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.buffer.into_inner()
+    }
+}
+
+/// An `UnsafeCell` that is `Sync`, and only gives access to its contents with a
+/// [`CriticalSection`] token.
+///
+/// This is the minimal building block for data that has to be accessed through raw pointers,
+/// such as DMA descriptors whose addresses are written to peripheral registers. Unlike a
+/// `static mut`, every access site needs a token, so the type system still demands a
+/// critical section. Keeping the accesses free of aliasing is up to the user, which is why
+/// the reference accessors are `unsafe`.
+///
+/// ```no_run
+/// use critical_section::cell::SyncUnsafeCell;
+///
+/// #[repr(C, align(16))]
+/// struct Descriptor {
+///     control: u32,
+///     buffer: u32,
+/// }
+///
+/// static DESCRIPTOR: SyncUnsafeCell<Descriptor> = SyncUnsafeCell::new(Descriptor {
+///     control: 0,
+///     buffer: 0,
+/// });
+///
+/// # fn write_descriptor_address(_: usize) {}
+/// critical_section::with(|cs| {
+///     unsafe { DESCRIPTOR.as_mut(cs).control = 1 };
+///     write_descriptor_address(DESCRIPTOR.get_ptr(cs) as usize);
+/// });
+/// ```
+///
+/// For buffers that DMA writes to while the CPU runs, [`DmaGuard`] also tracks who owns the
+/// buffer.
+#[repr(transparent)]
+#[derive(Debug)]
+pub struct SyncUnsafeCell<T: ?Sized> {
+    value: UnsafeCell<T>,
+}
+
+// NOTE Same as for `Mutex`, the value moves between contexts, so it must be `Send`. All
+// access needs a critical section, so it doesn't need to be `Sync`.
+unsafe impl<T: ?Sized + Send> Sync for SyncUnsafeCell<T> {}
+
+impl<T> SyncUnsafeCell<T> {
+    /// Creates a new cell containing `value`.
+    #[inline]
+    pub const fn new(value: T) -> Self {
+        SyncUnsafeCell {
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    /// Unwraps the contained value, consuming the cell.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+}
+
+impl<T: ?Sized> SyncUnsafeCell<T> {
+    /// Returns a raw pointer to the contained value.
+    ///
+    /// Getting the pointer is safe, using it is subject to the same rules as for
+    /// `UnsafeCell::get`. The pointer stays valid after the critical section, but accesses
+    /// through it need their own synchronization then.
+    #[inline]
+    pub fn get_ptr(&self, _cs: CriticalSection) -> *mut T {
+        self.value.get()
+    }
+
+    /// Returns a shared reference to the contained value.
+    ///
+    /// # Safety
+    ///
+    /// No mutable reference to the value, from [`as_mut`](Self::as_mut) or through the
+    /// pointer, may exist while the returned reference is used, and the value must not be
+    /// written to, for example by DMA.
+    #[inline]
+    pub unsafe fn as_ref<'cs>(&'cs self, _cs: CriticalSection<'cs>) -> &'cs T {
+        &*self.value.get()
+    }
+
+    /// Returns a mutable reference to the contained value.
+    ///
+    /// # Safety
+    ///
+    /// No other reference to the value may exist while the returned reference is used, and
+    /// the value must not be accessed through the pointer from [`get_ptr`](Self::get_ptr), for
+    /// example by DMA. This includes nested critical sections and a second call to `as_mut`.
+    #[inline]
+    #[allow(clippy::mut_from_ref)]
+    pub unsafe fn as_mut<'cs>(&'cs self, _cs: CriticalSection<'cs>) -> &'cs mut T {
+        &mut *self.value.get()
+    }
+
+    /// Gets a mutable reference to the contained value when the cell is already uniquely
+    /// borrowed.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+}
+
+impl<T: Default> Default for SyncUnsafeCell<T> {
+    #[inline]
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
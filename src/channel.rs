@@ -0,0 +1,181 @@
+//! Fixed-capacity channel for passing values between interrupt handlers and threads.
+//!
+//! [`Channel`] is a ring buffer with room for `N` values. Every operation only holds the
+//! critical section for the short index update and the copy of a single value, so it can be
+//! used from interrupt handlers to hand data to the main loop:
+//!
+//! ```no_run
+//! use critical_section::channel::Channel;
+//!
+//! static SAMPLES: Channel<u16, 32> = Channel::new();
+//!
+//! // In the ADC interrupt handler: drop the sample if the main loop falls behind.
+//! fn on_adc_interrupt(sample: u16) {
+//!     let _ = SAMPLES.try_send(sample);
+//! }
+//!
+//! // In the main loop.
+//! fn process() {
+//!     while let Some(sample) = SAMPLES.try_recv() {
+//!         // ...
+//! #       let _ = sample;
+//!     }
+//! }
+//! ```
+//!
+//! Any number of contexts may send, and any number may receive, but values are received in
+//! the order they were sent, so it's usually used with a single receiver.
+
+use core::cell::{Cell, UnsafeCell};
+use core::mem::MaybeUninit;
+use core::ptr;
+
+use super::{with, CriticalSection};
+
+/// A fixed-capacity channel holding up to `N` values of type `T`.
+///
+/// See the [module docs](self).
+pub struct Channel<T, const N: usize> {
+    buffer: UnsafeCell<MaybeUninit<[T; N]>>,
+    /// Index of the oldest value.
+    head: Cell<usize>,
+    /// Number of values in the buffer.
+    len: Cell<usize>,
+}
+
+// NOTE Values are moved from the sending context to the receiving context, so `T` must be
+// `Send`. The buffer and the indices are only accessed inside the critical section.
+unsafe impl<T: Send, const N: usize> Sync for Channel<T, N> {}
+
+impl<T, const N: usize> Channel<T, N> {
+    /// Creates a new empty channel.
+    #[inline]
+    pub const fn new() -> Self {
+        Channel {
+            buffer: UnsafeCell::new(MaybeUninit::uninit()),
+            head: Cell::new(0),
+            len: Cell::new(0),
+        }
+    }
+
+    /// Returns the maximum number of values the channel can hold.
+    #[inline]
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    #[inline]
+    fn slot(&self, index: usize) -> *mut T {
+        self.buffer.get().cast::<T>().wrapping_add(index % N)
+    }
+
+    /// Sends `value`, or returns it in `Err` if the channel is full.
+    #[inline]
+    pub fn try_send(&self, value: T) -> Result<(), T> {
+        with(|cs| self.try_send_in(cs, value))
+    }
+
+    /// Sends `value` from inside a critical section, or returns it in `Err` if the channel is
+    /// full.
+    #[inline]
+    pub fn try_send_in(&self, _cs: CriticalSection, value: T) -> Result<(), T> {
+        let len = self.len.get();
+        if len == N {
+            return Err(value);
+        }
+        unsafe { ptr::write(self.slot(self.head.get() + len), value) };
+        self.len.set(len + 1);
+        Ok(())
+    }
+
+    /// Receives the oldest value, or returns `None` if the channel is empty.
+    #[inline]
+    pub fn try_recv(&self) -> Option<T> {
+        with(|cs| self.try_recv_in(cs))
+    }
+
+    /// Receives the oldest value from inside a critical section, or returns `None` if the
+    /// channel is empty.
+    #[inline]
+    pub fn try_recv_in(&self, _cs: CriticalSection) -> Option<T> {
+        let len = self.len.get();
+        if len == 0 {
+            return None;
+        }
+        let head = self.head.get();
+        let value = unsafe { ptr::read(self.slot(head)) };
+        self.head.set((head + 1) % N);
+        self.len.set(len - 1);
+        Some(value)
+    }
+
+    /// Returns an iterator receiving values until the channel is empty.
+    ///
+    /// Each value is received in its own short critical section, and the loop body runs
+    /// outside of it, so interrupt handlers can send while the values are processed:
+    ///
+    /// ```no_run
+    /// # use critical_section::channel::Channel;
+    /// # static SAMPLES: Channel<u16, 32> = Channel::new();
+    /// # fn store(_: u16) {}
+    /// for sample in SAMPLES.drain() {
+    ///     // Slow processing, with interrupts enabled.
+    ///     store(sample);
+    /// }
+    /// ```
+    ///
+    /// Values sent during the loop are received as well, so it only ends once the receiver
+    /// catches up. The iterator may return values again after returning `None`.
+    #[inline]
+    pub fn drain(&self) -> Drain<'_, T, N> {
+        Drain { channel: self }
+    }
+
+    /// Returns the number of values in the channel.
+    #[inline]
+    pub fn len(&self) -> usize {
+        with(|_| self.len.get())
+    }
+
+    /// Returns whether the channel is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns whether the channel is full.
+    #[inline]
+    pub fn is_full(&self) -> bool {
+        self.len() == N
+    }
+}
+
+/// Iterator receiving values from a [`Channel`], created by [`Channel::drain`].
+pub struct Drain<'a, T, const N: usize> {
+    channel: &'a Channel<T, N>,
+}
+
+impl<T, const N: usize> Iterator for Drain<'_, T, N> {
+    type Item = T;
+
+    #[inline]
+    fn next(&mut self) -> Option<T> {
+        self.channel.try_recv()
+    }
+}
+
+impl<T, const N: usize> Default for Channel<T, N> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize> Drop for Channel<T, N> {
+    fn drop(&mut self) {
+        let head = self.head.get();
+        for i in 0..self.len.get() {
+            unsafe { ptr::drop_in_place(self.slot(head + i)) };
+        }
+    }
+}
@@ -0,0 +1,117 @@
+//! Random delays at the critical section boundaries, to shake out timing assumptions.
+//!
+//! With the `chaos` Cargo feature enabled, every acquire is preceded by a random delay, and
+//! every release of the outermost critical section is followed by one, and sometimes by a
+//! forced context switch. Code that only works because of lucky timing, rather than because
+//! the critical section excludes other contexts, then fails much more often. This is meant for
+//! tests on the host or in an emulator such as QEMU, never for production builds.
+//!
+//! ```no_run
+//! use critical_section::chaos;
+//!
+//! // Make failures reproducible, at least with a single thread.
+//! chaos::set_seed(0x1234_5678);
+//! chaos::set_max_delay(1000);
+//! // Force a context switch after one in 4 releases.
+//! chaos::set_yield_rate(4);
+//! ```
+//!
+//! Delays are spin loops. With the `std` Cargo feature, context switches use
+//! `std::thread::yield_now`. Otherwise, they call the hook set with [`set_yield_hook`], if
+//! any, such as a function asking the RTOS scheduler to switch tasks.
+//!
+//! The configuration and random number generator state are atomics, since they're used outside
+//! of the critical section, but only with loads and stores, so this works without
+//! compare-and-swap too.
+
+use core::hint::spin_loop;
+use core::ptr;
+use core::sync::atomic::{AtomicPtr, AtomicU32, Ordering};
+
+type YieldHook = fn();
+
+/// State of the xorshift random number generator, never zero.
+static RNG: AtomicU32 = AtomicU32::new(0x9e37_79b9);
+static MAX_DELAY: AtomicU32 = AtomicU32::new(100);
+static YIELD_RATE: AtomicU32 = AtomicU32::new(4);
+// Function pointers can't be stored in atomics directly.
+static YIELD_HOOK: AtomicPtr<()> = AtomicPtr::new(ptr::null_mut());
+
+/// Seed the random number generator.
+///
+/// With a single thread, the same seed produces the same delays.
+pub fn set_seed(seed: u32) {
+    // Xorshift gets stuck on zero.
+    let seed = if seed == 0 { 0x9e37_79b9 } else { seed };
+    RNG.store(seed, Ordering::Relaxed);
+}
+
+/// Set the maximum delay, in spin loop iterations. The default is 100, and `0` disables
+/// delays.
+pub fn set_max_delay(iterations: u32) {
+    MAX_DELAY.store(iterations, Ordering::Relaxed);
+}
+
+/// Force a context switch after one in `one_in` releases of the outermost critical section, on
+/// average. The default is 4, and `0` disables context switches.
+pub fn set_yield_rate(one_in: u32) {
+    YIELD_RATE.store(one_in, Ordering::Relaxed);
+}
+
+/// Set the function called to force a context switch.
+///
+/// With the `std` Cargo feature, this replaces `std::thread::yield_now`.
+pub fn set_yield_hook(hook: fn()) {
+    YIELD_HOOK.store(hook as *mut (), Ordering::Release);
+}
+
+fn next() -> u32 {
+    // Racy when used from several threads, which only makes it more random.
+    let mut x = RNG.load(Ordering::Relaxed);
+    x ^= x << 13;
+    x ^= x >> 17;
+    x ^= x << 5;
+    RNG.store(x, Ordering::Relaxed);
+    x
+}
+
+fn delay() {
+    let max = MAX_DELAY.load(Ordering::Relaxed);
+    if max != 0 {
+        // Up to `max` spins, any `u32` for `u32::MAX`.
+        let spins = max.checked_add(1).map_or_else(next, |range| next() % range);
+        for _ in 0..spins {
+            spin_loop();
+        }
+    }
+}
+
+fn yield_now() {
+    let hook = YIELD_HOOK.load(Ordering::Acquire);
+    if !hook.is_null() {
+        let hook = unsafe { core::mem::transmute::<*mut (), YieldHook>(hook) };
+        hook();
+    } else {
+        #[cfg(feature = "std")]
+        {
+            extern crate std;
+            std::thread::yield_now();
+        }
+    }
+}
+
+/// Called before acquiring the critical section.
+#[inline]
+pub(crate) fn before_acquire() {
+    delay();
+}
+
+/// Called after releasing the outermost critical section.
+#[inline]
+pub(crate) fn after_release() {
+    delay();
+    let rate = YIELD_RATE.load(Ordering::Relaxed);
+    if rate != 0 && next() < u32::MAX / rate {
+        yield_now();
+    }
+}
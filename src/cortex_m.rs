@@ -0,0 +1,148 @@
+//! Critical section implementation for single-core Cortex-M.
+//!
+//! The critical section disables interrupts with `cpsid i`. On ARMv7-M and ARMv8-M Mainline,
+//! [`with_max_priority`](crate::with_max_priority) raises BASEPRI instead, so interrupts more
+//! urgent than the given priority keep running. `priority` is the raw priority value, as
+//! written to the NVIC priority registers: interrupts whose priority value is greater than or
+//! equal to it are masked. A priority of `0`, which BASEPRI can't express, and targets without
+//! BASEPRI, such as ARMv6-M, mask all interrupts with PRIMASK.
+//!
+//! BASEPRI is only raised, never lowered, by nested priority sections, and is independent of
+//! PRIMASK, so priority sections and full critical sections nest in any order.
+//!
+//! In debug builds, the exception number read from IPSR is recorded in the restore state, and
+//! releasing panics if it doesn't match, for example when a restore state stored in a static
+//! by thread code is released by an interrupt handler. Releases from fault handlers, and of
+//! `RestoreState::invalid()`, aren't checked.
+//!
+//! IPSR also tells whether the current context is an exception handler, for the checks of
+//! [`with_from_isr`](crate::with_from_isr) and for [`Dual`](crate::Dual).
+
+use core::arch::asm;
+
+/// Restore state bit: interrupts were enabled before acquiring.
+const INTERRUPTS_ENABLED: u32 = 1 << 0;
+
+/// Restore state bit: acquired by raising BASEPRI. The lower 8 bits hold the previous BASEPRI.
+#[cfg(has_basepri)]
+const BASEPRI: u32 = 1 << 8;
+
+/// Restore state bit, in debug builds: the context is recorded. Unlike zero, which is also what
+/// acquiring in thread mode with interrupts already disabled returns, this tells
+/// `RestoreState::invalid()` apart from real restore states.
+#[cfg(debug_assertions)]
+const CONTEXT_RECORDED: u32 = 1 << 15;
+
+/// Restore state bits, in debug builds: the exception number the critical section was acquired
+/// in.
+#[cfg(debug_assertions)]
+const CONTEXT_SHIFT: u32 = 16;
+
+struct CortexMCriticalSection;
+crate::set_impl!(CortexMCriticalSection);
+
+unsafe impl crate::Impl for CortexMCriticalSection {
+    #[inline(always)]
+    unsafe fn acquire() -> u32 {
+        let primask: u32;
+        asm!("mrs {}, PRIMASK", out(reg) primask, options(nomem, nostack, preserves_flags));
+        // No `nomem`, so this also acts as a compiler fence.
+        asm!("cpsid i", options(nostack, preserves_flags));
+
+        let restore_state = if primask & 1 == 0 {
+            INTERRUPTS_ENABLED
+        } else {
+            0
+        };
+        with_context(restore_state)
+    }
+
+    #[inline(always)]
+    unsafe fn release(restore_state: u32) {
+        check_context(restore_state);
+        // Only re-enable interrupts if they were enabled before the critical section.
+        if restore_state & INTERRUPTS_ENABLED != 0 {
+            asm!("cpsie i", options(nostack, preserves_flags));
+        }
+    }
+
+    #[cfg(has_basepri)]
+    #[inline(always)]
+    unsafe fn acquire_priority(priority: u8) -> Option<u32> {
+        if priority == 0 {
+            return None;
+        }
+
+        let basepri: u32;
+        asm!("mrs {}, BASEPRI", out(reg) basepri, options(nomem, nostack, preserves_flags));
+        // `BASEPRI_MAX` is only written if that masks more interrupts than before. No `nomem`,
+        // so this also acts as a compiler fence.
+        asm!("msr BASEPRI_MAX, {}", in(reg) u32::from(priority), options(nostack, preserves_flags));
+
+        Some(with_context(BASEPRI | (basepri & 0xff)))
+    }
+
+    #[cfg(has_basepri)]
+    #[inline(always)]
+    unsafe fn release_priority(restore_state: u32) {
+        check_context(restore_state);
+        asm!("msr BASEPRI, {}", in(reg) restore_state & 0xff, options(nostack, preserves_flags));
+    }
+
+    #[inline(always)]
+    unsafe fn in_isr() -> bool {
+        exception_number() != 0
+    }
+}
+
+/// Returns the number of the current exception, `0` in thread mode.
+#[inline(always)]
+fn exception_number() -> u32 {
+    let ipsr: u32;
+    unsafe { asm!("mrs {}, IPSR", out(reg) ipsr, options(nomem, nostack, preserves_flags)) };
+    ipsr & 0x1ff
+}
+
+/// Records the current exception in `restore_state`, in debug builds.
+#[inline(always)]
+fn with_context(restore_state: u32) -> u32 {
+    #[cfg(debug_assertions)]
+    return restore_state | CONTEXT_RECORDED | (exception_number() << CONTEXT_SHIFT);
+
+    #[cfg(not(debug_assertions))]
+    restore_state
+}
+
+/// Checks that `restore_state` was recorded in the current exception, in debug builds.
+///
+/// Some releases legitimately cross contexts, and are not checked:
+///
+/// - `RestoreState::invalid()`, which records no context. It's the outermost restore state of
+///   `with_from_isr`, which `without` releases.
+/// - Releases from the fault handlers and NMI, such as `force_release_on_panic` releasing the
+///   critical section of the thread code that faulted.
+///
+/// Restore states handed between contexts with `FullState` keep the exception they were
+/// acquired in, so a kernel switching tasks with [`restore`](crate::restore) must switch
+/// between contexts that release in the same exception, usually thread mode.
+#[inline(always)]
+fn check_context(restore_state: u32) {
+    #[cfg(debug_assertions)]
+    {
+        /// NMI, HardFault, MemManage, BusFault, UsageFault, SecureFault.
+        const FAULTS: core::ops::RangeInclusive<u32> = 2..=7;
+
+        let acquired = restore_state >> CONTEXT_SHIFT;
+        let current = exception_number();
+        let invalid = restore_state & CONTEXT_RECORDED == 0;
+        assert!(
+            acquired == current || invalid || FAULTS.contains(&current),
+            "critical section acquired in exception {} released in exception {}",
+            acquired,
+            current
+        );
+    }
+
+    #[cfg(not(debug_assertions))]
+    let _ = restore_state;
+}
@@ -0,0 +1,46 @@
+use core::panic::Location;
+use core::ptr;
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+/// Location of the last entry into the outermost critical section.
+///
+/// This is an atomic instead of a [`Mutex`](crate::Mutex), so it can be read by a fault
+/// handler while the critical section is held.
+static LAST_ENTRY: AtomicPtr<Location<'static>> = AtomicPtr::new(ptr::null_mut());
+
+/// Called right after entering the outermost critical section.
+#[inline]
+#[track_caller]
+pub(crate) fn on_enter() {
+    let location: &'static Location<'static> = Location::caller();
+    LAST_ENTRY.store(location as *const _ as *mut _, Ordering::Relaxed);
+}
+
+/// Returns where the outermost critical section was last entered.
+///
+/// This is meant for diagnostics: a watchdog or fault handler can report where the system was
+/// stuck with the critical section held. It doesn't acquire the critical section, so it's
+/// safe to call from any context, including while the critical section is held by the code
+/// being diagnosed.
+///
+/// The location stays set after the critical section is released. Returns `None` if no
+/// critical section has been entered yet.
+///
+/// The location is that of the outermost call to [`with`](crate::with),
+/// [`enter`](crate::enter) or [`acquire`](crate::acquire), or of the caller of a function
+/// built on them that is itself `#[track_caller]`.
+///
+/// Only available with the `debug` Cargo feature, which needs atomic pointer loads and stores.
+///
+/// ```no_run
+/// fn on_watchdog_timeout() {
+///     if let Some(location) = critical_section::last_entry_location() {
+///         // Log `location` somewhere that survives the reset...
+/// #       let _ = location;
+///     }
+/// }
+/// ```
+#[inline]
+pub fn last_entry_location() -> Option<&'static Location<'static>> {
+    unsafe { LAST_ENTRY.load(Ordering::Relaxed).as_ref() }
+}
@@ -0,0 +1,162 @@
+#[cfg(feature = "alloc")]
+use alloc::boxed::Box;
+#[cfg(feature = "alloc")]
+use alloc::collections::VecDeque;
+use core::cell::RefCell;
+
+use super::{with, CriticalSection, Mutex};
+
+/// Maximum number of deferred functions waiting to run.
+const CAPACITY: usize = 16;
+
+type Deferred = fn();
+
+struct Queue {
+    slots: [Option<Deferred>; CAPACITY],
+    /// Index of the oldest entry.
+    head: usize,
+    /// Number of entries.
+    len: usize,
+}
+
+static QUEUE: Mutex<RefCell<Queue>> = Mutex::new(RefCell::new(Queue {
+    slots: [None; CAPACITY],
+    head: 0,
+    len: 0,
+}));
+
+#[cfg(feature = "alloc")]
+type DeferredBoxed = Box<dyn FnOnce() + Send>;
+
+/// Closures deferred with [`defer_boxed`], in their own unbounded queue.
+#[cfg(feature = "alloc")]
+static BOXED: Mutex<RefCell<VecDeque<DeferredBoxed>>> = Mutex::new(RefCell::new(VecDeque::new()));
+
+/// Error returned by [`defer`] when too many functions are already waiting to run.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DeferQueueFull;
+
+impl core::fmt::Display for DeferQueueFull {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("deferred function queue is full")
+    }
+}
+
+/// Run `f` right after the outermost critical section is released.
+///
+/// This lets drivers schedule work, such as waking a task or calling a user callback, from
+/// inside a critical section without running it with interrupts masked. If the current thread
+/// is not in a critical section, `f` runs right away.
+///
+/// Deferred functions run in the order they were deferred, in the context that releases the
+/// outermost critical section, which might be an interrupt handler. Up to 16 functions can
+/// wait at a time, after which [`DeferQueueFull`] is returned.
+///
+/// ```no_run
+/// # use critical_section::Mutex;
+/// # use std::cell::Cell;
+/// static PENDING: Mutex<Cell<bool>> = Mutex::new(Cell::new(false));
+///
+/// fn notify() {
+///     // Slow, must not run with interrupts masked...
+/// }
+///
+/// fn on_rx_interrupt() {
+///     critical_section::with(|cs| {
+///         PENDING.borrow(cs).set(true);
+///         critical_section::defer(notify).unwrap();
+///     });
+///     // `notify` has run here.
+/// }
+/// ```
+pub fn defer(f: fn()) -> Result<(), DeferQueueFull> {
+    with(|cs| {
+        let mut queue = QUEUE.borrow_ref_mut(cs);
+        if queue.len == CAPACITY {
+            return Err(DeferQueueFull);
+        }
+        let tail = (queue.head + queue.len) % CAPACITY;
+        queue.slots[tail] = Some(f);
+        queue.len += 1;
+        Ok(())
+    })
+}
+
+/// Run closure `f` right after the outermost critical section is released.
+///
+/// This is [`defer`] for closures, which can capture their environment. They are kept in a
+/// heap-allocated queue without a fixed capacity, so this doesn't fail, but allocates inside
+/// the critical section if the queue has to grow.
+///
+/// Boxed closures run after the functions deferred with [`defer`], in the order they were
+/// deferred.
+///
+/// ```no_run
+/// fn on_rx_interrupt(byte: u8) {
+///     critical_section::with(|_| {
+///         critical_section::defer_boxed(Box::new(move || {
+///             // Handle `byte` without interrupts masked...
+/// #           let _ = byte;
+///         }));
+///     });
+/// }
+/// ```
+#[cfg(feature = "alloc")]
+pub fn defer_boxed(f: Box<dyn FnOnce() + Send>) {
+    with(|cs| BOXED.borrow_ref_mut(cs).push_back(f));
+}
+
+/// Pop the oldest deferred function.
+///
+/// # Safety
+///
+/// Must be called while the critical section is held.
+unsafe fn pop() -> Option<Deferred> {
+    let mut queue = QUEUE.borrow_ref_mut(CriticalSection::new_unchecked());
+    if queue.len == 0 {
+        return None;
+    }
+    let head = queue.head;
+    queue.head = (head + 1) % CAPACITY;
+    queue.len -= 1;
+    queue.slots[head].take()
+}
+
+/// Run the deferred functions, called right after releasing the outermost critical section.
+///
+/// Functions deferred by the functions run here are run too.
+#[inline]
+pub(crate) fn run_pending() {
+    loop {
+        // The queue is only touched for the pop, without the crate's bookkeeping, so this
+        // doesn't call itself again.
+        let f = unsafe {
+            let restore_state = crate::acquire_raw();
+            let f = pop();
+            crate::release_raw(restore_state);
+            f
+        };
+        match f {
+            Some(f) => f(),
+            None => break,
+        }
+    }
+
+    #[cfg(feature = "alloc")]
+    loop {
+        // Popped the same way. The closure is run and dropped, freeing it, outside of the
+        // critical section.
+        let f = unsafe {
+            let restore_state = crate::acquire_raw();
+            let f = BOXED
+                .borrow_ref_mut(CriticalSection::new_unchecked())
+                .pop_front();
+            crate::release_raw(restore_state);
+            f
+        };
+        match f {
+            Some(f) => f(),
+            None => break,
+        }
+    }
+}
@@ -0,0 +1,231 @@
+//! Named critical section domains.
+//!
+//! The global critical section protects everything at once. On multi-core systems, that means
+//! unrelated resources contend for the same lock. A [`Domain`] is an independent critical
+//! section, identified by a `const` ID, that only protects the data associated with it, such
+//! as a [`domain::Mutex`](Mutex) with the same ID.
+//!
+//! The global critical section stays the default: [`crate::with`] and [`crate::Mutex`] are
+//! unaffected by domains, and holding the global critical section does not imply holding any
+//! domain.
+//!
+//! Domains are provided by an implementation registered with
+//! [`set_domain_impl!`](crate::set_domain_impl), separately from the global one:
+//!
+//! ```no_run
+//! use critical_section::RawRestoreState;
+//! use critical_section::domain::{Domain, Mutex};
+//! use std::cell::Cell;
+//!
+//! struct MyDomains;
+//! critical_section::set_domain_impl!(MyDomains);
+//!
+//! unsafe impl critical_section::domain::Impl for MyDomains {
+//!     unsafe fn acquire(_domain: usize) -> RawRestoreState {
+//!         // Acquire the lock for `domain`...
+//!         RawRestoreState::default()
+//!     }
+//!
+//!     unsafe fn release(_domain: usize, _restore_state: RawRestoreState) {
+//!         // Release the lock for `domain`...
+//!     }
+//! }
+//!
+//! const RADIO: usize = 1;
+//! static PACKETS: Mutex<Cell<u32>, RADIO> = Mutex::new(Cell::new(0));
+//!
+//! Domain::<RADIO>::with(|cs| {
+//!     let packets = PACKETS.borrow(cs);
+//!     packets.set(packets.get() + 1);
+//! });
+//! ```
+//!
+//! Nesting works like for the global critical section: acquiring a domain that is already
+//! held by the current thread must succeed, which implementations track through the restore
+//! state. Acquiring different domains in different orders can deadlock. The `lock-order`
+//! Cargo feature detects that in debug builds, see the `lock_order` module.
+
+use core::cell::UnsafeCell;
+use core::marker::PhantomData;
+
+use super::{RawRestoreState, RestoreState};
+
+/// Critical section token for the domain `ID`.
+///
+/// An instance of this type indicates that the current thread is executing code within the
+/// critical section of domain `ID`.
+#[derive(Clone, Copy, Debug)]
+pub struct DomainSection<'cs, const ID: usize> {
+    _0: PhantomData<&'cs ()>,
+    _not_send: PhantomData<*mut ()>,
+}
+
+impl<'cs, const ID: usize> DomainSection<'cs, ID> {
+    /// Creates a critical section token for the domain `ID`.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`CriticalSection::new`](crate::CriticalSection::new), for the critical section
+    /// of domain `ID`.
+    #[inline(always)]
+    pub const unsafe fn new() -> Self {
+        DomainSection {
+            _0: PhantomData,
+            _not_send: PhantomData,
+        }
+    }
+}
+
+/// A critical section domain, identified by `ID`.
+///
+/// See the [module docs](self).
+#[derive(Debug)]
+pub struct Domain<const ID: usize>;
+
+impl<const ID: usize> Domain<ID> {
+    /// Acquire the critical section of this domain in the current thread.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`crate::acquire`], for the critical section of this domain.
+    #[inline]
+    #[must_use = "the restore state must be passed to `release`"]
+    #[allow(clippy::unit_arg)] // `RawRestoreState` is `()` by default.
+    #[cfg_attr(all(feature = "lock-order", debug_assertions), track_caller)]
+    pub unsafe fn acquire() -> RestoreState {
+        extern "Rust" {
+            fn _critical_section_1_0_domain_acquire(domain: usize) -> RawRestoreState;
+        }
+
+        #[cfg(all(feature = "lock-order", debug_assertions))]
+        crate::lock_order::on_acquire(ID);
+
+        RestoreState(_critical_section_1_0_domain_acquire(ID))
+    }
+
+    /// Release the critical section of this domain.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`crate::release`], for the critical section of this domain.
+    #[inline]
+    #[allow(clippy::unit_arg)] // `RawRestoreState` is `()` by default.
+    pub unsafe fn release(restore_state: RestoreState) {
+        extern "Rust" {
+            fn _critical_section_1_0_domain_release(domain: usize, restore_state: RawRestoreState);
+        }
+
+        _critical_section_1_0_domain_release(ID, restore_state.0);
+
+        #[cfg(all(feature = "lock-order", debug_assertions))]
+        crate::lock_order::on_release(ID);
+    }
+
+    /// Execute closure `f` in the critical section of this domain.
+    ///
+    /// Nesting critical sections of the same domain is allowed.
+    #[inline]
+    #[cfg_attr(all(feature = "lock-order", debug_assertions), track_caller)]
+    pub fn with<R>(f: impl FnOnce(DomainSection<ID>) -> R) -> R {
+        struct Release<const ID: usize>(RestoreState);
+
+        impl<const ID: usize> Drop for Release<ID> {
+            #[inline]
+            fn drop(&mut self) {
+                unsafe { Domain::<ID>::release(self.0) }
+            }
+        }
+
+        let _release = Release::<ID>(unsafe { Self::acquire() });
+        f(unsafe { DomainSection::new() })
+    }
+}
+
+/// Methods required for a critical section domain implementation.
+///
+/// # Safety
+///
+/// Implementations must uphold the contract specified in [`crate::acquire`] and
+/// [`crate::release`], separately for each domain.
+pub unsafe trait Impl {
+    /// Acquire the critical section of `domain`.
+    ///
+    /// # Safety
+    ///
+    /// Callers must uphold the contract specified in [`Domain::acquire`].
+    unsafe fn acquire(domain: usize) -> RawRestoreState;
+    /// Release the critical section of `domain`.
+    ///
+    /// # Safety
+    ///
+    /// Callers must uphold the contract specified in [`Domain::release`].
+    unsafe fn release(domain: usize, restore_state: RawRestoreState);
+}
+
+/// Set the critical section domain implementation.
+///
+/// See the [`domain`](crate::domain) module for an example.
+#[macro_export]
+macro_rules! set_domain_impl {
+    ($t: ty) => {
+        #[no_mangle]
+        unsafe fn _critical_section_1_0_domain_acquire(domain: usize) -> $crate::RawRestoreState {
+            <$t as $crate::domain::Impl>::acquire(domain)
+        }
+        #[no_mangle]
+        unsafe fn _critical_section_1_0_domain_release(
+            domain: usize,
+            restore_state: $crate::RawRestoreState,
+        ) {
+            <$t as $crate::domain::Impl>::release(domain, restore_state)
+        }
+    };
+}
+
+/// A mutex based on the critical section of domain `ID`.
+///
+/// This works like [`crate::Mutex`], but requires a [`DomainSection`] token for the domain
+/// `ID` instead of a global [`CriticalSection`](crate::CriticalSection) token.
+#[derive(Debug)]
+pub struct Mutex<T, const ID: usize> {
+    inner: UnsafeCell<T>,
+}
+
+impl<T, const ID: usize> Mutex<T, ID> {
+    /// Creates a new mutex.
+    #[inline]
+    pub const fn new(value: T) -> Self {
+        Mutex {
+            inner: UnsafeCell::new(value),
+        }
+    }
+
+    /// Gets a mutable reference to the contained value when the mutex is already uniquely borrowed.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.inner.get() }
+    }
+
+    /// Unwraps the contained value, consuming the mutex.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.inner.into_inner()
+    }
+
+    /// Returns a raw pointer to the contained value.
+    ///
+    /// See [`crate::Mutex::as_ptr`].
+    #[inline]
+    pub const fn as_ptr(&self) -> *mut T {
+        self.inner.get()
+    }
+
+    /// Borrows the data for the duration of the domain's critical section.
+    #[inline]
+    pub fn borrow<'cs>(&'cs self, _cs: DomainSection<'cs, ID>) -> &'cs T {
+        unsafe { &*self.inner.get() }
+    }
+}
+
+// NOTE Same as for `crate::Mutex`, the protected data must be `Send`.
+unsafe impl<T, const ID: usize> Sync for Mutex<T, ID> where T: Send {}
@@ -0,0 +1,164 @@
+//! Separate implementations for thread and interrupt context.
+
+use core::marker::PhantomData;
+
+use crate::{Impl, RawRestoreState};
+
+/// A critical section implementation dispatching to `Thread` in thread context, and to `Isr`
+/// in interrupt context.
+///
+/// RTOSes such as FreeRTOS and ThreadX have different calls for entering a critical section
+/// from a task and from an interrupt handler. Each can be wrapped in its own [`Impl`], and
+/// registered together:
+///
+/// ```no_run
+/// use critical_section::{Dual, RawRestoreState};
+///
+/// struct TaskCriticalSection;
+/// struct IsrCriticalSection;
+/// critical_section::set_impl!(Dual<TaskCriticalSection, IsrCriticalSection>);
+///
+/// unsafe impl critical_section::Impl for TaskCriticalSection {
+///     unsafe fn acquire() -> RawRestoreState {
+///         // Call `taskENTER_CRITICAL`...
+///         RawRestoreState::default()
+///     }
+///
+///     unsafe fn release(_restore_state: RawRestoreState) {
+///         // Call `taskEXIT_CRITICAL`...
+///     }
+/// }
+///
+/// unsafe impl critical_section::Impl for IsrCriticalSection {
+///     unsafe fn acquire() -> RawRestoreState {
+///         // Call `taskENTER_CRITICAL_FROM_ISR` and return the mask, with
+///         // `restore-state-u32`...
+///         RawRestoreState::default()
+///     }
+///
+///     unsafe fn release(_restore_state: RawRestoreState) {
+///         // Call `taskEXIT_CRITICAL_FROM_ISR` with the mask...
+///     }
+/// }
+///
+/// unsafe impl critical_section::InterruptContext for IsrCriticalSection {
+///     fn in_isr() -> bool {
+///         // Call `xPortIsInsideInterrupt`...
+///         false
+///     }
+/// }
+/// ```
+///
+/// The context is probed with [`Isr::in_isr`](InterruptContext::in_isr) on every acquire and
+/// release. A critical section is always released in the context that acquired it, so both
+/// probes agree, and the restore state needs no bit to record the context.
+///
+/// Nesting works within each context, and never crosses contexts: while a thread holds the
+/// critical section, `Thread` keeps interrupt handlers using it from running. Interrupts not
+/// masked by `Thread` must not use the critical section at all: on FreeRTOS, that's every
+/// interrupt above `configMAX_SYSCALL_INTERRUPT_PRIORITY`.
+///
+/// [`suspend`](crate::suspend), [`with_max_priority`](crate::with_max_priority) and the
+/// hardware deadlines of `with_budget` are forwarded in the same way, except that `suspend`
+/// always falls back to a critical section in interrupt context.
+pub struct Dual<Thread, Isr> {
+    _0: PhantomData<(Thread, Isr)>,
+}
+
+unsafe impl<Thread: Impl, Isr: Impl + InterruptContext> Impl for Dual<Thread, Isr> {
+    #[inline]
+    unsafe fn acquire() -> RawRestoreState {
+        if in_isr::<Isr>() {
+            Isr::acquire()
+        } else {
+            Thread::acquire()
+        }
+    }
+
+    #[inline]
+    unsafe fn release(restore_state: RawRestoreState) {
+        if in_isr::<Isr>() {
+            Isr::release(restore_state)
+        } else {
+            Thread::release(restore_state)
+        }
+    }
+
+    #[inline]
+    unsafe fn try_acquire() -> Option<RawRestoreState> {
+        if in_isr::<Isr>() {
+            Isr::try_acquire()
+        } else {
+            Thread::try_acquire()
+        }
+    }
+
+    #[inline]
+    unsafe fn acquire_priority(priority: u8) -> Option<RawRestoreState> {
+        if in_isr::<Isr>() {
+            Isr::acquire_priority(priority)
+        } else {
+            Thread::acquire_priority(priority)
+        }
+    }
+
+    #[inline]
+    unsafe fn release_priority(restore_state: RawRestoreState) {
+        if in_isr::<Isr>() {
+            Isr::release_priority(restore_state)
+        } else {
+            Thread::release_priority(restore_state)
+        }
+    }
+
+    #[inline]
+    unsafe fn arm_deadline(cycles: u32) -> bool {
+        if in_isr::<Isr>() {
+            Isr::arm_deadline(cycles)
+        } else {
+            Thread::arm_deadline(cycles)
+        }
+    }
+
+    #[inline]
+    unsafe fn disarm_deadline() -> bool {
+        if in_isr::<Isr>() {
+            Isr::disarm_deadline()
+        } else {
+            Thread::disarm_deadline()
+        }
+    }
+
+    #[inline]
+    unsafe fn suspend_scheduler() -> bool {
+        !in_isr::<Isr>() && Thread::suspend_scheduler()
+    }
+
+    #[inline]
+    unsafe fn resume_scheduler() {
+        Thread::resume_scheduler()
+    }
+
+    #[inline]
+    unsafe fn in_isr() -> bool {
+        in_isr::<Isr>()
+    }
+}
+
+#[inline]
+fn in_isr<Isr: InterruptContext>() -> bool {
+    Isr::in_isr()
+}
+
+/// Telling apart thread and interrupt context, for the `Isr` implementation of [`Dual`].
+///
+/// Unlike [`Impl::in_isr`], which may give up and return `true`, this has no default: `Dual`
+/// can't pick the right implementation without it.
+///
+/// # Safety
+///
+/// `in_isr` must return `true` exactly when called from an interrupt handler.
+pub unsafe trait InterruptContext {
+    /// Returns whether the current context is an interrupt handler.
+    fn in_isr() -> bool;
+}
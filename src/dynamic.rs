@@ -0,0 +1,172 @@
+//! Critical section implementation that can be replaced once at runtime.
+//!
+//! With the `dynamic-impl` Cargo feature enabled, the critical section calls the functions of
+//! a [`Table`] installed with [`set`]. The first call installs the initial implementation, and
+//! a second call replaces it. This is meant for firmware where the right implementation
+//! changes once during startup, for example a bootloader that disables interrupts globally
+//! and then hands off to an RTOS whose scheduler lock must be used from then on.
+//!
+//! ```no_run
+//! use critical_section::dynamic::{self, Table};
+//! use critical_section::RawRestoreState;
+//!
+//! unsafe fn boot_acquire() -> RawRestoreState {
+//!     // Disable interrupts.
+//! }
+//! unsafe fn boot_release(_: RawRestoreState) {
+//!     // Restore interrupts.
+//! }
+//! unsafe fn rtos_acquire() -> RawRestoreState {
+//!     // Lock the scheduler.
+//! }
+//! unsafe fn rtos_release(_: RawRestoreState) {
+//!     // Unlock the scheduler.
+//! }
+//!
+//! static BOOT: Table = Table::new(boot_acquire, boot_release);
+//! static RTOS: Table = Table::new(rtos_acquire, rtos_release);
+//!
+//! fn main() {
+//!     dynamic::set(&BOOT).unwrap();
+//!     // ...
+//!     // After starting the kernel:
+//!     dynamic::set(&RTOS).unwrap();
+//! }
+//! ```
+//!
+//! The implementation can't be replaced while a critical section is active, since it would
+//! then be released through a different implementation than it was acquired with. Both
+//! implementations share the restore state type selected with the `restore-state-*` features.
+//!
+//! This requires atomic pointers.
+
+use core::fmt;
+use core::ptr;
+use core::sync::atomic::{AtomicBool, AtomicPtr, Ordering};
+
+use super::{state, RawRestoreState};
+
+/// A critical section implementation, as a pair of functions.
+///
+/// The functions must uphold the contract of [`Impl`](crate::Impl).
+#[derive(Debug)]
+pub struct Table {
+    acquire: unsafe fn() -> RawRestoreState,
+    release: unsafe fn(RawRestoreState),
+}
+
+impl Table {
+    /// Creates an implementation from its acquire and release functions.
+    #[inline]
+    pub const fn new(
+        acquire: unsafe fn() -> RawRestoreState,
+        release: unsafe fn(RawRestoreState),
+    ) -> Self {
+        Table { acquire, release }
+    }
+}
+
+/// Error returned by [`set`] when the implementation can't be replaced.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SetError {
+    /// A critical section is active.
+    Active,
+    /// The implementation was already replaced once.
+    AlreadyReplaced,
+}
+
+impl fmt::Display for SetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SetError::Active => "a critical section is active",
+            SetError::AlreadyReplaced => "the critical section implementation was already replaced",
+        })
+    }
+}
+
+static TABLE: AtomicPtr<Table> = AtomicPtr::new(ptr::null_mut());
+static REPLACED: AtomicBool = AtomicBool::new(false);
+
+/// Install or replace the critical section implementation.
+///
+/// The first call installs `table` as the initial implementation. The second call replaces it,
+/// and fails with [`SetError::Active`] if a critical section is active in any context. Once
+/// replaced, the implementation can't be changed anymore.
+///
+/// Replacing acquires the critical section through the initial implementation, so it's safe
+/// against other contexts entering a critical section at the same time.
+pub fn set(table: &'static Table) -> Result<(), SetError> {
+    let new = table as *const Table as *mut Table;
+    if TABLE
+        .compare_exchange(ptr::null_mut(), new, Ordering::AcqRel, Ordering::Acquire)
+        .is_ok()
+    {
+        return Ok(());
+    }
+
+    unsafe {
+        let (old, restore_state) = acquire_current();
+        // The nesting depth counts the critical sections entered through the crate, in all
+        // contexts, since they share a single critical section.
+        let result = if state::depth() != 0 {
+            Err(SetError::Active)
+        } else if REPLACED.swap(true, Ordering::Relaxed) {
+            Err(SetError::AlreadyReplaced)
+        } else {
+            TABLE.store(new, Ordering::Release);
+            Ok(())
+        };
+        release_with(old, restore_state);
+        result
+    }
+}
+
+#[inline]
+#[track_caller]
+fn current() -> &'static Table {
+    let table = TABLE.load(Ordering::Acquire);
+    if table.is_null() {
+        panic!("critical section used before calling dynamic::set");
+    }
+    unsafe { &*table }
+}
+
+/// Acquire the critical section through the current implementation.
+///
+/// The implementation might be replaced between loading it and acquiring it, in which case
+/// the critical section is released again and acquired through the new implementation.
+#[inline]
+#[allow(clippy::let_unit_value)] // `RawRestoreState` is `()` by default.
+unsafe fn acquire_current() -> (&'static Table, RawRestoreState) {
+    loop {
+        let table = current();
+        let restore_state = (table.acquire)();
+        if ptr::eq(table, TABLE.load(Ordering::Acquire)) {
+            return (table, restore_state);
+        }
+        release_with(table, restore_state);
+    }
+}
+
+#[inline]
+#[allow(clippy::unit_arg)] // `RawRestoreState` is `()` by default.
+unsafe fn release_with(table: &Table, restore_state: RawRestoreState) {
+    (table.release)(restore_state)
+}
+
+struct DynamicCriticalSection;
+crate::set_impl!(DynamicCriticalSection);
+
+unsafe impl crate::Impl for DynamicCriticalSection {
+    #[inline]
+    unsafe fn acquire() -> RawRestoreState {
+        acquire_current().1
+    }
+
+    #[inline]
+    unsafe fn release(restore_state: RawRestoreState) {
+        // The implementation can't be replaced while the critical section is held, so this is
+        // the one it was acquired with.
+        release_with(current(), restore_state)
+    }
+}
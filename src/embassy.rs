@@ -0,0 +1,55 @@
+//! Integration with `embassy-sync`.
+//!
+//! With the `embassy-sync` Cargo feature enabled, [`CriticalSectionRawMutex`] implements
+//! `embassy_sync::blocking_mutex::raw::RawMutex`, so code generic over embassy's mutex
+//! abstraction can pick it without naming embassy's own types:
+//!
+//! ```no_run
+//! use critical_section::embassy::CriticalSectionRawMutex;
+//! use embassy_sync::channel::Channel;
+//!
+//! static EVENTS: Channel<CriticalSectionRawMutex, u32, 4> = Channel::new();
+//! ```
+//!
+//! It behaves exactly like `embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex`, and
+//! the two can be used side by side.
+//!
+//! `embassy-sync` depends on a released version of critical-section, which calls the same
+//! implementation through the same link symbols. Its `restore-state-*` feature must match the
+//! one enabled on this crate, or the two disagree on the type of the restore state.
+
+use embassy_sync::blocking_mutex::raw::RawMutex;
+
+/// A raw mutex locked by entering the critical section, for `embassy-sync`.
+///
+/// See the [module docs](self).
+#[derive(Debug)]
+pub struct CriticalSectionRawMutex {
+    _0: (),
+}
+
+impl CriticalSectionRawMutex {
+    /// Creates a new raw mutex.
+    #[inline]
+    pub const fn new() -> Self {
+        CriticalSectionRawMutex { _0: () }
+    }
+}
+
+impl Default for CriticalSectionRawMutex {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// NOTE The critical section excludes every other thread and interrupt handler, and nests
+// within the current one, as `RawMutex` requires.
+unsafe impl RawMutex for CriticalSectionRawMutex {
+    const INIT: Self = Self::new();
+
+    #[inline]
+    fn lock<R>(&self, f: impl FnOnce() -> R) -> R {
+        crate::with(|_| f())
+    }
+}
@@ -0,0 +1,49 @@
+//! Turning the critical section into a no-op for crash handling.
+
+use core::sync::atomic::{compiler_fence, fence, AtomicBool, Ordering};
+
+static EMERGENCY: AtomicBool = AtomicBool::new(false);
+
+/// Stop using the critical section implementation, for the rest of the execution.
+///
+/// From the next acquire on, critical sections only do the crate's bookkeeping, and never
+/// call the implementation, like with [`with_from_isr`](crate::with_from_isr). Panic
+/// handlers, fault handlers and crash loggers can then reuse normal driver code, which calls
+/// [`with`](crate::with) internally, without deadlocking on a spinlock held by another core
+/// or a half-finished critical section, and without an RTOS call from a context where it's
+/// not allowed. Critical sections already held are released without calling the
+/// implementation either, so interrupts stay masked if they were.
+///
+/// This can't be undone.
+///
+/// ```no_run
+/// # fn uart_write(_: &str) {}
+/// // Called from the panic handler.
+/// fn report_panic() -> ! {
+///     // Mask interrupts and stop the other cores...
+///     unsafe { critical_section::enter_emergency_mode() };
+///     // The UART driver uses `critical_section::with`, which is now a no-op.
+///     uart_write("panicked");
+///     loop {}
+/// }
+/// ```
+///
+/// # Safety
+///
+/// Nothing else may use the critical section from now on, since it no longer excludes
+/// anything: interrupt handlers using it must be masked, and other cores stopped, before
+/// calling this.
+#[cold]
+pub unsafe fn enter_emergency_mode() {
+    EMERGENCY.store(true, Ordering::Relaxed);
+    // Keep the accesses of the crash handler after this point, and make the writes of other
+    // cores, made before they were stopped, visible.
+    compiler_fence(Ordering::SeqCst);
+    fence(Ordering::SeqCst);
+}
+
+/// Returns whether [`enter_emergency_mode`] was called.
+#[inline]
+pub fn in_emergency_mode() -> bool {
+    EMERGENCY.load(Ordering::Relaxed)
+}
@@ -0,0 +1,74 @@
+//! Critical section implementation calling functions set at runtime.
+//!
+//! With the `extern-fns` Cargo feature enabled, the critical section is implemented by calling
+//! a pair of `extern "C"` functions registered with [`set`]. This is meant for firmware that
+//! only receives its locking primitives at runtime, such as from a vendor SDK or a ROM
+//! function table.
+//!
+//! ```no_run
+//! extern "C" {
+//!     fn vendor_enter_critical();
+//!     fn vendor_exit_critical();
+//! }
+//!
+//! fn main() {
+//!     critical_section::extern_fns::set(vendor_enter_critical, vendor_exit_critical);
+//!     // The critical section can be used from here on.
+//! }
+//! ```
+//!
+//! The functions don't pass a restore state, so they must handle nesting themselves, for
+//! example with a nesting counter like most RTOSes do.
+
+use core::ptr;
+use core::sync::atomic::{AtomicBool, AtomicPtr, Ordering};
+
+type Fn = unsafe extern "C" fn();
+
+// Function pointers can't be stored in atomics directly.
+static ENTER: AtomicPtr<()> = AtomicPtr::new(ptr::null_mut());
+static EXIT: AtomicPtr<()> = AtomicPtr::new(ptr::null_mut());
+/// Set on the first acquire, after which the functions can't be changed anymore.
+static USED: AtomicBool = AtomicBool::new(false);
+
+/// Set the functions called to enter and exit the critical section.
+///
+/// This must be called before the critical section is first used. It can be called more than
+/// once before that, the last call wins.
+///
+/// # Panics
+///
+/// Panics if the critical section has already been used.
+pub fn set(enter: unsafe extern "C" fn(), exit: unsafe extern "C" fn()) {
+    if USED.load(Ordering::Acquire) {
+        panic!("extern_fns::set called after the critical section was first used");
+    }
+    ENTER.store(enter as *mut (), Ordering::Relaxed);
+    EXIT.store(exit as *mut (), Ordering::Release);
+}
+
+#[inline]
+fn load(f: &AtomicPtr<()>) -> Fn {
+    let f = f.load(Ordering::Acquire);
+    if f.is_null() {
+        panic!("critical section used before calling extern_fns::set");
+    }
+    unsafe { core::mem::transmute::<*mut (), Fn>(f) }
+}
+
+struct ExternFnsCriticalSection;
+crate::set_impl!(ExternFnsCriticalSection);
+
+unsafe impl crate::Impl for ExternFnsCriticalSection {
+    #[inline]
+    unsafe fn acquire() {
+        let enter = load(&ENTER);
+        USED.store(true, Ordering::Release);
+        enter()
+    }
+
+    #[inline]
+    unsafe fn release(_: ()) {
+        load(&EXIT)()
+    }
+}
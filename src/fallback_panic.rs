@@ -0,0 +1,21 @@
+use crate::RawRestoreState;
+
+struct FallbackPanic;
+crate::set_impl!(FallbackPanic);
+
+unsafe impl crate::Impl for FallbackPanic {
+    #[cold]
+    #[track_caller]
+    unsafe fn acquire() -> RawRestoreState {
+        panic!(
+            "critical section acquired, but only the `fallback-panic` implementation of the \
+             critical-section crate is enabled. Enable a built-in implementation with a Cargo \
+             feature such as `std`, or link a crate that provides one with `set_impl!`."
+        )
+    }
+
+    #[cold]
+    unsafe fn release(_: RawRestoreState) {
+        unreachable!()
+    }
+}
@@ -0,0 +1,63 @@
+//! Reading protected data from fault handlers.
+
+use core::sync::atomic::{compiler_fence, fence, AtomicBool, Ordering};
+
+use crate::Mutex;
+
+static STOLEN: AtomicBool = AtomicBool::new(false);
+
+/// Borrow the data of `mutex` without entering the critical section, to read diagnostic state
+/// from a fault handler.
+///
+/// A hard fault can happen in the middle of a critical section. Calling [`with`](crate::with)
+/// from the fault handler then deadlocks on multi-core systems, or with implementations that
+/// don't nest, and in any case can't stop the faulted code from having left the data half
+/// updated. This gives the fault handler access anyway, under this protocol:
+///
+/// 1. The fault handler never returns to the interrupted code, and all other code that could
+///    access `mutex` is stopped or can no longer run, such as the other cores being halted.
+/// 2. The data is only read. It may be inconsistent, so it should be made of plain values
+///    that are valid for any bit pattern written so far, such as counters and `Cell`s of
+///    integers, and be checked before use.
+/// 3. Code shared with normal operation, such as a logger called by the fault handler, checks
+///    [`was_stolen`] and treats protected data as possibly inconsistent if it returns `true`.
+///
+/// Memory barriers are issued before the data is borrowed, so the handler sees every write
+/// the faulted code made before the fault, also on a different core.
+///
+/// ```no_run
+/// use core::cell::Cell;
+/// use critical_section::Mutex;
+///
+/// static LAST_COMMAND: Mutex<Cell<u8>> = Mutex::new(Cell::new(0));
+///
+/// # fn report(_: u8) {}
+/// // In the `HardFault` handler:
+/// let last_command = unsafe { critical_section::steal_for_fault_handler(&LAST_COMMAND) };
+/// report(last_command.get());
+/// ```
+///
+/// # Safety
+///
+/// The caller must follow the protocol above. In particular, `T` must not hold a reference
+/// that was mutably borrowed when the fault happened, such as a `RefCell` in the middle of a
+/// `borrow_mut`, and the returned reference must not be used to write.
+#[cold]
+pub unsafe fn steal_for_fault_handler<T>(mutex: &Mutex<T>) -> &T {
+    STOLEN.store(true, Ordering::Relaxed);
+    // Writes by the faulted code on the current core are visible in program order, so only
+    // the compiler must not move reads of the data before this point. The fence also covers
+    // writes by other cores, made before they were stopped.
+    compiler_fence(Ordering::SeqCst);
+    fence(Ordering::SeqCst);
+    &*mutex.as_ptr()
+}
+
+/// Returns whether [`steal_for_fault_handler`] was called since startup.
+///
+/// Once this returns `true`, data protected by the critical section may be inconsistent, see
+/// the protocol there. It is never reset.
+#[inline]
+pub fn was_stolen() -> bool {
+    STOLEN.load(Ordering::Relaxed)
+}
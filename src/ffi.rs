@@ -0,0 +1,127 @@
+//! Entering the critical section from C.
+//!
+//! With the `ffi` Cargo feature enabled, this crate exports `cs_acquire` and `cs_release`, so
+//! C code linked into the same firmware enters the same critical section as the Rust code,
+//! through the same implementation and nesting bookkeeping. A critical section acquired in C
+//! may call into Rust code using [`with`](crate::with), and the other way around.
+//!
+//! The restore state is passed as a [`CRestoreState`], whose layout doesn't depend on the
+//! `restore-state-*` Cargo features. The matching C declarations are:
+//!
+//! ```c
+//! #include <stdint.h>
+//!
+//! typedef struct {
+//!     uint64_t raw;
+//! } cs_restore_state_t;
+//!
+//! cs_restore_state_t cs_acquire(void);
+//! void cs_release(cs_restore_state_t state);
+//! ```
+//!
+//! The calls must follow the contract of [`acquire`](crate::acquire) and
+//! [`release`](crate::release): each `cs_acquire` is paired with one `cs_release` in the same
+//! context, with the state it returned, and pairs are properly nested, also with the critical
+//! sections entered by Rust code.
+
+use super::{RawRestoreState, RestoreState};
+
+/// A restore state with a fixed layout, for passing across the C ABI.
+///
+/// It holds the [`RawRestoreState`] selected through Cargo features, widened to 64 bits. C
+/// code should treat it as opaque.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct CRestoreState {
+    raw: u64,
+}
+
+/// Acquire the critical section, for C code.
+///
+/// # Safety
+///
+/// See [`acquire`](crate::acquire).
+#[no_mangle]
+pub unsafe extern "C" fn cs_acquire() -> CRestoreState {
+    let restore_state = crate::acquire();
+    CRestoreState {
+        raw: widen(restore_state.0),
+    }
+}
+
+/// Release the critical section, for C code.
+///
+/// # Safety
+///
+/// `state` must be the one returned by the matching [`cs_acquire`] call. See
+/// [`release`](crate::release).
+#[no_mangle]
+pub unsafe extern "C" fn cs_release(state: CRestoreState) {
+    crate::release(RestoreState(narrow(state.raw)));
+}
+
+#[inline(always)]
+#[allow(clippy::useless_conversion, clippy::unnecessary_cast)]
+fn widen(restore_state: RawRestoreState) -> u64 {
+    #[cfg(not(any(
+        feature = "restore-state-bool",
+        feature = "restore-state-u8",
+        feature = "restore-state-u16",
+        feature = "restore-state-u32",
+        feature = "restore-state-u64",
+        feature = "restore-state-usize",
+        feature = "restore-state-opaque"
+    )))]
+    return {
+        let () = restore_state;
+        0
+    };
+
+    #[cfg(feature = "restore-state-usize")]
+    return restore_state as u64;
+
+    #[cfg(feature = "restore-state-opaque")]
+    return restore_state.bits() as u64;
+
+    #[cfg(any(
+        feature = "restore-state-bool",
+        feature = "restore-state-u8",
+        feature = "restore-state-u16",
+        feature = "restore-state-u32",
+        feature = "restore-state-u64"
+    ))]
+    return u64::from(restore_state);
+}
+
+#[inline(always)]
+#[allow(clippy::unnecessary_cast)]
+fn narrow(raw: u64) -> RawRestoreState {
+    // The value always comes from `widen`, so these are lossless.
+    #[cfg(not(any(
+        feature = "restore-state-bool",
+        feature = "restore-state-u8",
+        feature = "restore-state-u16",
+        feature = "restore-state-u32",
+        feature = "restore-state-u64",
+        feature = "restore-state-usize",
+        feature = "restore-state-opaque"
+    )))]
+    return {
+        let _ = raw;
+    };
+
+    #[cfg(feature = "restore-state-bool")]
+    return raw != 0;
+
+    #[cfg(any(
+        feature = "restore-state-u8",
+        feature = "restore-state-u16",
+        feature = "restore-state-u32",
+        feature = "restore-state-u64",
+        feature = "restore-state-usize"
+    ))]
+    return raw as RawRestoreState;
+
+    #[cfg(feature = "restore-state-opaque")]
+    return crate::OpaqueRestoreState::new(raw as usize);
+}
@@ -0,0 +1,102 @@
+//! Critical section implementation for FreeRTOS.
+//!
+//! With the `freertos` Cargo feature enabled, the critical section is implemented with
+//! `taskENTER_CRITICAL`/`taskEXIT_CRITICAL` in task context, and with
+//! `taskENTER_CRITICAL_FROM_ISR`/`taskEXIT_CRITICAL_FROM_ISR` in interrupt context. Which one
+//! to use is decided at runtime on each acquire.
+//!
+//! These are macros, whose definition depends on the FreeRTOS port, so they can't be called
+//! from Rust directly. The application must provide these `extern "C"` functions instead,
+//! usually in a C file compiled along with FreeRTOS:
+//!
+//! ```c
+//! #include "FreeRTOS.h"
+//! #include "task.h"
+//!
+//! void critical_section_freertos_enter(void) { taskENTER_CRITICAL(); }
+//! void critical_section_freertos_exit(void) { taskEXIT_CRITICAL(); }
+//!
+//! uint32_t critical_section_freertos_enter_from_isr(void) {
+//!     return (uint32_t)taskENTER_CRITICAL_FROM_ISR();
+//! }
+//! void critical_section_freertos_exit_from_isr(uint32_t mask) {
+//!     taskEXIT_CRITICAL_FROM_ISR((UBaseType_t)mask);
+//! }
+//!
+//! int critical_section_freertos_in_isr(void) { return xPortIsInsideInterrupt(); }
+//! ```
+//!
+//! Ports without `xPortIsInsideInterrupt` need another way to check for interrupt context,
+//! such as reading the IPSR register on Cortex-M.
+//!
+//! `taskENTER_CRITICAL` only masks interrupts up to `configMAX_SYSCALL_INTERRUPT_PRIORITY` on
+//! most ports. Interrupts above that priority aren't allowed to call FreeRTOS APIs, and must
+//! not use the critical section either.
+//!
+//! [`suspend`](crate::suspend) is supported in task context, with `vTaskSuspendAll` and
+//! `xTaskResumeAll`.
+//!
+//! Both variants nest: FreeRTOS counts nesting in task context, and the mask returned in
+//! interrupt context is kept in the restore state. This implementation uses a `u64` restore
+//! state, so enabling the `freertos` Cargo feature also enables `restore-state-u64`.
+
+use core::ffi::c_int;
+
+extern "C" {
+    fn critical_section_freertos_enter();
+    fn critical_section_freertos_exit();
+    fn critical_section_freertos_enter_from_isr() -> u32;
+    fn critical_section_freertos_exit_from_isr(mask: u32);
+    fn critical_section_freertos_in_isr() -> c_int;
+    fn vTaskSuspendAll();
+    fn xTaskResumeAll() -> c_int;
+}
+
+/// Restore state bit: acquired in interrupt context. The lower 32 bits hold the mask.
+const FROM_ISR: u64 = 1 << 63;
+
+struct FreeRtosCriticalSection;
+crate::set_impl!(FreeRtosCriticalSection);
+
+unsafe impl crate::Impl for FreeRtosCriticalSection {
+    #[inline]
+    unsafe fn acquire() -> u64 {
+        if critical_section_freertos_in_isr() != 0 {
+            FROM_ISR | u64::from(critical_section_freertos_enter_from_isr())
+        } else {
+            critical_section_freertos_enter();
+            0
+        }
+    }
+
+    #[inline]
+    unsafe fn release(restore_state: u64) {
+        // Decided by the restore state rather than checking again, so acquire and release
+        // always match.
+        if restore_state & FROM_ISR != 0 {
+            critical_section_freertos_exit_from_isr(restore_state as u32);
+        } else {
+            critical_section_freertos_exit();
+        }
+    }
+
+    #[inline]
+    unsafe fn suspend_scheduler() -> bool {
+        // The scheduler can't be suspended from interrupts, fall back to a critical section.
+        if critical_section_freertos_in_isr() != 0 {
+            return false;
+        }
+        vTaskSuspendAll();
+        true
+    }
+
+    #[inline]
+    unsafe fn resume_scheduler() {
+        xTaskResumeAll();
+    }
+
+    #[inline]
+    unsafe fn in_isr() -> bool {
+        critical_section_freertos_in_isr() != 0
+    }
+}
@@ -0,0 +1,245 @@
+use core::marker::PhantomData;
+
+use super::{
+    acquire, acquire_raw, release, release_raw, state, try_acquire, CriticalSection, RestoreState,
+};
+
+/// RAII guard for a critical section.
+///
+/// Created by [`enter`]. The critical section is released when the guard is dropped.
+///
+/// Guards should be dropped in the reverse order they were created in. Dropping them out of
+/// order releases one nesting level all the same, and the critical section stays held until
+/// the last guard is dropped, but it panics in debug builds, unless the thread is already
+/// panicking with the `std` Cargo feature. Dropping a guard while the critical section is
+/// suspended by [`without`](crate::without) always panics.
+///
+/// The guard is neither `Send` nor `Sync`, since the critical section must be released
+/// in the same thread that acquired it.
+#[must_use = "the critical section is released immediately if the guard is not held"]
+#[derive(Debug)]
+pub struct Guard {
+    restore_state: RestoreState,
+    pub(crate) depth: usize,
+    _not_send: PhantomData<*mut ()>,
+}
+
+/// Acquire a critical section in the current thread, returning a guard that releases it on drop.
+///
+/// This is an alternative to the closure-based [`with`](crate::with), for cases where the
+/// critical section has to be held across early returns and `?`.
+///
+/// Nesting critical sections is allowed. The inner critical sections
+/// are mostly no-ops since they're already protected by the outer one.
+///
+/// ```no_run
+/// # use critical_section::Mutex;
+/// # use std::cell::Cell;
+/// static COUNTER: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+///
+/// fn increment() -> Result<u32, ()> {
+///     let guard = critical_section::enter();
+///     let counter = COUNTER.borrow(guard.token());
+///     let value = counter.get().checked_add(1).ok_or(())?;
+///     counter.set(value);
+///     Ok(value)
+/// }
+/// ```
+#[inline(always)]
+#[cfg_attr(
+    any(feature = "debug", feature = "defmt-trace", feature = "log-trace"),
+    track_caller
+)]
+pub fn enter() -> Guard {
+    unsafe {
+        let restore_state = acquire();
+        Guard {
+            restore_state,
+            depth: state::depth(),
+            _not_send: PhantomData,
+        }
+    }
+}
+
+/// [`enter`], or `None` if another context holds the critical section, see
+/// [`try_acquire`](crate::try_acquire).
+#[inline(always)]
+pub(crate) fn try_enter() -> Option<Guard> {
+    unsafe {
+        let restore_state = try_acquire()?;
+        Some(Guard {
+            restore_state,
+            depth: state::depth(),
+            _not_send: PhantomData,
+        })
+    }
+}
+
+/// Execute a block in a critical section, with the token bound to a name.
+///
+/// `scope!(cs => { ... })` is like [`with`](crate::with), but the block is not a closure: it
+/// can `return` from, and use `?` in, the surrounding function, and `break` out of
+/// surrounding loops. The critical section is released at the end of the block, or when
+/// leaving it early.
+///
+/// ```no_run
+/// use critical_section::Mutex;
+/// use std::cell::Cell;
+///
+/// static BALANCE: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+///
+/// fn withdraw(amount: u32) -> Result<u32, ()> {
+///     critical_section::scope!(cs => {
+///         let balance = BALANCE.borrow(cs);
+///         let left = balance.get().checked_sub(amount).ok_or(())?;
+///         balance.set(left);
+///         Ok(left)
+///     })
+/// }
+/// ```
+///
+/// Use `_` instead of a name if the token isn't needed. With the `macros` Cargo feature, the
+/// `critical_section` attribute does the same for a whole function.
+#[macro_export]
+macro_rules! scope {
+    ($cs:pat => $body:block) => {{
+        let guard = $crate::enter();
+        let $cs = guard.token();
+        $body
+    }};
+}
+
+impl Guard {
+    /// Get a critical section token, valid for as long as the guard is borrowed.
+    #[inline(always)]
+    pub fn token(&self) -> CriticalSection<'_> {
+        unsafe { CriticalSection::new() }
+    }
+
+    /// Briefly release the critical section, so pending interrupts can run, and acquire it
+    /// again, returning whether it was released.
+    ///
+    /// This inserts points of bounded interrupt latency into a long loop, without splitting
+    /// it into several [`with`](crate::with) calls:
+    ///
+    /// ```no_run
+    /// # use critical_section::Mutex;
+    /// # use std::cell::RefCell;
+    /// static TABLE: Mutex<RefCell<[u32; 1024]>> = Mutex::new(RefCell::new([0; 1024]));
+    ///
+    /// let mut guard = critical_section::enter();
+    /// for i in 0..1024 {
+    ///     TABLE.borrow_ref_mut(guard.token())[i] = i as u32;
+    ///     if i % 64 == 63 {
+    ///         guard.yield_point();
+    ///     }
+    /// }
+    /// ```
+    ///
+    /// This takes `&mut self`, so the borrow checker ensures that no token from
+    /// [`token`](Self::token), and no reference obtained with one, is used across the gap.
+    /// A plain [`CriticalSection`] token can't offer this, since it's `Copy` and data borrowed
+    /// with it lives as long as the closure passed to `with`.
+    ///
+    /// If this guard is nested in another critical section, nothing is released and `false`
+    /// is returned.
+    ///
+    /// With the `watchdog` Cargo feature, this is also a checkpoint for `configure_watchdog`.
+    #[inline]
+    pub fn yield_point(&mut self) -> bool {
+        #[cfg(feature = "watchdog")]
+        crate::watchdog_checkpoint(self.token());
+        unsafe {
+            if state::depth() != 1 {
+                return false;
+            }
+            crate::without(|| {});
+        }
+        true
+    }
+
+    /// Release the critical section now, instead of at the end of the scope.
+    ///
+    /// This is the same as dropping the guard, and makes early releases explicit. Tokens from
+    /// [`token`](Self::token) can't outlive it.
+    ///
+    /// ```no_run
+    /// # fn read_sensor() -> u32 { 0 }
+    /// # fn log(_: u32) {}
+    /// let guard = critical_section::enter();
+    /// let value = read_sensor();
+    /// guard.release();
+    /// // Logging is slow, so do it with interrupts enabled.
+    /// log(value);
+    /// ```
+    #[inline(always)]
+    pub fn release(self) {
+        drop(self)
+    }
+
+    /// Keep the critical section acquired without the guard, returning the restore state to
+    /// release it with.
+    ///
+    /// This hands the critical section over to code that releases it with
+    /// [`release`](crate::release), such as the unlock function of a C library's port layer:
+    ///
+    /// ```no_run
+    /// use critical_section::RestoreState;
+    ///
+    /// unsafe fn sys_arch_unprotect(restore_state: RestoreState) {
+    ///     critical_section::release(restore_state)
+    /// }
+    ///
+    /// let guard = critical_section::enter();
+    /// // ...
+    /// let restore_state = guard.leak();
+    /// unsafe { sys_arch_unprotect(restore_state) };
+    /// ```
+    ///
+    /// The critical section is held until then, and the same safety rules as for a restore
+    /// state returned by [`acquire`](crate::acquire) apply.
+    #[inline(always)]
+    #[must_use = "the restore state must be passed to `release`"]
+    pub fn leak(self) -> RestoreState {
+        let restore_state = self.restore_state;
+        core::mem::forget(self);
+        restore_state
+    }
+}
+
+impl Drop for Guard {
+    #[inline(always)]
+    fn drop(&mut self) {
+        unsafe {
+            let depth = state::depth();
+            if depth == self.depth {
+                release(self.restore_state);
+                return;
+            }
+            assert!(
+                depth != 0,
+                "critical section guard dropped while it's suspended"
+            );
+            debug_assert!(panicking(), "critical section guards dropped out of order");
+            // This guard may hold the outermost restore state while others are still nested in
+            // it. Release a nesting level with the restore state of a nested acquire instead:
+            // they're interchangeable, and the outermost release uses the recorded outermost
+            // restore state anyway.
+            let nested = acquire_raw();
+            release_raw(nested);
+            release(nested);
+        }
+    }
+}
+
+/// Returns whether the current thread is panicking, if that can be told.
+#[inline(always)]
+fn panicking() -> bool {
+    #[cfg(feature = "std")]
+    {
+        extern crate std;
+        std::thread::panicking()
+    }
+    #[cfg(not(feature = "std"))]
+    false
+}
@@ -0,0 +1,168 @@
+use core::cell::Cell;
+use core::marker::PhantomData;
+#[cfg(debug_assertions)]
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use super::{with, CriticalSection, Mutex};
+
+/// Set once [`init`] has been called.
+static INITIALIZED: Mutex<Cell<bool>> = Mutex::new(Cell::new(false));
+
+/// Token for the one-time initialization region entered with [`init`].
+///
+/// This proves that the code holding it runs inside a critical section, during startup,
+/// exactly once. It can be downgraded to a regular [`CriticalSection`] token with
+/// [`cs`](InitToken::cs).
+///
+/// The token is only valid for the duration of the closure passed to [`init`], not `'static`.
+/// A `'static` token would allow borrowing a [`Mutex`] for the rest of the program, outside of
+/// any critical section, which would be unsound. Data set up during `init` has to be accessed
+/// through [`with`] afterwards, like any other data protected by the critical section.
+#[derive(Debug)]
+pub struct InitToken<'cs> {
+    _0: PhantomData<&'cs ()>,
+    // Not `Copy` or `Send`, so it can't leave the initialization region.
+    _not_send: PhantomData<*mut ()>,
+}
+
+impl<'cs> InitToken<'cs> {
+    /// Get a critical section token, valid for as long as the initialization region.
+    #[inline]
+    pub fn cs(&self) -> CriticalSection<'cs> {
+        unsafe { CriticalSection::new_unchecked() }
+    }
+}
+
+/// Execute closure `f` once at startup, inside a critical section.
+///
+/// This is meant for initializing data shared with interrupt handlers, before they are
+/// enabled. Unlike [`with`], this asserts at runtime that it's only ever called once, so
+/// initialization code can't accidentally run again and overwrite live state.
+///
+/// ```no_run
+/// use critical_section::Mutex;
+/// use std::cell::RefCell;
+///
+/// static BUFFER: Mutex<RefCell<Option<[u8; 64]>>> = Mutex::new(RefCell::new(None));
+///
+/// fn main() {
+///     critical_section::init(|token| {
+///         BUFFER.borrow(token.cs()).replace(Some([0; 64]));
+///     });
+///
+///     // Later, in the main loop and in interrupt handlers:
+///     critical_section::with(|cs| {
+///         if let Some(buffer) = BUFFER.borrow_ref_mut(cs).as_mut() {
+///             buffer[0] = 1;
+///         }
+///     });
+/// }
+/// ```
+///
+/// # Panics
+///
+/// Panics if called more than once, or from inside `f`.
+#[inline]
+#[track_caller]
+pub fn init<R>(f: impl FnOnce(InitToken) -> R) -> R {
+    with(|cs| {
+        if INITIALIZED.borrow(cs).replace(true) {
+            panic!("critical_section::init called more than once");
+        }
+        f(InitToken {
+            _0: PhantomData,
+            _not_send: PhantomData,
+        })
+    })
+}
+
+/// [`PreKernel`] phase, only tracked in debug builds.
+#[cfg(debug_assertions)]
+static PHASE: AtomicU8 = AtomicU8::new(PHASE_NONE);
+#[cfg(debug_assertions)]
+const PHASE_NONE: u8 = 0;
+#[cfg(debug_assertions)]
+const PHASE_PRE_KERNEL: u8 = 1;
+#[cfg(debug_assertions)]
+const PHASE_SEALED: u8 = 2;
+
+/// Capability for an early program phase that runs with interrupts disabled, such as a
+/// bootloader or the startup code before the scheduler starts.
+///
+/// Obtained once with [`assume_interrupts_disabled`]. While it exists, [`token`](Self::token)
+/// hands out critical section tokens without acquiring the critical section, so `Mutex`
+/// statics can be used without paying for masking that is already in effect:
+///
+/// ```no_run
+/// use critical_section::Mutex;
+/// use std::cell::Cell;
+///
+/// static BOOT_COUNT: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+///
+/// # fn start_scheduler() {}
+/// fn main() {
+///     // Interrupts are still disabled after reset.
+///     let pre_kernel = unsafe { critical_section::assume_interrupts_disabled() };
+///     let count = BOOT_COUNT.borrow(pre_kernel.token());
+///     count.set(count.get() + 1);
+///
+///     pre_kernel.seal();
+///     start_scheduler();
+/// }
+/// ```
+///
+/// References borrowed with its tokens can't outlive the capability, and
+/// [`seal`](Self::seal) consumes it, so the borrow checker ensures none are used after the
+/// phase ends. Like [`InitToken`], it's not `Send`.
+#[derive(Debug)]
+pub struct PreKernel {
+    _not_send: PhantomData<*mut ()>,
+}
+
+/// Claim the [`PreKernel`] capability for the current, interrupts-disabled program phase.
+///
+/// In debug builds, this panics if called more than once, and in particular after
+/// [`PreKernel::seal`].
+///
+/// # Safety
+///
+/// For as long as the capability exists, interrupts must stay disabled, and no other thread or
+/// core may access data protected by the critical section. End the phase with
+/// [`PreKernel::seal`] before enabling interrupts or starting other cores.
+#[inline]
+#[track_caller]
+pub unsafe fn assume_interrupts_disabled() -> PreKernel {
+    #[cfg(debug_assertions)]
+    match PHASE.load(Ordering::Relaxed) {
+        PHASE_NONE => PHASE.store(PHASE_PRE_KERNEL, Ordering::Relaxed),
+        PHASE_PRE_KERNEL => {
+            panic!("critical_section::assume_interrupts_disabled called more than once")
+        }
+        _ => panic!("critical_section::assume_interrupts_disabled called after PreKernel::seal"),
+    }
+    PreKernel {
+        _not_send: PhantomData,
+    }
+}
+
+impl PreKernel {
+    /// Get a critical section token, valid for as long as the capability is borrowed.
+    ///
+    /// This doesn't acquire the critical section, and isn't counted in the nesting depth.
+    #[inline(always)]
+    pub fn token(&self) -> CriticalSection<'_> {
+        unsafe { CriticalSection::new_unchecked() }
+    }
+
+    /// End the interrupts-disabled phase, permanently.
+    ///
+    /// After this, data protected by the critical section has to be accessed through
+    /// [`with`] like anywhere else. Dropping the capability also ends the phase: either way,
+    /// [`assume_interrupts_disabled`] panics in debug builds if called again, sealing only
+    /// makes the panic message say why.
+    #[inline]
+    pub fn seal(self) {
+        #[cfg(debug_assertions)]
+        PHASE.store(PHASE_SEALED, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,83 @@
+//! Hooks called when entering and exiting the outermost critical section.
+//!
+//! This is useful for measuring how long interrupts are masked, for example by reading a
+//! cycle counter in both hooks:
+//!
+//! ```no_run
+//! # fn cycle_counter() -> u32 { 0 }
+//! use core::cell::Cell;
+//! use critical_section::Mutex;
+//!
+//! static START: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+//! static WORST: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+//!
+//! fn on_enter() {
+//!     critical_section::with(|cs| START.borrow(cs).set(cycle_counter()));
+//! }
+//!
+//! fn on_exit() {
+//!     critical_section::with(|cs| {
+//!         let elapsed = cycle_counter().wrapping_sub(START.borrow(cs).get());
+//!         let worst = WORST.borrow(cs);
+//!         worst.set(worst.get().max(elapsed));
+//!     });
+//! }
+//!
+//! critical_section::instrument::set_hooks(on_enter, on_exit);
+//! ```
+//!
+//! Nested critical sections don't call the hooks, only the outermost one does. The hooks
+//! run inside the critical section, so they may use [`with`](crate::with) themselves.
+
+use core::cell::Cell;
+
+use super::{CriticalSection, Mutex};
+
+#[derive(Clone, Copy)]
+struct Hooks {
+    on_enter: fn(),
+    on_exit: fn(),
+}
+
+static HOOKS: Mutex<Cell<Option<Hooks>>> = Mutex::new(Cell::new(None));
+
+/// Hooks whose `on_enter` ran for the current outermost critical section, so the matching
+/// `on_exit` runs even if the hooks are changed in between.
+static ACTIVE: Mutex<Cell<Option<Hooks>>> = Mutex::new(Cell::new(None));
+
+/// Set the hooks called when entering and exiting the outermost critical section.
+///
+/// `on_enter` runs right after the critical section is acquired, `on_exit` right before it
+/// is released. This replaces any previously set hooks, starting with the next outermost
+/// critical section.
+pub fn set_hooks(on_enter: fn(), on_exit: fn()) {
+    crate::with(|cs| HOOKS.borrow(cs).set(Some(Hooks { on_enter, on_exit })));
+}
+
+/// Remove the hooks set with [`set_hooks`].
+pub fn clear_hooks() {
+    crate::with(|cs| HOOKS.borrow(cs).set(None));
+}
+
+/// # Safety
+///
+/// Must be called while the critical section is held.
+#[inline]
+pub(crate) unsafe fn on_enter() {
+    let cs = CriticalSection::new_unchecked();
+    let hooks = HOOKS.borrow(cs).get();
+    ACTIVE.borrow(cs).set(hooks);
+    if let Some(hooks) = hooks {
+        (hooks.on_enter)();
+    }
+}
+
+/// # Safety
+///
+/// Must be called while the critical section is held.
+#[inline]
+pub(crate) unsafe fn on_exit() {
+    if let Some(hooks) = ACTIVE.borrow(CriticalSection::new_unchecked()).take() {
+        (hooks.on_exit)();
+    }
+}
@@ -0,0 +1,128 @@
+use core::cell::Cell;
+
+use super::{with, CriticalSection, Mutex};
+
+type CycleCounter = fn() -> u32;
+type LatencyHook = fn(LatencyExceeded);
+
+struct Latency {
+    counter: Cell<Option<CycleCounter>>,
+    budget: Cell<u32>,
+    hook: Cell<Option<LatencyHook>>,
+    /// Cycle count when the outermost critical section was entered.
+    start: Cell<u32>,
+}
+
+static LATENCY: Mutex<Latency> = Mutex::new(Latency {
+    counter: Cell::new(None),
+    budget: Cell::new(0),
+    hook: Cell::new(None),
+    start: Cell::new(0),
+});
+
+/// Details about an outermost critical section that exceeded the latency budget, passed to
+/// the hook set with [`set_latency_hook`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[non_exhaustive]
+pub struct LatencyExceeded {
+    /// The budget set with [`configure_latency_budget`], in cycles.
+    pub budget: u32,
+    /// The cycles the critical section was held for.
+    pub elapsed: u32,
+}
+
+impl core::fmt::Display for LatencyExceeded {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "critical section held for {} cycles, exceeding the latency budget of {} cycles",
+            self.elapsed, self.budget
+        )
+    }
+}
+
+/// Check that no outermost critical section is held for more than `budget` cycles.
+///
+/// `counter` should return a free-running cycle counter, which may wrap around, such as the
+/// DWT cycle counter on Cortex-M. It's called inside the critical section.
+///
+/// Every outermost critical section entered through this crate is checked when it's
+/// released. If it exceeded the budget, the hook set with [`set_latency_hook`] is called. If
+/// there is no hook, debug builds panic, and release builds ignore it. This is meant to catch
+/// long critical sections early during development, unlike the `metrics` feature which
+/// measures them. With the `debug` feature enabled, the hook can find the offending critical
+/// section with `last_entry_location`.
+///
+/// The check runs after the critical section is released. If the critical section is released
+/// because of a panic, panicking again aborts, so set a hook if that matters.
+///
+/// ```no_run
+/// fn read_cycle_counter() -> u32 {
+///     // Read a free-running counter...
+///     0
+/// }
+///
+/// // Interrupts must not be masked for more than 10µs at 64 MHz.
+/// critical_section::configure_latency_budget(read_cycle_counter, 640);
+/// ```
+pub fn configure_latency_budget(counter: fn() -> u32, budget: u32) {
+    with(|cs| {
+        let latency = LATENCY.borrow(cs);
+        latency.counter.set(Some(counter));
+        latency.budget.set(budget);
+        // This critical section is already being measured.
+        latency.start.set(counter());
+    });
+}
+
+/// Set the hook called when an outermost critical section exceeds the latency budget set with
+/// [`configure_latency_budget`], instead of panicking.
+///
+/// The hook runs right after the critical section is released.
+pub fn set_latency_hook(hook: fn(LatencyExceeded)) {
+    with(|cs| LATENCY.borrow(cs).hook.set(Some(hook)));
+}
+
+/// Start measuring the outermost critical section.
+///
+/// # Safety
+///
+/// Must be called while the critical section is held.
+#[inline]
+pub(crate) unsafe fn start() {
+    let latency = LATENCY.borrow(CriticalSection::new_unchecked());
+    if let Some(counter) = latency.counter.get() {
+        latency.start.set(counter());
+    }
+}
+
+/// Stop measuring the outermost critical section, returning whether it exceeded the budget.
+///
+/// # Safety
+///
+/// Must be called while the critical section is held, right before releasing it.
+#[inline]
+pub(crate) unsafe fn stop() -> Option<LatencyExceeded> {
+    let latency = LATENCY.borrow(CriticalSection::new_unchecked());
+    let counter = latency.counter.get()?;
+    let elapsed = counter().wrapping_sub(latency.start.get());
+    let budget = latency.budget.get();
+    (elapsed > budget).then_some(LatencyExceeded { budget, elapsed })
+}
+
+/// Report a critical section that exceeded the latency budget, after releasing it.
+#[cold]
+#[inline(never)]
+pub(crate) fn report(exceeded: LatencyExceeded) {
+    let hook = unsafe {
+        let restore_state = crate::acquire_raw();
+        let hook = LATENCY.borrow(CriticalSection::new_unchecked()).hook.get();
+        crate::release_raw(restore_state);
+        hook
+    };
+    match hook {
+        Some(hook) => hook(exceeded),
+        None => debug_assert!(false, "{}", exceeded),
+    }
+}
@@ -0,0 +1,184 @@
+//! Support for critical section implementations written against critical-section 0.2.
+//!
+//! In 0.2, the restore state was always a `u8`. Implementations of [`legacy::Impl`](Impl)
+//! registered with [`custom_impl!`](crate::custom_impl) have their `u8` widened to the
+//! [`RawRestoreState`](crate::RawRestoreState) selected through Cargo features, so the whole dependency tree can share
+//! one implementation. This requires one of the `restore-state-u8`, `restore-state-u16`,
+//! `restore-state-u32`, `restore-state-u64` or `restore-state-usize` features.
+//!
+//! # Bridging critical-section 0.2.7 and earlier
+//!
+//! critical-section 0.2.8 is itself a wrapper around 1.x, but up to 0.2.7 it had its own
+//! built-in implementations, or, with its `custom-impl` feature, called an implementation
+//! through the `_critical_section_acquire` and `_critical_section_release` symbols. A
+//! dependency tree still containing a HAL built on 0.2.7 then ends up with two independent
+//! critical sections, which don't exclude each other.
+//!
+//! With the `bridge-0-2-7` Cargo feature enabled, this crate defines those symbols on top of
+//! the 1.x implementation, so both share it. The 0.2.7 crate has to be switched to them by
+//! enabling its `custom-impl` feature in the application. Versions before 0.2.8 are yanked,
+//! so this only resolves with an existing lock file:
+//!
+//! ```toml
+//! [dependencies]
+//! critical-section = { version = "1.0", features = ["bridge-0-2-7"] }
+//! critical-section-0-2 = { package = "critical-section", version = "=0.2.7", features = ["custom-impl"] }
+//! ```
+//!
+//! Only the outermost critical section is acquired through the 1.x implementation, whose
+//! restore state is kept by this crate, so the `u8` token of 0.2.7 doesn't have to be able to
+//! hold it. Other crates calling 0.2.7's `custom_impl!` define the same symbols, and fail to
+//! link with this feature enabled.
+
+/// Methods required for a critical section implementation, as defined by critical-section 0.2.
+///
+/// New implementations should implement [`crate::Impl`] instead.
+///
+/// # Safety
+///
+/// Implementations must uphold the contract specified in [`crate::acquire`] and [`crate::release`].
+pub unsafe trait Impl {
+    /// Acquire the critical section.
+    ///
+    /// # Safety
+    ///
+    /// Callers must uphold the contract specified in [`crate::acquire`].
+    unsafe fn acquire() -> u8;
+    /// Release the critical section.
+    ///
+    /// # Safety
+    ///
+    /// Callers must uphold the contract specified in [`crate::release`].
+    unsafe fn release(token: u8);
+}
+
+#[doc(hidden)]
+#[cfg(any(
+    feature = "restore-state-u8",
+    feature = "restore-state-u16",
+    feature = "restore-state-u32",
+    feature = "restore-state-u64",
+    feature = "restore-state-usize"
+))]
+#[inline(always)]
+#[allow(clippy::useless_conversion)]
+pub fn widen(token: u8) -> crate::RawRestoreState {
+    token.into()
+}
+
+#[doc(hidden)]
+#[cfg(any(
+    feature = "restore-state-u8",
+    feature = "restore-state-u16",
+    feature = "restore-state-u32",
+    feature = "restore-state-u64",
+    feature = "restore-state-usize"
+))]
+#[inline(always)]
+#[allow(clippy::unnecessary_cast)]
+pub fn narrow(restore_state: crate::RawRestoreState) -> u8 {
+    // The value always comes from `widen`, so this is lossless.
+    restore_state as u8
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(any(
+    feature = "restore-state-u8",
+    feature = "restore-state-u16",
+    feature = "restore-state-u32",
+    feature = "restore-state-u64",
+    feature = "restore-state-usize"
+))]
+macro_rules! __legacy_check_restore_state {
+    () => {};
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(not(any(
+    feature = "restore-state-u8",
+    feature = "restore-state-u16",
+    feature = "restore-state-u32",
+    feature = "restore-state-u64",
+    feature = "restore-state-usize"
+)))]
+macro_rules! __legacy_check_restore_state {
+    () => {
+        compile_error!("custom_impl! needs a RestoreState of at least 8 bits, enable one of these Cargo features of critical-section: restore-state-u8, restore-state-u16, restore-state-u32, restore-state-u64, restore-state-usize");
+    };
+}
+
+/// Set the critical section implementation from a critical-section 0.2 style implementation.
+///
+/// New code should implement [`Impl`](crate::Impl) and use [`set_impl!`](crate::set_impl) instead.
+/// See the [`legacy`](crate::legacy) module for details.
+///
+/// # Example
+///
+/// ```ignore
+/// struct MyCriticalSection;
+/// critical_section::custom_impl!(MyCriticalSection);
+///
+/// unsafe impl critical_section::legacy::Impl for MyCriticalSection {
+///     unsafe fn acquire() -> u8 {
+///         // ...
+///     }
+///
+///     unsafe fn release(token: u8) {
+///         // ...
+///     }
+/// }
+/// ```
+#[deprecated(note = "implement `critical_section::Impl` and use `set_impl!` instead")]
+#[macro_export]
+macro_rules! custom_impl {
+    ($t: ty) => {
+        $crate::__legacy_check_restore_state!();
+
+        const _: () = {
+            struct LegacyImpl;
+            $crate::set_impl!(LegacyImpl);
+
+            // Forwarded with `inline(always)`, so the exported symbols call the 0.2 style
+            // implementation directly, without another level of indirection.
+            unsafe impl $crate::Impl for LegacyImpl {
+                #[inline(always)]
+                unsafe fn acquire() -> $crate::RawRestoreState {
+                    $crate::legacy::widen(<$t as $crate::legacy::Impl>::acquire())
+                }
+
+                #[inline(always)]
+                unsafe fn release(restore_state: $crate::RawRestoreState) {
+                    <$t as $crate::legacy::Impl>::release($crate::legacy::narrow(restore_state))
+                }
+            }
+        };
+    };
+}
+
+/// Token of the 0.2.7 bridge for the outermost critical section. Nested ones get `0`.
+#[cfg(feature = "bridge-0-2-7")]
+const OUTERMOST: u8 = 1;
+
+#[cfg(feature = "bridge-0-2-7")]
+#[no_mangle]
+unsafe fn _critical_section_acquire() -> u8 {
+    let restore_state = crate::acquire();
+    if crate::state::depth() == 1 {
+        return OUTERMOST;
+    }
+    // Already held, so this pair is a no-op and doesn't have to wait for the 0.2.7 release,
+    // whose token can't hold the restore state.
+    crate::release(restore_state);
+    0
+}
+
+#[cfg(feature = "bridge-0-2-7")]
+#[no_mangle]
+unsafe fn _critical_section_release(token: u8) {
+    if token == OUTERMOST {
+        // `release` uses the restore state of the outermost critical section anyway.
+        crate::release(crate::state::outermost());
+    }
+}
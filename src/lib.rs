@@ -1,19 +1,189 @@
 #![no_std]
+#![cfg_attr(
+    any(
+        all(feature = "avr", target_arch = "avr"),
+        all(feature = "msp430", target_arch = "msp430"),
+        all(feature = "xtensa", target_arch = "xtensa")
+    ),
+    feature(asm_experimental_arch)
+)]
 #![doc = include_str!("../README.md")]
+// The README wraps list items without indenting them.
+#![allow(clippy::doc_lazy_continuation)]
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(feature = "async")]
+pub mod asynch;
+#[cfg(feature = "atomic")]
+pub mod atomic;
+#[cfg(all(feature = "avr", target_arch = "avr"))]
+mod avr;
+#[cfg(feature = "bench")]
+#[doc(hidden)]
+pub mod bench;
+#[cfg(feature = "bh")]
+pub mod bh;
+#[cfg(feature = "budget")]
+mod budget;
+pub mod cell;
+#[cfg(feature = "channel")]
+pub mod channel;
+#[cfg(feature = "chaos")]
+pub mod chaos;
+#[cfg(all(feature = "cortex-m", cortex_m))]
+mod cortex_m;
+#[cfg(feature = "debug")]
+mod debug;
+#[cfg(feature = "defer")]
+mod defer;
+#[cfg(feature = "domains")]
+pub mod domain;
+mod dual;
+#[cfg(feature = "dynamic-impl")]
+pub mod dynamic;
+#[cfg(feature = "embassy-sync")]
+pub mod embassy;
+#[cfg(feature = "emergency")]
+mod emergency;
+#[cfg(feature = "extern-fns")]
+pub mod extern_fns;
+#[cfg(feature = "fallback-panic")]
+mod fallback_panic;
+mod fault;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "freertos")]
+pub mod freertos;
+mod guard;
+mod init;
+#[cfg(feature = "instrument")]
+pub mod instrument;
+#[cfg(feature = "max-latency-assert")]
+mod latency;
+pub mod legacy;
+#[cfg(feature = "lock-order")]
+pub mod lock_order;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+#[cfg(all(feature = "miri", miri))]
+mod miri;
+#[cfg(all(feature = "msp430", target_arch = "msp430"))]
+mod msp430;
+#[cfg(feature = "multicore-spinlock")]
+pub mod multicore;
 mod mutex;
+mod once;
+#[cfg(feature = "panic-release")]
+mod panic_release;
+#[cfg(feature = "percore")]
+pub mod percore;
+#[cfg(feature = "poison")]
+mod poison;
+#[cfg(feature = "pool")]
+pub mod pool;
+mod priority;
+#[cfg(feature = "debug-registry")]
+pub mod registry;
+#[cfg(all(
+    feature = "riscv-single-hart",
+    any(target_arch = "riscv32", target_arch = "riscv64")
+))]
+mod riscv;
+mod rwlock;
+#[cfg(feature = "self-test")]
+mod self_test;
+mod signal;
+#[cfg(all(feature = "sim", not(loom)))]
+pub mod sim;
+#[cfg(feature = "single-threaded")]
+mod single_threaded;
+mod state;
+#[cfg(all(feature = "std", not(all(feature = "miri", miri))))]
+mod std;
+#[cfg(feature = "sync")]
+pub mod sync;
+#[cfg(all(feature = "test-harness", not(loom)))]
+pub mod test_harness;
+mod token;
+#[cfg(any(feature = "defmt-trace", feature = "log-trace"))]
+mod trace;
+#[cfg(feature = "trusted-single-context")]
+mod trusted_single_context;
+#[cfg(feature = "watchdog")]
+mod watchdog;
+#[cfg(all(feature = "xtensa", target_arch = "xtensa"))]
+mod xtensa;
+#[cfg(feature = "zephyr")]
+pub mod zephyr;
 
 use core::marker::PhantomData;
+use core::ops::ControlFlow;
+use core::time::Duration;
 
-pub use self::mutex::Mutex;
+#[cfg(feature = "budget")]
+pub use self::budget::{set_cycle_counter, set_overrun_handler, with_budget, Overrun};
+#[cfg(feature = "debug")]
+pub use self::debug::last_entry_location;
+#[cfg(all(feature = "defer", feature = "alloc"))]
+pub use self::defer::defer_boxed;
+#[cfg(feature = "defer")]
+pub use self::defer::{defer, DeferQueueFull};
+pub use self::dual::{Dual, InterruptContext};
+#[cfg(feature = "emergency")]
+pub use self::emergency::{enter_emergency_mode, in_emergency_mode};
+pub use self::fault::{steal_for_fault_handler, was_stolen};
+pub use self::guard::{enter, Guard};
+pub use self::init::{assume_interrupts_disabled, init, InitToken, PreKernel};
+#[cfg(feature = "max-latency-assert")]
+pub use self::latency::{configure_latency_budget, set_latency_hook, LatencyExceeded};
+pub use self::mutex::{BlockingMutex, BorrowMany, CsRef, Mutex};
+pub use self::once::{LazyCell, OnceCell};
+#[cfg(feature = "panic-release")]
+pub use self::panic_release::force_release_on_panic;
+#[cfg(feature = "poison")]
+pub use self::poison::{clear_poison, is_poisoned, with_checked, Poisoned};
+pub use self::priority::{with_max_priority, PrioritySection};
+pub use self::rwlock::{ReadLocked, RwLock};
+#[cfg(feature = "self-test")]
+pub use self::self_test::{self_test, SelfTestReport};
+pub use self::signal::{Event, Signal};
+pub use self::state::{restore, save, FullState};
+pub use self::token::{CsRead, CsWrite};
+#[cfg(feature = "watchdog")]
+pub use self::watchdog::{configure_watchdog, watchdog_checkpoint};
+#[cfg(feature = "macros")]
+pub use critical_section_macros::{critical_section, protected};
 
 /// Critical section token.
 ///
 /// An instance of this type indicates that the current thread is executing code within a critical
 /// section.
+///
+/// The token is neither `Send` nor `Sync`, since the critical section is only held by the
+/// thread that acquired it. Other threads, such as scoped threads spawned inside the critical
+/// section, can't use it:
+///
+/// ```compile_fail
+/// # use critical_section::Mutex;
+/// # use std::cell::Cell;
+/// static COUNTER: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+///
+/// critical_section::with(|cs| {
+///     std::thread::scope(|s| {
+///         // Another thread isn't in the critical section.
+///         s.spawn(move || COUNTER.borrow(cs).set(1));
+///     });
+/// });
+/// ```
+///
+/// This also makes futures that hold a token across an `.await` not `Send`, see
+/// [`assert_not_across_await!`](crate::assert_not_across_await).
 #[derive(Clone, Copy, Debug)]
 pub struct CriticalSection<'cs> {
     _0: PhantomData<&'cs ()>,
+    _not_send: PhantomData<*mut ()>,
 }
 
 impl<'cs> CriticalSection<'cs> {
@@ -34,54 +204,117 @@ impl<'cs> CriticalSection<'cs> {
     /// Note that the lifetime `'cs` of the returned instance is unconstrained. User code must not
     /// be able to influence the lifetime picked for this type, since that might cause it to be
     /// inferred to `'static`.
+    ///
+    /// With the `debug-assert-token` Cargo feature enabled, debug builds check that the
+    /// current context is in a critical section acquired through this crate, and panic
+    /// otherwise. Like [`is_active`], the check briefly acquires the critical section itself,
+    /// so it reads the nesting depth of the context holding it, and waits if that's another
+    /// thread or core.
     #[inline(always)]
     pub unsafe fn new() -> Self {
-        CriticalSection { _0: PhantomData }
-    }
-}
-
-#[cfg(any(
-    all(feature = "restore-state-none", feature = "restore-state-bool"),
-    all(feature = "restore-state-none", feature = "restore-state-u8"),
-    all(feature = "restore-state-none", feature = "restore-state-u16"),
-    all(feature = "restore-state-none", feature = "restore-state-u32"),
-    all(feature = "restore-state-none", feature = "restore-state-u64"),
-    all(feature = "restore-state-bool", feature = "restore-state-u8"),
-    all(feature = "restore-state-bool", feature = "restore-state-u16"),
-    all(feature = "restore-state-bool", feature = "restore-state-u32"),
-    all(feature = "restore-state-bool", feature = "restore-state-u64"),
-    all(feature = "restore-state-u8", feature = "restore-state-u16"),
-    all(feature = "restore-state-u8", feature = "restore-state-u32"),
-    all(feature = "restore-state-u8", feature = "restore-state-u64"),
-    all(feature = "restore-state-u16", feature = "restore-state-u32"),
-    all(feature = "restore-state-u16", feature = "restore-state-u64"),
-    all(feature = "restore-state-u32", feature = "restore-state-u64"),
-))]
-compile_error!("You must set at most one of these Cargo features: restore-state-none, restore-state-bool, restore-state-u8, restore-state-u16, restore-state-u32, restore-state-u64");
+        #[cfg(feature = "debug-assert-token")]
+        debug_assert!(
+            is_active(),
+            "CriticalSection::new() called outside of a critical section"
+        );
+
+        Self::new_unchecked()
+    }
+
+    /// Creates a critical section token, without the `debug-assert-token` check.
+    #[inline(always)]
+    pub(crate) const unsafe fn new_unchecked() -> Self {
+        CriticalSection {
+            _0: PhantomData,
+            _not_send: PhantomData,
+        }
+    }
+}
+
+#[cfg(feature = "bare-metal")]
+impl<'cs> CriticalSection<'cs> {
+    /// Creates a critical section token from a [`bare_metal::CriticalSection`] token with the
+    /// same lifetime.
+    ///
+    /// # Safety
+    ///
+    /// A `bare_metal::CriticalSection` only guarantees that interrupts are disabled on the current
+    /// core. The caller must ensure that this is equivalent to holding this crate's critical
+    /// section, which is the case when the installed implementation does nothing more than
+    /// disabling interrupts on a single-core system.
+    ///
+    /// This can't be checked at runtime instead. Asking whether this crate's critical section
+    /// is held, like [`is_active`] does, only tells about the moment of the conversion: the
+    /// `bare_metal::CriticalSection` comes from elsewhere, such as `cortex_m::interrupt::free`,
+    /// and its lifetime isn't tied to this crate's critical section, which may be released
+    /// while the token is still in use. On a multi-core system, another core can then enter
+    /// the critical section and access the same data.
+    #[inline(always)]
+    pub unsafe fn upgrade(_cs: bare_metal::CriticalSection<'cs>) -> Self {
+        // Not tracked by the nesting depth, so skip the `debug-assert-token` check.
+        CriticalSection::new_unchecked()
+    }
+
+    /// Converts this token into a [`bare_metal::CriticalSection`] token with the same lifetime.
+    ///
+    /// This is the same as the `From` conversion, spelled out for readability.
+    #[inline(always)]
+    pub fn downgrade(self) -> bare_metal::CriticalSection<'cs> {
+        self.into()
+    }
+}
+
+/// Converts a critical section token into a [`bare_metal::CriticalSection`] token.
+///
+/// This allows using the token with [`bare_metal::Mutex`], which is what crates from the
+/// critical-section 0.2 era (such as `cortex-m` 0.7) use.
+///
+/// The opposite conversion is not provided: a `bare_metal::CriticalSection` only guarantees that
+/// interrupts are disabled on the current core, which is not enough to use a [`Mutex`] on
+/// multi-core systems. [`CriticalSection::upgrade`] and `Mutex::borrow_bare_metal` do it
+/// unsafely instead.
+#[cfg(feature = "bare-metal")]
+impl<'cs> From<CriticalSection<'cs>> for bare_metal::CriticalSection<'cs> {
+    #[inline(always)]
+    fn from(_cs: CriticalSection<'cs>) -> Self {
+        // Safety: holding this crate's critical section is at least as strong as
+        // having interrupts disabled on the current core.
+        unsafe { bare_metal::CriticalSection::new() }
+    }
+}
+
+// Conflicting Cargo features, such as two built-in implementations, are checked by the build
+// script, which can name the features involved. It also defines `RawRestoreStateInner` from the
+// selected `restore-state-*` feature, so a conflict doesn't cause more errors.
+include!(concat!(env!("OUT_DIR"), "/features.rs"));
 
-#[cfg(not(any(
-    feature = "restore-state-bool",
-    feature = "restore-state-u8",
-    feature = "restore-state-u16",
-    feature = "restore-state-u32",
-    feature = "restore-state-u64"
-)))]
-type RawRestoreStateInner = ();
+#[cfg(all(feature = "unix-signal-safe", not(unix)))]
+compile_error!("The `unix-signal-safe` Cargo feature requires a Unix target");
 
-#[cfg(feature = "restore-state-bool")]
-type RawRestoreStateInner = bool;
+#[cfg(all(feature = "cortex-m", not(cortex_m)))]
+compile_error!("The `cortex-m` Cargo feature requires a Cortex-M target (`thumbv*`)");
 
-#[cfg(feature = "restore-state-u8")]
-type RawRestoreStateInner = u8;
+#[cfg(all(
+    feature = "riscv-single-hart",
+    not(any(target_arch = "riscv32", target_arch = "riscv64"))
+))]
+compile_error!("The `riscv-single-hart` Cargo feature requires a RISC-V target");
 
-#[cfg(feature = "restore-state-u16")]
-type RawRestoreStateInner = u16;
+#[cfg(all(feature = "avr", not(target_arch = "avr")))]
+compile_error!("The `avr` Cargo feature requires an AVR target");
 
-#[cfg(feature = "restore-state-u32")]
-type RawRestoreStateInner = u32;
+#[cfg(all(feature = "msp430", not(target_arch = "msp430")))]
+compile_error!("The `msp430` Cargo feature requires an MSP430 target");
 
-#[cfg(feature = "restore-state-u64")]
-type RawRestoreStateInner = u64;
+#[cfg(all(feature = "xtensa", not(target_arch = "xtensa")))]
+compile_error!("The `xtensa` Cargo feature requires an Xtensa target");
+
+#[cfg(all(
+    feature = "single-threaded",
+    target_arch = "wasm32",
+    target_feature = "atomics"
+))]
+compile_error!("The `single-threaded` Cargo feature can't be used with WebAssembly threads");
 
 // We have RawRestoreStateInner and RawRestoreState so that we don't have to copypaste the docs 5 times.
 // In the docs this shows as `pub type RawRestoreState = u8` or whatever the selected type is, because
@@ -96,6 +329,8 @@ type RawRestoreStateInner = u64;
 /// - `restore-state-u16`
 /// - `restore-state-u32`
 /// - `restore-state-u64`
+/// - `restore-state-usize`
+/// - `restore-state-opaque`, makes the type be `OpaqueRestoreState`
 ///
 /// See [`RestoreState`].
 ///
@@ -103,6 +338,31 @@ type RawRestoreStateInner = u64;
 /// use [`RawRestoreState`] so that they can use the inner value.
 pub type RawRestoreState = RawRestoreStateInner;
 
+/// Raw restore state selected by the `restore-state-opaque` Cargo feature.
+///
+/// This is a `usize` that implementations convert from and to explicitly, instead of a plain
+/// integer that any code could do arithmetic on, or confuse with other integers. Multi-core
+/// implementations can pack the interrupt state and ownership of the lock into it.
+#[cfg(feature = "restore-state-opaque")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[repr(transparent)]
+pub struct OpaqueRestoreState(usize);
+
+#[cfg(feature = "restore-state-opaque")]
+impl OpaqueRestoreState {
+    /// Wraps the bits of a restore state.
+    #[inline(always)]
+    pub const fn new(bits: usize) -> Self {
+        OpaqueRestoreState(bits)
+    }
+
+    /// Returns the bits the restore state was created with.
+    #[inline(always)]
+    pub const fn bits(self) -> usize {
+        self.0
+    }
+}
+
 /// Opaque "restore state".
 ///
 /// Implementations use this to "carry over" information between acquiring and releasing
@@ -114,6 +374,10 @@ pub type RawRestoreState = RawRestoreStateInner;
 ///
 /// User code uses [`RestoreState`] opaquely, critical section implementations
 /// use [`RawRestoreState`] so that they can use the inner value.
+///
+/// A `RestoreState` returned by [`acquire`] is only valid for the matching [`release`].
+/// Releasing the outer one of two nested critical sections first, or releasing with a
+/// `RestoreState` from another thread, is undefined behavior.
 #[derive(Clone, Copy, Debug)]
 pub struct RestoreState(RawRestoreState);
 
@@ -131,7 +395,9 @@ impl RestoreState {
             feature = "restore-state-u8",
             feature = "restore-state-u16",
             feature = "restore-state-u32",
-            feature = "restore-state-u64"
+            feature = "restore-state-u64",
+            feature = "restore-state-usize",
+            feature = "restore-state-opaque"
         )))]
         return Self(());
 
@@ -149,6 +415,12 @@ impl RestoreState {
 
         #[cfg(feature = "restore-state-u64")]
         return Self(0);
+
+        #[cfg(feature = "restore-state-usize")]
+        return Self(0);
+
+        #[cfg(feature = "restore-state-opaque")]
+        return Self(OpaqueRestoreState(0));
     }
 }
 
@@ -166,13 +438,72 @@ impl RestoreState {
 /// - `acquire`/`release` pairs must be "properly nested", ie it's not OK to do `a=acquire(); b=acquire(); release(a); release(b);`.
 /// - It is UB to call `release` if the critical section is not acquired in the current thread.
 /// - It is UB to call `release` with a "restore state" that does not come from the corresponding `acquire` call.
-#[inline]
+///
+/// # Example
+///
+/// `acquire`/`release` map directly onto C-style lock/unlock pairs, where whatever the lock
+/// function returns is handed back to the unlock function, such as lwIP's
+/// `sys_arch_protect`/`sys_arch_unprotect`:
+///
+/// ```no_run
+/// use critical_section::RestoreState;
+///
+/// fn sys_arch_protect() -> RestoreState {
+///     unsafe { critical_section::acquire() }
+/// }
+///
+/// unsafe fn sys_arch_unprotect(restore_state: RestoreState) {
+///     critical_section::release(restore_state)
+/// }
+/// ```
+///
+/// The lock function may be called again before the unlock function, as long as the
+/// calls stay properly nested.
+#[inline(always)]
+#[must_use = "the restore state must be passed to `release`"]
+#[cfg_attr(
+    any(feature = "debug", feature = "defmt-trace", feature = "log-trace"),
+    track_caller
+)]
 pub unsafe fn acquire() -> RestoreState {
-    extern "Rust" {
-        fn _critical_section_1_0_acquire() -> RawRestoreState;
+    #[cfg(feature = "chaos")]
+    chaos::before_acquire();
+
+    let restore_state = acquire_raw();
+    if state::enter() == 0 {
+        state::set_outermost(restore_state);
+        #[cfg(feature = "max-latency-assert")]
+        latency::start();
+        #[cfg(feature = "watchdog")]
+        watchdog::start();
     }
+    restore_state
+}
 
-    RestoreState(_critical_section_1_0_acquire())
+/// Acquire a critical section in the current thread, unless another context holds it.
+///
+/// This is [`acquire`] for [`with_or_else`]: it returns `None` instead of waiting when the
+/// implementation supports it, see [`Impl::try_acquire`]. Nesting within the current thread
+/// always succeeds.
+///
+/// # Safety
+///
+/// When this returns `Some`, see [`acquire`] for the safety contract description.
+#[inline(always)]
+#[must_use = "the restore state must be passed to `release`"]
+pub unsafe fn try_acquire() -> Option<RestoreState> {
+    #[cfg(feature = "chaos")]
+    chaos::before_acquire();
+
+    let restore_state = try_acquire_raw()?;
+    if state::enter() == 0 {
+        state::set_outermost(restore_state);
+        #[cfg(feature = "max-latency-assert")]
+        latency::start();
+        #[cfg(feature = "watchdog")]
+        watchdog::start();
+    }
+    Some(restore_state)
 }
 
 /// Release the critical section.
@@ -182,25 +513,924 @@ pub unsafe fn acquire() -> RestoreState {
 /// # Safety
 ///
 /// See [`acquire`] for the safety contract description.
-#[inline]
+#[inline(always)]
 pub unsafe fn release(restore_state: RestoreState) {
+    let prev = state::exit();
+    // `without` may have re-acquired the outermost critical section, replacing its restore
+    // state.
+    let restore_state = if prev == 1 {
+        state::outermost()
+    } else {
+        restore_state
+    };
+    #[cfg(feature = "max-latency-assert")]
+    let exceeded = if prev == 1 { latency::stop() } else { None };
+    release_raw(restore_state);
+
+    #[cfg(feature = "max-latency-assert")]
+    if let Some(exceeded) = exceeded {
+        latency::report(exceeded);
+    }
+
+    #[cfg(feature = "chaos")]
+    if prev == 1 {
+        chaos::after_release();
+    }
+
+    #[cfg(feature = "defer")]
+    if prev == 1 {
+        defer::run_pending();
+    }
+
+    #[cfg(all(feature = "sim", not(loom)))]
+    if prev == 1 {
+        sim::run_pending();
+    }
+
+    #[cfg(not(any(feature = "defer", all(feature = "sim", not(loom)))))]
+    let _ = prev;
+}
+
+/// Acquire the critical section from the implementation, without the crate's bookkeeping.
+#[inline(always)]
+#[allow(clippy::unit_arg)] // `RawRestoreState` is `()` by default.
+unsafe fn acquire_raw() -> RestoreState {
+    extern "Rust" {
+        fn _critical_section_1_0_acquire() -> RawRestoreState;
+    }
+
+    #[cfg(feature = "emergency")]
+    if in_emergency_mode() {
+        return RestoreState::invalid();
+    }
+
+    #[cfg(feature = "trusted-single-context")]
+    let restore_state = RestoreState::invalid();
+
+    #[cfg(not(feature = "trusted-single-context"))]
+    let restore_state = RestoreState(_critical_section_1_0_acquire());
+
+    barrier();
+    restore_state
+}
+
+/// [`acquire_raw`], without waiting for other contexts if the implementation supports it.
+#[inline(always)]
+#[allow(clippy::unit_arg)] // `RawRestoreState` is `()` by default.
+unsafe fn try_acquire_raw() -> Option<RestoreState> {
+    extern "Rust" {
+        fn _critical_section_1_0_try_acquire() -> Option<RawRestoreState>;
+    }
+
+    #[cfg(feature = "emergency")]
+    if in_emergency_mode() {
+        return Some(RestoreState::invalid());
+    }
+
+    #[cfg(feature = "trusted-single-context")]
+    let restore_state = RestoreState::invalid();
+
+    #[cfg(not(feature = "trusted-single-context"))]
+    let restore_state = RestoreState(_critical_section_1_0_try_acquire()?);
+
+    barrier();
+    Some(restore_state)
+}
+
+/// Release the critical section to the implementation, without the crate's bookkeeping.
+#[inline(always)]
+#[allow(clippy::unit_arg)] // `RawRestoreState` is `()` by default.
+unsafe fn release_raw(restore_state: RestoreState) {
     extern "Rust" {
         fn _critical_section_1_0_release(restore_state: RawRestoreState);
     }
+
+    barrier();
+
+    #[cfg(feature = "emergency")]
+    if in_emergency_mode() {
+        return;
+    }
+
+    #[cfg(feature = "trusted-single-context")]
+    let _ = restore_state;
+
+    #[cfg(not(feature = "trusted-single-context"))]
     _critical_section_1_0_release(restore_state.0)
 }
 
+/// The barrier selected with the `barriers-*` Cargo features, emitted at the critical section
+/// boundaries.
+#[inline(always)]
+pub(crate) fn barrier() {
+    #[cfg(feature = "barriers-compiler")]
+    core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
+    #[cfg(feature = "barriers-full")]
+    core::sync::atomic::fence(core::sync::atomic::Ordering::SeqCst);
+}
+
+/// Execute closure `f` with the critical section temporarily released.
+///
+/// All critical sections the current thread is nested in are released, so interrupts can run
+/// while `f` executes. Afterwards, the critical section is re-acquired, and the nesting depth
+/// is restored. This opens an interrupt window during long operations inside a critical
+/// section, such as waiting for a flash controller.
+///
+/// If the current thread is not in a critical section, this just runs `f`.
+///
+/// Hooks and tracing don't see the window: it's counted as part of the critical section. The
+/// latency budget of the `max-latency-assert` feature is checked separately before and after
+/// the window, though.
+///
+/// # Safety
+///
+/// Data protected by the critical section may change while `f` runs, and must not be
+/// accessed through references obtained before calling this. This includes:
+///
+/// - `f` must not use any [`CriticalSection`] token from before calling this.
+/// - References returned by [`Mutex::borrow`] and similar methods must not be used while `f`
+///   runs, or after it returns if the data might have changed.
+///
+/// The critical section must only have been entered through this crate, so that this crate
+/// knows the restore state of the outermost critical section. Each nesting level is released
+/// and re-acquired from the implementation, see [`Impl`].
+///
+/// ```no_run
+/// # fn flash_busy() -> bool { false }
+/// critical_section::with(|cs| {
+///     // Start a flash erase...
+///     while flash_busy() {
+///         // Let interrupts run while waiting.
+///         unsafe { critical_section::without(|| {}) };
+///     }
+/// #   let _ = cs;
+/// });
+/// ```
+#[inline]
+pub unsafe fn without<R>(f: impl FnOnce() -> R) -> R {
+    struct Reacquire(usize);
+
+    impl Drop for Reacquire {
+        #[inline]
+        fn drop(&mut self) {
+            unsafe {
+                let restore_state = acquire_raw();
+                // The nested restore states are interchangeable, so the ones held by the
+                // callers are still valid.
+                for _ in 1..self.0 {
+                    let _ = acquire_raw();
+                }
+                state::set_outermost(restore_state);
+                state::resume(self.0);
+                #[cfg(feature = "max-latency-assert")]
+                latency::start();
+                #[cfg(feature = "watchdog")]
+                watchdog::start();
+            }
+        }
+    }
+
+    let nested = acquire_raw();
+    let depth = state::depth();
+    if depth == 0 {
+        release_raw(nested);
+        return f();
+    }
+
+    let outermost = state::outermost();
+    #[cfg(feature = "max-latency-assert")]
+    let exceeded = latency::stop();
+    let _reacquire = Reacquire(state::suspend());
+    // Implementations counting the nesting, such as FreeRTOS, only release the critical
+    // section when every level is released: the nested ones, and the one just acquired, with
+    // a nested restore state, then the outermost one.
+    for _ in 0..depth {
+        release_raw(nested);
+    }
+    release_raw(outermost);
+    // Interrupts pending during the critical section run in the window.
+    #[cfg(all(feature = "sim", not(loom)))]
+    sim::run_pending();
+    #[cfg(feature = "max-latency-assert")]
+    if let Some(exceeded) = exceeded {
+        latency::report(exceeded);
+    }
+    f()
+}
+
+/// Execute closure `f` without being preempted by other tasks.
+///
+/// With an RTOS, this only stops the scheduler from switching tasks, leaving interrupts
+/// enabled, which is often all that's needed to keep a longer operation consistent with other
+/// tasks. Data shared with interrupt handlers still needs a critical section.
+///
+/// Stopping the scheduler is an optional capability of the implementation, see
+/// [`Impl::suspend_scheduler`]. Implementations without it, including the ones for bare-metal
+/// targets, acquire the full critical section instead. On a single core, `f` can rely on not
+/// being preempted by other tasks either way. On SMP systems, RTOSes such as FreeRTOS and
+/// Zephyr only stop the scheduler of the current CPU, so tasks on the other CPUs keep running,
+/// and data shared with them still needs a critical section.
+///
+/// ```no_run
+/// # fn write_config_to_flash() {}
+/// critical_section::suspend(|| {
+///     // Other tasks don't run until this returns, but interrupts are still handled.
+///     write_config_to_flash();
+/// });
+/// ```
+///
+/// Nesting is allowed. `f` must not block waiting for another task, since no other task can
+/// run.
+#[inline]
+pub fn suspend<R>(f: impl FnOnce() -> R) -> R {
+    extern "Rust" {
+        fn _critical_section_1_0_suspend_scheduler() -> bool;
+        fn _critical_section_1_0_resume_scheduler();
+    }
+
+    struct Resume;
+
+    impl Drop for Resume {
+        #[inline]
+        fn drop(&mut self) {
+            unsafe { _critical_section_1_0_resume_scheduler() }
+        }
+    }
+
+    #[cfg(feature = "emergency")]
+    if in_emergency_mode() {
+        return f();
+    }
+
+    if unsafe { _critical_section_1_0_suspend_scheduler() } {
+        let _resume = Resume;
+        f()
+    } else {
+        with(|_| f())
+    }
+}
+
+/// Returns whether the current thread is in a critical section.
+///
+/// This is useful to check preconditions, such as a function that must not be called with
+/// interrupts disabled:
+///
+/// ```no_run
+/// fn flush_uart() {
+///     debug_assert!(
+///         !critical_section::is_active(),
+///         "flush_uart() blocks, don't call it in a critical section",
+///     );
+///     // ...
+/// }
+/// ```
+///
+/// This is answered from the nesting depth tracked by this crate, so it works with every
+/// implementation. Critical sections entered by calling the implementation directly, without
+/// going through this crate, are not seen.
+///
+/// To read the nesting depth consistently, this briefly acquires the critical section itself,
+/// without calling hooks or tracing. If another thread holds the critical section, this waits
+/// for it to be released.
+#[inline]
+pub fn is_active() -> bool {
+    unsafe {
+        let restore_state = acquire_raw();
+        let depth = state::depth();
+        release_raw(restore_state);
+        depth > 0
+    }
+}
+
+/// Returns how many critical sections the current thread is nested in.
+///
+/// This is `0` outside of a critical section, `1` directly inside [`with`], and so on. Like
+/// [`is_active`], this briefly acquires the critical section itself.
+#[inline]
+pub fn nesting_depth() -> usize {
+    unsafe {
+        let restore_state = acquire_raw();
+        let depth = state::depth();
+        release_raw(restore_state);
+        depth
+    }
+}
+
+/// Returns the deepest nesting of critical sections reached since startup, or since the last
+/// call to [`reset_max_nesting_depth`].
+///
+/// This is meant for integration testing, to check that the locking discipline stays shallow
+/// and to catch unexpected recursion into [`with`]. The watermark is shared by all threads.
+#[inline]
+pub fn max_nesting_depth() -> usize {
+    unsafe {
+        let restore_state = acquire_raw();
+        let max_depth = state::max_depth();
+        release_raw(restore_state);
+        max_depth
+    }
+}
+
+/// Resets the watermark returned by [`max_nesting_depth`].
+///
+/// If the current thread is in a critical section, the watermark is reset to `0` anyway, and
+/// only raised again by the next nested critical section.
+#[inline]
+pub fn reset_max_nesting_depth() {
+    unsafe {
+        let restore_state = acquire_raw();
+        state::reset_max_depth();
+        release_raw(restore_state);
+    }
+}
+
 /// Execute closure `f` in a critical section.
 ///
 /// Nesting critical sections is allowed. The inner critical sections
 /// are mostly no-ops since they're already protected by the outer one.
-#[inline]
+///
+/// If `f` panics and the panic unwinds, the critical section is released.
+///
+/// With the `poison` Cargo feature enabled, such a panic also poisons the critical section,
+/// and subsequent calls to `with` panic until `clear_poison` is called. Use `with_checked`
+/// to get an error instead.
+///
+/// With the `small-code` Cargo feature enabled, this is [`with_outlined`]: acquiring and
+/// releasing the critical section is compiled once, instead of inlined into every caller, at
+/// the cost of an indirect call.
+#[inline(always)]
+#[cfg_attr(
+    any(feature = "debug", feature = "defmt-trace", feature = "log-trace"),
+    track_caller
+)]
 pub fn with<R>(f: impl FnOnce(CriticalSection) -> R) -> R {
-    unsafe {
-        let restore_state = acquire();
-        let r = f(CriticalSection::new());
-        release(restore_state);
-        r
+    #[cfg(feature = "small-code")]
+    return with_outlined(f);
+
+    #[cfg(not(feature = "small-code"))]
+    with_inlined(f)
+}
+
+/// [`with`], always inlined.
+#[inline(always)]
+#[cfg_attr(
+    any(feature = "debug", feature = "defmt-trace", feature = "log-trace"),
+    track_caller
+)]
+fn with_inlined<R>(f: impl FnOnce(CriticalSection) -> R) -> R {
+    let guard = enter();
+
+    #[cfg(feature = "poison")]
+    {
+        poison::check(guard.token());
+        poison::run(guard.token(), f)
+    }
+
+    #[cfg(not(feature = "poison"))]
+    f(guard.token())
+}
+
+/// Execute closure `f` in a critical section, or `fallback` if another context holds it.
+///
+/// With implementations that can tell, such as `multicore::Spinlock` with
+/// `OnContention::TryFail`, this doesn't wait for another
+/// core to release the critical section: `fallback` runs instead, outside of any critical
+/// section. Other implementations always wait and run `f`, like [`with`]. Nesting within the
+/// current thread always runs `f`.
+///
+/// ```no_run
+/// use critical_section::Mutex;
+/// use std::cell::Cell;
+///
+/// static SAMPLES: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+/// static DROPPED: core::sync::atomic::AtomicU32 = core::sync::atomic::AtomicU32::new(0);
+///
+/// // In a timer interrupt that must never miss its deadline:
+/// critical_section::with_or_else(
+///     |cs| SAMPLES.borrow(cs).set(SAMPLES.borrow(cs).get() + 1),
+///     || {
+///         DROPPED.fetch_add(1, core::sync::atomic::Ordering::Relaxed);
+///     },
+/// );
+/// ```
+#[inline]
+#[cfg_attr(
+    any(feature = "debug", feature = "defmt-trace", feature = "log-trace"),
+    track_caller
+)]
+pub fn with_or_else<R>(f: impl FnOnce(CriticalSection) -> R, fallback: impl FnOnce() -> R) -> R {
+    let guard = match guard::try_enter() {
+        Some(guard) => guard,
+        None => return fallback(),
+    };
+
+    #[cfg(feature = "poison")]
+    {
+        poison::check(guard.token());
+        poison::run(guard.token(), f)
+    }
+
+    #[cfg(not(feature = "poison"))]
+    f(guard.token())
+}
+
+/// Execute function `f` in a critical section.
+///
+/// This works like [`with`], but takes a plain function pointer instead of a closure. No
+/// closure environment is created, and a single copy of the critical section code is shared
+/// by all callers with the same return type, instead of one per closure. On targets with
+/// little RAM, such as AVR, this can keep the stack usage of code with many critical sections
+/// down, at the cost of an indirect call.
+///
+/// ```no_run
+/// # use critical_section::{CriticalSection, Mutex};
+/// # use std::cell::Cell;
+/// static TICKS: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+///
+/// fn tick(cs: CriticalSection) -> u32 {
+///     let ticks = TICKS.borrow(cs);
+///     ticks.set(ticks.get() + 1);
+///     ticks.get()
+/// }
+///
+/// let ticks = critical_section::with_fn(tick);
+/// # let _ = ticks;
+/// ```
+///
+/// # Code generation
+///
+/// A call site only loads the function pointer and calls the instance of `with_fn` for its
+/// return type. That instance acquires the critical section, calls `f` indirectly, and
+/// releases it. If a single function is ever passed for a return type, the compiler may
+/// specialize the instance for it and inline it, which removes the indirect call.
+///
+/// The instructions of the call sites and of the instances on Cortex-M are checked into the
+/// repository, in `ci/asm`. The overhead is measured by `cargo bench --features std,bench`, and on hardware by
+/// the `bench-embedded` example.
+#[inline(never)]
+#[cfg_attr(
+    any(feature = "debug", feature = "defmt-trace", feature = "log-trace"),
+    track_caller
+)]
+pub fn with_fn<R>(f: fn(CriticalSection) -> R) -> R {
+    with(f)
+}
+
+/// Execute function `f` with `arg` in a critical section.
+///
+/// This is [`with_fn`] for functions that need an argument, which is passed by value instead
+/// of being captured by a closure. The critical section code is shared by all callers with
+/// the same argument and return types.
+///
+/// ```no_run
+/// # use critical_section::{CriticalSection, Mutex};
+/// # use std::cell::Cell;
+/// static TICKS: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+///
+/// fn add(cs: CriticalSection, n: u32) {
+///     let ticks = TICKS.borrow(cs);
+///     ticks.set(ticks.get() + n);
+/// }
+///
+/// critical_section::with_fn_arg(5, add);
+/// ```
+///
+/// The code generated is the same as for [`with_fn`](with_fn#code-generation), with `arg`
+/// passed along in a register when it fits in one.
+#[inline(never)]
+#[cfg_attr(
+    any(feature = "debug", feature = "defmt-trace", feature = "log-trace"),
+    track_caller
+)]
+pub fn with_fn_arg<A, R>(arg: A, f: fn(CriticalSection, A) -> R) -> R {
+    with(|cs| f(cs, arg))
+}
+
+/// Execute boxed closure `f` in a critical section.
+///
+/// This is [`with`] for closures only known at runtime, such as callbacks registered by
+/// other code. A single copy of the critical section code is shared by all callers with the
+/// same return type.
+///
+/// ```no_run
+/// use critical_section::CriticalSection;
+///
+/// let mut callbacks: Vec<Box<dyn FnOnce(CriticalSection)>> = Vec::new();
+/// callbacks.push(Box::new(|_cs| {
+///     // Runs in the critical section...
+/// }));
+///
+/// for callback in callbacks {
+///     critical_section::with_boxed(callback);
+/// }
+/// ```
+#[cfg(feature = "alloc")]
+#[inline(never)]
+#[cfg_attr(
+    any(feature = "debug", feature = "defmt-trace", feature = "log-trace"),
+    track_caller
+)]
+pub fn with_boxed<R>(f: alloc::boxed::Box<dyn FnOnce(CriticalSection) -> R + '_>) -> R {
+    with(f)
+}
+
+/// Execute closure `f` in a critical section, in a single, non-inlined function with a fixed
+/// symbol name.
+///
+/// [`with`] is inlined into each caller, so static stack analysis tools such as
+/// `cargo call-stack` can't attribute the stack used by the critical section code to anything.
+/// This always runs it in the `_critical_section_1_0_with_outlined` function, whose frame
+/// covers acquiring and releasing the critical section, and which calls `f` through a
+/// `&mut dyn FnMut(CriticalSection)`.
+///
+/// ```no_run
+/// # use critical_section::Mutex;
+/// # use std::cell::Cell;
+/// static TICKS: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+///
+/// let ticks = critical_section::with_outlined(|cs| {
+///     let ticks = TICKS.borrow(cs);
+///     ticks.set(ticks.get() + 1);
+///     ticks.get()
+/// });
+/// # let _ = ticks;
+/// ```
+///
+/// Only a small wrapper, storing the result of `f`, is generic and inlined. The `small-code`
+/// Cargo feature makes [`with`] do the same, to save flash with many critical sections.
+#[inline]
+pub fn with_outlined<R>(f: impl FnOnce(CriticalSection) -> R) -> R {
+    let mut f = Some(f);
+    let mut result = None;
+    _critical_section_1_0_with_outlined(&mut |cs| {
+        if let Some(f) = f.take() {
+            result = Some(f(cs));
+        }
+    });
+    match result {
+        Some(result) => result,
+        // `_critical_section_1_0_with_outlined` always calls the closure once.
+        None => unreachable!(),
+    }
+}
+
+#[no_mangle]
+#[inline(never)]
+fn _critical_section_1_0_with_outlined(f: &mut dyn FnMut(CriticalSection)) {
+    with_inlined(f)
+}
+
+/// Execute closure `f` in a critical section, from an interrupt handler that can't be
+/// preempted.
+///
+/// An interrupt handler running at the highest priority that any code using the critical
+/// section runs at already can't be interrupted by that code, so masking interrupts again is
+/// wasted work. This skips acquiring and releasing the critical section from the
+/// implementation, and only does the crate's bookkeeping, so nested critical sections and
+/// [`is_active`] keep working.
+///
+/// ```no_run
+/// # use critical_section::Mutex;
+/// # use std::cell::Cell;
+/// static SAMPLES: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+///
+/// // The ADC interrupt has the highest priority, and nothing else runs on this core.
+/// fn adc_interrupt() {
+///     unsafe {
+///         critical_section::with_from_isr(|cs| {
+///             let samples = SAMPLES.borrow(cs);
+///             samples.set(samples.get() + 1);
+///         });
+///     }
+/// }
+/// ```
+///
+/// In debug builds, this panics if the implementation reports that it's not called from an
+/// interrupt handler, see [`Impl::in_isr`].
+///
+/// # Safety
+///
+/// - `f` must run to completion without being preempted by any other code using the critical
+///   section: this must be called from an interrupt handler at the highest priority of all
+///   such code, on a single-core system.
+/// - [`without`] must not be called inside `f`, since there is no critical section to release.
+#[inline(always)]
+#[cfg_attr(
+    any(feature = "debug", feature = "defmt-trace", feature = "log-trace"),
+    track_caller
+)]
+pub unsafe fn with_from_isr<R>(f: impl FnOnce(CriticalSection) -> R) -> R {
+    extern "Rust" {
+        fn _critical_section_1_0_in_isr() -> bool;
+    }
+
+    struct Exit;
+
+    impl Drop for Exit {
+        #[inline(always)]
+        fn drop(&mut self) {
+            unsafe {
+                state::exit();
+            }
+        }
+    }
+
+    debug_assert!(
+        _critical_section_1_0_in_isr(),
+        "with_from_isr called outside of an interrupt handler"
+    );
+
+    if state::enter() == 0 {
+        state::set_outermost(RestoreState::invalid());
+    }
+    let _exit = Exit;
+    f(CriticalSection::new_unchecked())
+}
+
+/// Execute closure `f` for each item of `items`, each in its own critical section.
+///
+/// Interrupts can run between items, so processing a large batch, such as copying a buffer
+/// into a queue shared with an interrupt handler, doesn't mask interrupts for longer than a
+/// single item takes. The iterator itself is advanced outside of the critical section.
+///
+/// Since other code may run in the gaps, anything `f` checked for a previous item may have
+/// changed, and `f` must check it again. Return [`ControlFlow::Break`] to stop, for example when
+/// the queue is full, and the value it carries is returned. Otherwise, `None` is returned once
+/// all items are processed.
+///
+/// ```no_run
+/// # use critical_section::{Chunk, Mutex};
+/// # use std::cell::RefCell;
+/// use core::ops::ControlFlow;
+///
+/// static QUEUE: Mutex<RefCell<Vec<u8>>> = Mutex::new(RefCell::new(Vec::new()));
+/// const CAPACITY: usize = 256;
+///
+/// fn enqueue_all(data: &[u8]) -> usize {
+///     let enqueued = critical_section::with_chunks(data, |chunk: Chunk, byte| {
+///         // The interrupt handler may have emptied the queue since the last byte.
+///         let mut queue = QUEUE.borrow_ref_mut(chunk.token());
+///         if queue.len() == CAPACITY {
+///             return ControlFlow::Break(chunk.index());
+///         }
+///         queue.push(*byte);
+///         ControlFlow::Continue(())
+///     });
+///     enqueued.unwrap_or(data.len())
+/// }
+/// ```
+///
+/// If the current thread is already in a critical section, `f` still runs once per item,
+/// but there are no gaps.
+#[inline]
+pub fn with_chunks<I: IntoIterator, B>(
+    items: I,
+    mut f: impl FnMut(Chunk, I::Item) -> ControlFlow<B>,
+) -> Option<B> {
+    for (index, item) in items.into_iter().enumerate() {
+        let flow = with(|cs| {
+            #[cfg(feature = "watchdog")]
+            watchdog_checkpoint(cs);
+            f(Chunk { cs, index }, item)
+        });
+        if let ControlFlow::Break(value) = flow {
+            return Some(value);
+        }
+    }
+    None
+}
+
+/// The critical section of a single item, passed to the closure of [`with_chunks`].
+#[derive(Clone, Copy, Debug)]
+pub struct Chunk<'cs> {
+    cs: CriticalSection<'cs>,
+    index: usize,
+}
+
+impl<'cs> Chunk<'cs> {
+    /// Returns the critical section token.
+    #[inline(always)]
+    pub fn token(&self) -> CriticalSection<'cs> {
+        self.cs
+    }
+
+    /// Returns the index of the item, counting from `0`.
+    #[inline]
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns whether other code may have run since the previous item was processed, which is
+    /// the case for every item but the first, unless already nested in a critical section.
+    #[inline]
+    pub fn after_gap(&self) -> bool {
+        self.index > 0 && unsafe { state::depth() } == 1
+    }
+}
+
+/// Busy-wait for `total`, with interrupts masked, but never for longer than `max_masked` at a
+/// time.
+///
+/// `delay` busy-waits for the duration it's given, for example with a cycle-counting delay
+/// loop. It's called in critical sections of up to `max_masked` each, which are released in
+/// between so pending interrupts can run. This is for timing that must not be stretched by
+/// interrupts, such as the bits of a bit-banged protocol, while keeping the interrupt latency
+/// bounded. The gaps add to the total time, so the wait ends at `total` or later.
+///
+/// With `guard`, the critical section of that guard is used, and released between chunks
+/// with [`Guard::yield_point`]. It must be the outermost critical section, since a nested one
+/// can't be released. Otherwise, each chunk enters its own critical section, and this must be
+/// called outside of any critical section.
+///
+/// ```no_run
+/// use core::time::Duration;
+///
+/// # fn delay_cycles(_: Duration) {}
+/// # fn set_pin(_: bool) {}
+/// // A 480µs reset pulse, without masking interrupts for more than 50µs.
+/// set_pin(false);
+/// critical_section::delay_in_sections(
+///     None,
+///     Duration::from_micros(480),
+///     Duration::from_micros(50),
+///     delay_cycles,
+/// );
+/// set_pin(true);
+/// ```
+///
+/// # Panics
+///
+/// Panics if `max_masked` is zero and `total` isn't, and if called nested in another critical
+/// section, which would keep interrupts masked for the whole `total`: inside one with `None`,
+/// or with a `guard` that isn't the outermost critical section.
+pub fn delay_in_sections(
+    mut guard: Option<&mut Guard>,
+    total: Duration,
+    max_masked: Duration,
+    mut delay: impl FnMut(Duration),
+) {
+    assert!(
+        !max_masked.is_zero() || total.is_zero(),
+        "delay_in_sections with a zero `max_masked`"
+    );
+    match guard {
+        // The guard holds the critical section, so the depth can be read.
+        Some(_) => assert!(
+            unsafe { state::depth() } == 1,
+            "delay_in_sections with a guard nested in another critical section"
+        ),
+        None => assert!(
+            !is_active(),
+            "delay_in_sections without a guard inside a critical section"
+        ),
+    }
+
+    let mut remaining = total;
+    let mut first = true;
+    while !remaining.is_zero() {
+        let chunk = remaining.min(max_masked);
+        match guard.as_deref_mut() {
+            Some(guard) => {
+                if !first {
+                    guard.yield_point();
+                }
+                delay(chunk);
+            }
+            None => with(|_| delay(chunk)),
+        }
+        remaining -= chunk;
+        first = false;
+    }
+}
+
+/// Execute closure `f` in a critical section up to `max_attempts` times, releasing it in
+/// between, until it returns [`ControlFlow::Break`].
+///
+/// This is for polling a condition that must be checked under the critical section, such as
+/// a hardware flag that an interrupt handler also clears. Releasing between attempts lets
+/// interrupts run while waiting, which a loop inside [`with`] would block. Returns the value
+/// carried by `Break`, or `None` if every attempt returned [`ControlFlow::Continue`].
+///
+/// ```no_run
+/// # use critical_section::Mutex;
+/// # use std::cell::Cell;
+/// use core::ops::ControlFlow;
+///
+/// static RX_READY: Mutex<Cell<Option<u8>>> = Mutex::new(Cell::new(None));
+///
+/// let received = critical_section::retry_with(1000, |cs| match RX_READY.borrow(cs).take() {
+///     Some(byte) => ControlFlow::Break(byte),
+///     None => ControlFlow::Continue(()),
+/// });
+/// ```
+///
+/// If the current thread is already in a critical section, releasing it does nothing, so the
+/// condition can't change between attempts unless `f` changes it itself.
+#[inline]
+pub fn retry_with<R>(
+    max_attempts: usize,
+    f: impl FnMut(CriticalSection) -> ControlFlow<R>,
+) -> Option<R> {
+    retry_with_backoff(max_attempts, f, |_| {})
+}
+
+/// Like [`retry_with`], calling `backoff` outside of the critical section between attempts.
+///
+/// `backoff` is passed the number of attempts made so far, and can wait before the next one,
+/// for example with an exponentially growing delay or a `wfi` instruction. It is not called
+/// after the last attempt.
+#[inline]
+pub fn retry_with_backoff<R>(
+    max_attempts: usize,
+    mut f: impl FnMut(CriticalSection) -> ControlFlow<R>,
+    mut backoff: impl FnMut(usize),
+) -> Option<R> {
+    for attempt in 1..=max_attempts {
+        if let ControlFlow::Break(value) = with(&mut f) {
+            return Some(value);
+        }
+        if attempt < max_attempts {
+            backoff(attempt);
+        }
+    }
+    None
+}
+
+/// Execute closure `f` in a critical section, unless the current thread is already in one.
+///
+/// Unlike [`with`], this never nests: if the current thread is already inside a critical
+/// section, `f` is not run and [`AlreadyAcquired`] is returned. This is useful for code that
+/// must not run with the critical section already held by a caller, for example because
+/// it would hold it for a long time.
+///
+/// If another thread holds the critical section, this waits for it to be released,
+/// like [`with`] does.
+#[inline]
+#[cfg_attr(
+    any(feature = "debug", feature = "defmt-trace", feature = "log-trace"),
+    track_caller
+)]
+pub fn try_with<R>(f: impl FnOnce(CriticalSection) -> R) -> Result<R, AlreadyAcquired> {
+    let guard = enter();
+    if guard.depth > 1 {
+        return Err(AlreadyAcquired);
+    }
+    Ok(f(guard.token()))
+}
+
+/// Execute fallible closure `f` in a critical section, returning its result.
+///
+/// This is the same as [`with`], spelled out for closures returning a [`Result`], so `?` can
+/// be used inside them without annotating the closure's return type.
+///
+/// ```no_run
+/// # use critical_section::Mutex;
+/// # use std::cell::RefCell;
+/// static QUEUE: Mutex<RefCell<Vec<u8>>> = Mutex::new(RefCell::new(Vec::new()));
+///
+/// fn pop_pair() -> Result<(u8, u8), ()> {
+///     critical_section::with_try(|cs| {
+///         let mut queue = QUEUE.borrow_ref_mut(cs);
+///         let a = queue.pop().ok_or(())?;
+///         let b = queue.pop().ok_or(())?;
+///         Ok((a, b))
+///     })
+/// }
+/// ```
+#[inline]
+#[cfg_attr(
+    any(feature = "debug", feature = "defmt-trace", feature = "log-trace"),
+    track_caller
+)]
+pub fn with_try<R, E>(f: impl FnOnce(CriticalSection) -> Result<R, E>) -> Result<R, E> {
+    with(f)
+}
+
+/// Execute fallible closure `f` in a critical section, then map its error with `map_err`
+/// after releasing the critical section.
+///
+/// `map_err` runs after the critical section entered by this call is released, so it can do
+/// slow work such as logging the error without keeping interrupts disabled. When this is
+/// nested inside another critical section, that one is still held while `map_err` runs.
+#[inline]
+#[cfg_attr(
+    any(feature = "debug", feature = "defmt-trace", feature = "log-trace"),
+    track_caller
+)]
+pub fn with_try_map_err<R, E, F>(
+    f: impl FnOnce(CriticalSection) -> Result<R, E>,
+    map_err: impl FnOnce(E) -> F,
+) -> Result<R, F> {
+    with(f).map_err(map_err)
+}
+
+/// Error returned by [`try_with`] when the current thread is already in a critical section.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AlreadyAcquired;
+
+impl core::fmt::Display for AlreadyAcquired {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("already in a critical section")
     }
 }
 
@@ -211,33 +1441,168 @@ pub fn with<R>(f: impl FnOnce(CriticalSection) -> R) -> R {
 /// # Safety
 ///
 /// Implementations must uphold the contract specified in [`crate::acquire`] and [`crate::release`].
+///
+/// In addition, the restore states returned while the current context already holds the
+/// critical section must be interchangeable: any of them may be passed to the `release` of
+/// any nested level. [`without`] relies on this to release every level, and acquire them
+/// again, which is needed for implementations that count the nesting. This holds for
+/// implementations returning the same restore state for every nested acquire, such as
+/// "interrupts were already disabled".
 pub unsafe trait Impl {
     /// Acquire the critical section.
+    ///
+    /// # Safety
+    ///
+    /// Callers must uphold the contract specified in [`crate::acquire`].
     unsafe fn acquire() -> RawRestoreState;
     /// Release the critical section.
+    ///
+    /// # Safety
+    ///
+    /// Callers must uphold the contract specified in [`crate::release`].
     unsafe fn release(restore_state: RawRestoreState);
+
+    /// Acquire the critical section, unless that means waiting for another context to release
+    /// it, returning `None` then.
+    ///
+    /// This is optional, the default implementation always waits and acquires the critical
+    /// section. See [`with_or_else`].
+    ///
+    /// # Safety
+    ///
+    /// Callers must uphold the contract specified in [`crate::acquire`], when this returns
+    /// `Some`.
+    #[inline]
+    #[allow(clippy::unit_arg)] // `RawRestoreState` is `()` by default.
+    unsafe fn try_acquire() -> Option<RawRestoreState> {
+        Some(Self::acquire())
+    }
+
+    /// Acquire a critical section that only masks interrupts up to `priority`, or return `None`
+    /// to have [`with_max_priority`] acquire the full critical section instead.
+    ///
+    /// This is optional, the default implementation always returns `None`. The full critical
+    /// section is then acquired through [`crate::acquire`], so the crate knows it's held, and
+    /// nested critical sections don't mistake it for a priority section.
+    ///
+    /// # Safety
+    ///
+    /// Callers must uphold the contract specified in [`crate::acquire`] when this returns
+    /// `Some`, with [`release_priority`](Impl::release_priority) instead of [`crate::release`].
+    #[inline]
+    unsafe fn acquire_priority(priority: u8) -> Option<RawRestoreState> {
+        let _ = priority;
+        None
+    }
+
+    /// Release a critical section acquired with [`acquire_priority`](Impl::acquire_priority).
+    ///
+    /// This is only called with restore states returned by `acquire_priority`, so it must be
+    /// implemented along with it. The default implementation calls [`release`](Impl::release).
+    ///
+    /// # Safety
+    ///
+    /// Callers must uphold the contract specified in [`crate::release`], with
+    /// [`acquire_priority`](Impl::acquire_priority) instead of [`crate::acquire`].
+    #[inline]
+    unsafe fn release_priority(restore_state: RawRestoreState) {
+        Self::release(restore_state)
+    }
+
+    /// Arm a deadline `cycles` from now, returning whether this is supported.
+    ///
+    /// This is optional, the default implementation returns `false`, and budgets are then
+    /// checked with a software cycle counter if one is set. See `with_budget`.
+    ///
+    /// # Safety
+    ///
+    /// Only called inside the outermost critical section, followed by a call to
+    /// [`disarm_deadline`](Impl::disarm_deadline) before releasing it.
+    #[inline]
+    unsafe fn arm_deadline(cycles: u32) -> bool {
+        let _ = cycles;
+        false
+    }
+
+    /// Disarm the deadline armed with [`arm_deadline`](Impl::arm_deadline), returning whether
+    /// it expired.
+    ///
+    /// # Safety
+    ///
+    /// Only called after [`arm_deadline`](Impl::arm_deadline) returned `true`, inside the
+    /// same critical section.
+    #[inline]
+    unsafe fn disarm_deadline() -> bool {
+        false
+    }
+
+    /// Suspend preemption by the scheduler, leaving interrupts enabled, returning whether this
+    /// is supported.
+    ///
+    /// This is optional, the default implementation returns `false`, and [`suspend`] then
+    /// acquires the full critical section instead.
+    ///
+    /// Calls must nest: suspending again while suspended must succeed, and preemption resumes
+    /// when the outermost suspension is resumed.
+    ///
+    /// # Safety
+    ///
+    /// When this returns `true`, it's followed by a call to
+    /// [`resume_scheduler`](Impl::resume_scheduler) in the same context.
+    #[inline]
+    unsafe fn suspend_scheduler() -> bool {
+        false
+    }
+
+    /// Resume preemption suspended with [`suspend_scheduler`](Impl::suspend_scheduler).
+    ///
+    /// # Safety
+    ///
+    /// Only called after [`suspend_scheduler`](Impl::suspend_scheduler) returned `true`, in
+    /// the same context.
+    #[inline]
+    unsafe fn resume_scheduler() {}
+
+    /// Returns whether the current context is an interrupt handler.
+    ///
+    /// This is only used to check callers of [`with_from_isr`] in debug builds. It's optional,
+    /// the default implementation can't tell and returns `true`, which skips the check.
+    ///
+    /// # Safety
+    ///
+    /// None, this is `unsafe` for consistency with the other methods.
+    #[inline]
+    unsafe fn in_isr() -> bool {
+        true
+    }
 }
 
 /// Set the critical section implementation.
 ///
 /// # Example
 ///
-/// ```
+/// ```no_run
 /// use critical_section::RawRestoreState;
 ///
+/// extern "C" {
+///     fn vPortEnterCritical();
+///     fn vPortExitCritical();
+/// }
+///
 /// struct MyCriticalSection;
 /// critical_section::set_impl!(MyCriticalSection);
 ///
 /// unsafe impl critical_section::Impl for MyCriticalSection {
 ///     unsafe fn acquire() -> RawRestoreState {
-///         // ...
+///         vPortEnterCritical();
+///         RawRestoreState::default()
 ///     }
 ///
-///     unsafe fn release(restore_state: RawRestoreState) {
-///         // ...
+///     unsafe fn release(_restore_state: RawRestoreState) {
+///         vPortExitCritical();
 ///     }
 /// }
-///
+/// ```
 #[macro_export]
 macro_rules! set_impl {
     ($t: ty) => {
@@ -249,5 +1614,83 @@ macro_rules! set_impl {
         unsafe fn _critical_section_1_0_release(restore_state: $crate::RawRestoreState) {
             <$t as $crate::Impl>::release(restore_state)
         }
+        #[no_mangle]
+        unsafe fn _critical_section_1_0_try_acquire() -> Option<$crate::RawRestoreState> {
+            <$t as $crate::Impl>::try_acquire()
+        }
+        #[no_mangle]
+        unsafe fn _critical_section_1_0_acquire_priority(
+            priority: u8,
+        ) -> Option<$crate::RawRestoreState> {
+            <$t as $crate::Impl>::acquire_priority(priority)
+        }
+        #[no_mangle]
+        unsafe fn _critical_section_1_0_release_priority(restore_state: $crate::RawRestoreState) {
+            <$t as $crate::Impl>::release_priority(restore_state)
+        }
+        #[no_mangle]
+        unsafe fn _critical_section_1_0_arm_deadline(cycles: u32) -> bool {
+            <$t as $crate::Impl>::arm_deadline(cycles)
+        }
+        #[no_mangle]
+        unsafe fn _critical_section_1_0_disarm_deadline() -> bool {
+            <$t as $crate::Impl>::disarm_deadline()
+        }
+        #[no_mangle]
+        unsafe fn _critical_section_1_0_suspend_scheduler() -> bool {
+            <$t as $crate::Impl>::suspend_scheduler()
+        }
+        #[no_mangle]
+        unsafe fn _critical_section_1_0_resume_scheduler() {
+            <$t as $crate::Impl>::resume_scheduler()
+        }
+        #[no_mangle]
+        unsafe fn _critical_section_1_0_in_isr() -> bool {
+            <$t as $crate::Impl>::in_isr()
+        }
+    };
+}
+
+/// Set the critical section implementation from a pair of functions.
+///
+/// This is a shorthand for [`set_impl!`] when the implementation is just a lock and unlock
+/// function, for example from a vendor SDK. The first function is called to acquire the
+/// critical section and returns the restore state, the second one is called with it to
+/// release the critical section. Both are called in an `unsafe` context, so they can be
+/// `unsafe` or `extern "C"` functions.
+///
+/// The functions must uphold the contract of [`Impl`], including allowing nesting.
+///
+/// ```no_run
+/// extern "C" {
+///     // Disables interrupts, returning the previous interrupt state.
+///     fn sdk_irq_save() -> u32;
+///     // Restores the interrupt state returned by `sdk_irq_save`.
+///     fn sdk_irq_restore(state: u32);
+/// }
+///
+/// // With the `restore-state-u32` Cargo feature enabled.
+/// # #[cfg(any())]
+/// critical_section::set_impl_from_fns!(sdk_irq_save, sdk_irq_restore);
+/// ```
+#[macro_export]
+macro_rules! set_impl_from_fns {
+    ($acquire: expr, $release: expr $(,)?) => {
+        const _: () = {
+            struct FnImpl;
+            $crate::set_impl!(FnImpl);
+
+            unsafe impl $crate::Impl for FnImpl {
+                #[inline(always)]
+                unsafe fn acquire() -> $crate::RawRestoreState {
+                    $acquire()
+                }
+
+                #[inline(always)]
+                unsafe fn release(restore_state: $crate::RawRestoreState) {
+                    $release(restore_state)
+                }
+            }
+        };
     };
 }
@@ -0,0 +1,182 @@
+//! Lock-order tracking for critical section domains.
+//!
+//! Acquiring domains in different orders in different contexts can deadlock, but only when
+//! the timing is unlucky. With the `lock-order` Cargo feature enabled in debug builds, the
+//! order in which domains are acquired is recorded, and acquiring a domain panics if it was
+//! ever acquired the other way around, before it has a chance to deadlock.
+//!
+//! The domains held by each context are tracked per thread with the `std` Cargo feature, and
+//! per core otherwise, using the function set with [`set_core_id`]. Only domains with an ID
+//! below [`MAX_DOMAINS`] are tracked. The bookkeeping is protected by the global critical
+//! section, so a global implementation must be available.
+//!
+//! ```no_run
+//! # fn read_core_id_register() -> usize { 0 }
+//! // On a multi-core chip without `std`:
+//! critical_section::lock_order::set_core_id(read_core_id_register);
+//! ```
+//!
+//! In release builds, nothing is tracked.
+
+#![cfg_attr(not(debug_assertions), allow(dead_code))]
+
+#[cfg(feature = "std")]
+extern crate std;
+
+use core::cell::RefCell;
+
+use super::{CriticalSection, Mutex};
+
+/// Number of domain IDs tracked, starting at 0.
+pub const MAX_DOMAINS: usize = 64;
+/// Number of domains a context can hold at the same time while being tracked.
+const MAX_HELD: usize = 8;
+#[cfg(not(feature = "std"))]
+const MAX_CORES: usize = 8;
+
+/// Domains held by a context, in acquisition order.
+#[derive(Clone, Copy)]
+struct Held {
+    ids: [usize; MAX_HELD],
+    len: usize,
+}
+
+impl Held {
+    const EMPTY: Held = Held {
+        ids: [0; MAX_HELD],
+        len: 0,
+    };
+
+    fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.ids[..self.len].iter().copied()
+    }
+}
+
+/// Bit `b` of entry `a` is set when domain `b` was acquired while holding domain `a`.
+static ORDER: Mutex<RefCell<[u64; MAX_DOMAINS]>> = Mutex::new(RefCell::new([0; MAX_DOMAINS]));
+
+#[cfg(feature = "std")]
+std::thread_local!(static HELD: RefCell<Held> = const { RefCell::new(Held::EMPTY) });
+
+#[cfg(not(feature = "std"))]
+static HELD: Mutex<RefCell<[Held; MAX_CORES]>> = Mutex::new(RefCell::new([Held::EMPTY; MAX_CORES]));
+
+#[cfg(not(feature = "std"))]
+type CoreId = fn() -> usize;
+
+#[cfg(not(feature = "std"))]
+static CORE_ID: Mutex<core::cell::Cell<Option<CoreId>>> = Mutex::new(core::cell::Cell::new(None));
+
+/// Set the function returning the index of the current core, below 8.
+///
+/// Without it, all code is assumed to run on a single core. With the `std` Cargo feature, the
+/// domains held are tracked per thread instead, and this does nothing.
+pub fn set_core_id(core_id: fn() -> usize) {
+    #[cfg(not(feature = "std"))]
+    crate::with(|cs| CORE_ID.borrow(cs).set(Some(core_id)));
+    #[cfg(feature = "std")]
+    let _ = core_id;
+}
+
+/// Calls `f` with the domains held by the current context.
+///
+/// # Safety
+///
+/// Must be called while the global critical section is held.
+#[track_caller]
+unsafe fn with_held<R>(f: impl FnOnce(&mut Held) -> R) -> R {
+    #[cfg(feature = "std")]
+    return HELD.with(|held| f(&mut held.borrow_mut()));
+
+    #[cfg(not(feature = "std"))]
+    {
+        let cs = CriticalSection::new_unchecked();
+        let core = CORE_ID.borrow(cs).get().map_or(0, |core_id| core_id());
+        assert!(
+            core < MAX_CORES,
+            "core ID out of range for lock-order tracking"
+        );
+        f(&mut HELD.borrow_ref_mut(cs)[core])
+    }
+}
+
+/// Returns the set of domains acquired while holding `domain`, directly or indirectly.
+fn reachable(order: &[u64; MAX_DOMAINS], domain: usize) -> u64 {
+    let mut reached = 0u64;
+    let mut frontier = order[domain];
+    while frontier & !reached != 0 {
+        let next = frontier & !reached;
+        reached |= next;
+        frontier = 0;
+        for (id, &edges) in order.iter().enumerate() {
+            if next & (1 << id) != 0 {
+                frontier |= edges;
+            }
+        }
+    }
+    reached
+}
+
+/// Record that `domain` is about to be acquired, panicking if that inverts a previous order.
+#[inline(never)]
+#[track_caller]
+pub(crate) fn on_acquire(domain: usize) {
+    if domain >= MAX_DOMAINS {
+        return;
+    }
+
+    let conflict = unsafe {
+        let restore_state = crate::acquire_raw();
+        let cs = CriticalSection::new_unchecked();
+        let conflict = with_held(|held| {
+            let mut order = ORDER.borrow_ref_mut(cs);
+            // Nested acquisitions of a domain that's already held always succeed, and don't
+            // add to the order.
+            let conflict = if held.iter().any(|id| id == domain) {
+                None
+            } else {
+                let reached = reachable(&order, domain);
+                let conflict = held.iter().find(|&id| reached & (1 << id) != 0);
+                if conflict.is_none() {
+                    for id in held.iter() {
+                        order[id] |= 1 << domain;
+                    }
+                }
+                conflict
+            };
+            if conflict.is_none() && held.len < MAX_HELD {
+                held.ids[held.len] = domain;
+                held.len += 1;
+            }
+            conflict
+        });
+        crate::release_raw(restore_state);
+        conflict
+    };
+
+    if let Some(held) = conflict {
+        panic!(
+            "lock order violation: acquiring domain {} while holding domain {}, which was previously acquired while holding domain {}",
+            domain, held, domain
+        );
+    }
+}
+
+/// Record that `domain` was released.
+#[inline(never)]
+pub(crate) fn on_release(domain: usize) {
+    if domain >= MAX_DOMAINS {
+        return;
+    }
+
+    unsafe {
+        let restore_state = crate::acquire_raw();
+        with_held(|held| {
+            if let Some(i) = held.ids[..held.len].iter().rposition(|&id| id == domain) {
+                held.ids.copy_within(i + 1..held.len, i);
+                held.len -= 1;
+            }
+        });
+        crate::release_raw(restore_state);
+    }
+}
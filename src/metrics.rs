@@ -0,0 +1,247 @@
+//! Counters and durations of critical sections.
+//!
+//! With the `metrics` Cargo feature enabled, the crate counts critical sections and, if a
+//! timestamp function is set with [`set_timestamp`], measures how long the outermost ones are
+//! held. This is collected at the acquire and release boundary, so it covers every critical
+//! section entered through this crate:
+//!
+//! ```no_run
+//! // The DWT cycle counter on Cortex-M.
+//! const DWT_CYCCNT: *const u32 = 0xe000_1004 as *const u32;
+//!
+//! fn read_cycle_counter() -> u32 {
+//!     unsafe { DWT_CYCCNT.read_volatile() }
+//! }
+//!
+//! critical_section::metrics::set_timestamp(read_cycle_counter);
+//!
+//! // ... run the application for a while ...
+//!
+//! let snapshot = critical_section::metrics::snapshot();
+//! // Report `snapshot.max_duration` somewhere...
+//! # let _ = snapshot;
+//! ```
+//!
+//! The metrics are shared by all threads and cores. The timestamp function works like the
+//! instrumentation hooks, and is called inside the critical section.
+
+use core::cell::Cell;
+
+use super::{enter, with, CriticalSection, Mutex};
+
+type Timestamp = fn() -> u32;
+
+struct Metrics {
+    timestamp: Cell<Option<Timestamp>>,
+    /// Timestamp when the outermost critical section was entered.
+    start: Cell<u32>,
+    entries: Cell<u64>,
+    max_depth: Cell<usize>,
+    total_duration: Cell<u64>,
+    max_duration: Cell<u32>,
+}
+
+static METRICS: Mutex<Metrics> = Mutex::new(Metrics {
+    timestamp: Cell::new(None),
+    start: Cell::new(0),
+    entries: Cell::new(0),
+    max_depth: Cell::new(0),
+    total_duration: Cell::new(0),
+    max_duration: Cell::new(0),
+});
+
+/// Metrics collected since startup or the last [`reset`], returned by [`snapshot`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[non_exhaustive]
+pub struct Snapshot {
+    /// Number of critical sections entered, including nested ones.
+    pub entries: u64,
+    /// Deepest nesting reached.
+    pub max_depth: usize,
+    /// Total time spent in the outermost critical sections, in timestamp ticks.
+    pub total_duration: u64,
+    /// Longest time spent in a single outermost critical section, in timestamp ticks.
+    pub max_duration: u32,
+}
+
+/// Contention on the lock of [`multicore::Spinlock`](crate::multicore::Spinlock) or
+/// [`multicore::HardwareSpinlock`](crate::multicore::HardwareSpinlock), returned by
+/// [`contention`].
+///
+/// Only acquisitions of the lock are counted, not nested critical sections on a core that
+/// already holds it. Spin iterations are counted in the `while` loop waiting for the lock, so
+/// their duration depends on the chip.
+///
+/// The counts are for the single lock behind the global critical section, not per lock
+/// domain: the critical section domains of the `domain` module come from a separate
+/// implementation, whose contention isn't seen here.
+#[cfg(feature = "multicore-spinlock")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[non_exhaustive]
+pub struct Contention {
+    /// Number of times the lock was taken.
+    pub acquisitions: u64,
+    /// Number of times the lock was held by another core, and had to be waited for.
+    pub contended: u64,
+    /// Total spin iterations waiting for the lock.
+    pub total_spins: u64,
+    /// Most spin iterations waiting for the lock in a single acquisition.
+    pub max_spins: u32,
+}
+
+#[cfg(feature = "multicore-spinlock")]
+static CONTENTION: Mutex<Cell<Contention>> = Mutex::new(Cell::new(Contention {
+    acquisitions: 0,
+    contended: 0,
+    total_spins: 0,
+    max_spins: 0,
+}));
+
+/// Set the function used to measure durations.
+///
+/// It should return a free-running counter, which may wrap around. Durations are only
+/// measured for critical sections entered after setting it.
+pub fn set_timestamp(timestamp: fn() -> u32) {
+    with(|cs| {
+        let metrics = METRICS.borrow(cs);
+        metrics.timestamp.set(Some(timestamp));
+        // This critical section is already being measured.
+        metrics.start.set(timestamp());
+    });
+}
+
+/// Execute closure `f` in a critical section, returning its result and how long it took.
+///
+/// The duration is measured in ticks of the timestamp function set with [`set_timestamp`],
+/// read right after acquiring and right before releasing the critical section. It's `0` if no
+/// timestamp function is set. When nested, this measures `f` only, not the outer critical
+/// section.
+///
+/// ```no_run
+/// # fn write_flash_page() {}
+/// let ((), cycles) = critical_section::metrics::with_timed(|_| write_flash_page());
+/// // Log `cycles`...
+/// # let _ = cycles;
+/// ```
+#[inline]
+#[cfg_attr(
+    any(feature = "debug", feature = "defmt-trace", feature = "log-trace"),
+    track_caller
+)]
+pub fn with_timed<R>(f: impl FnOnce(CriticalSection) -> R) -> (R, u32) {
+    let guard = enter();
+    let timestamp = METRICS.borrow(guard.token()).timestamp.get();
+    let start = timestamp.map_or(0, |timestamp| timestamp());
+    let result = f(guard.token());
+    let elapsed = timestamp.map_or(0, |timestamp| timestamp().wrapping_sub(start));
+    drop(guard);
+    (result, elapsed)
+}
+
+/// Returns the metrics collected so far.
+///
+/// The critical section taken to read the metrics is not counted.
+pub fn snapshot() -> Snapshot {
+    unsafe {
+        let restore_state = crate::acquire_raw();
+        let metrics = METRICS.borrow(CriticalSection::new_unchecked());
+        let snapshot = Snapshot {
+            entries: metrics.entries.get(),
+            max_depth: metrics.max_depth.get(),
+            total_duration: metrics.total_duration.get(),
+            max_duration: metrics.max_duration.get(),
+        };
+        crate::release_raw(restore_state);
+        snapshot
+    }
+}
+
+/// Returns the contention on the lock of [`multicore::Spinlock`](crate::multicore::Spinlock)
+/// or [`multicore::HardwareSpinlock`](crate::multicore::HardwareSpinlock) so far.
+///
+/// A high share of contended acquisitions, or a high `max_spins`, means the cores stall each
+/// other. With the `debug` Cargo feature, sampling `last_entry_location` while another core
+/// spins finds the critical sections holding the lock. The critical section taken to read
+/// this is counted.
+#[cfg(feature = "multicore-spinlock")]
+pub fn contention() -> Contention {
+    with(|cs| CONTENTION.borrow(cs).get())
+}
+
+/// Resets the metrics collected so far.
+pub fn reset() {
+    unsafe {
+        let restore_state = crate::acquire_raw();
+        #[cfg(feature = "multicore-spinlock")]
+        CONTENTION
+            .borrow(CriticalSection::new_unchecked())
+            .set(Contention::default());
+        let metrics = METRICS.borrow(CriticalSection::new_unchecked());
+        metrics.entries.set(0);
+        metrics.max_depth.set(0);
+        metrics.total_duration.set(0);
+        metrics.max_duration.set(0);
+        crate::release_raw(restore_state);
+    }
+}
+
+/// # Safety
+///
+/// Must be called right after entering a critical section, with the previous nesting depth.
+#[inline]
+pub(crate) unsafe fn on_enter(prev: usize) {
+    let metrics = METRICS.borrow(CriticalSection::new_unchecked());
+    metrics.entries.set(metrics.entries.get().wrapping_add(1));
+    if prev + 1 > metrics.max_depth.get() {
+        metrics.max_depth.set(prev + 1);
+    }
+    if prev == 0 {
+        if let Some(timestamp) = metrics.timestamp.get() {
+            metrics.start.set(timestamp());
+        }
+    }
+}
+
+/// # Safety
+///
+/// Must be called right before exiting a critical section, with the nesting depth before
+/// exiting.
+#[inline]
+pub(crate) unsafe fn on_exit(prev: usize) {
+    if prev != 1 {
+        return;
+    }
+    let metrics = METRICS.borrow(CriticalSection::new_unchecked());
+    if let Some(timestamp) = metrics.timestamp.get() {
+        let duration = timestamp().wrapping_sub(metrics.start.get());
+        metrics.total_duration.set(
+            metrics
+                .total_duration
+                .get()
+                .wrapping_add(u64::from(duration)),
+        );
+        if duration > metrics.max_duration.get() {
+            metrics.max_duration.set(duration);
+        }
+    }
+}
+
+/// # Safety
+///
+/// Must be called by the `multicore` implementations right after taking the lock,
+/// with the number of spin iterations it waited.
+#[cfg(feature = "multicore-spinlock")]
+#[inline]
+pub(crate) unsafe fn on_spinlock_acquired(spins: u32) {
+    let contention = CONTENTION.borrow(CriticalSection::new_unchecked());
+    let mut counts = contention.get();
+    counts.acquisitions = counts.acquisitions.wrapping_add(1);
+    if spins > 0 {
+        counts.contended = counts.contended.wrapping_add(1);
+        counts.total_spins = counts.total_spins.wrapping_add(u64::from(spins));
+        counts.max_spins = counts.max_spins.max(spins);
+    }
+    contention.set(counts);
+}
@@ -0,0 +1,20 @@
+//! Implementation for Miri, replacing the `std` one with the `miri` Cargo feature.
+//!
+//! Tests under Miri are assumed to use the critical section from a single thread. The
+//! implementation does nothing, so the crate's bookkeeping is plain `Cell`s without any
+//! synchronization: if tests use it from several threads anyway, Miri reports the accesses
+//! as data races, instead of the locking hiding them.
+
+struct MiriCriticalSection;
+crate::set_impl!(MiriCriticalSection);
+
+unsafe impl crate::Impl for MiriCriticalSection {
+    #[inline(always)]
+    unsafe fn acquire() -> bool {
+        // The restore state of the `std` implementation, which this replaces: not nested.
+        false
+    }
+
+    #[inline(always)]
+    unsafe fn release(_: bool) {}
+}
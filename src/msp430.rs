@@ -0,0 +1,28 @@
+use core::arch::asm;
+
+/// General interrupt enable bit of the status register.
+const SR_GIE: u16 = 1 << 3;
+
+struct Msp430CriticalSection;
+crate::set_impl!(Msp430CriticalSection);
+
+unsafe impl crate::Impl for Msp430CriticalSection {
+    #[inline(always)]
+    unsafe fn acquire() -> u16 {
+        let sr: u16;
+        asm!("mov r2, {}", out(reg) sr, options(nomem, nostack, preserves_flags));
+        // `dint` only takes effect after the next instruction, hence the `nop`. No `nomem`, so
+        // this also acts as a compiler fence.
+        asm!("dint {{ nop", options(nostack));
+        sr
+    }
+
+    #[inline(always)]
+    unsafe fn release(sr: u16) {
+        // Only re-enable interrupts if they were enabled before the critical section. The
+        // `nop`s avoid the `eint` pipeline hazards described in the family user's guides.
+        if sr & SR_GIE != 0 {
+            asm!("nop {{ eint {{ nop", options(nostack));
+        }
+    }
+}
@@ -0,0 +1,402 @@
+//! Critical section implementation for multi-core systems.
+//!
+//! Disabling interrupts is not enough on multi-core systems, since the other cores keep
+//! running. [`Spinlock`] combines disabling interrupts on the current core with a ticket
+//! spinlock shared by all cores.
+//!
+//! HALs only need to provide the platform specific parts through [`Platform`]:
+//!
+//! ```no_run
+//! use critical_section::multicore::{Platform, Spinlock};
+//!
+//! struct MyChip;
+//!
+//! unsafe impl Platform for MyChip {
+//!     fn core_id() -> usize {
+//!         // Read the core ID register.
+//!         0
+//!     }
+//!
+//!     unsafe fn disable_interrupts() -> bool {
+//!         // Disable interrupts on the current core, returning whether they were enabled.
+//!         false
+//!     }
+//!
+//!     unsafe fn enable_interrupts() {
+//!         // Enable interrupts on the current core.
+//!     }
+//! }
+//!
+//! critical_section::set_impl!(Spinlock<MyChip>);
+//! ```
+//!
+//! The spinlock needs atomic read-modify-write operations, which some multi-core chips such
+//! as the RP2040 don't have, so [`Spinlock`] is only available on targets with
+//! `cfg(target_has_atomic = "ptr")`. Those chips can use [`HardwareSpinlock`] instead, which
+//! takes the lock through [`HardwareLock`], and only needs atomic loads and stores.
+//!
+//! What happens when a core tries to acquire the lock while another core holds it is chosen
+//! with [`Platform::ON_CONTENTION`], see [`OnContention`].
+//!
+//! Both implementations handle nesting the same way, by tracking which core holds the lock.
+//!
+//! With the `metrics` Cargo feature, contention on the spinlock is counted, see
+//! [`metrics::contention`](crate::metrics::contention).
+//!
+//! This implementation uses a `u8` restore state, so enabling the `multicore-spinlock` Cargo
+//! feature also enables `restore-state-u8`.
+
+use core::hint::spin_loop;
+use core::marker::PhantomData;
+use core::sync::atomic::{fence, AtomicUsize, Ordering};
+
+use super::{Impl, RawRestoreState};
+
+/// Platform specific operations needed by [`Spinlock`] and [`HardwareSpinlock`].
+///
+/// # Safety
+///
+/// `core_id` must return a different value on each core, and the same value every time it's
+/// called on the same core. `disable_interrupts` and `enable_interrupts` must actually disable
+/// and enable interrupts on the current core.
+pub unsafe trait Platform {
+    /// Returns the index of the current core.
+    fn core_id() -> usize;
+
+    /// Disables interrupts on the current core, returning whether they were enabled before.
+    ///
+    /// # Safety
+    ///
+    /// Only called by the implementations in this module.
+    unsafe fn disable_interrupts() -> bool;
+
+    /// Enables interrupts on the current core.
+    ///
+    /// # Safety
+    ///
+    /// Only called by the implementations in this module, when `disable_interrupts` returned
+    /// `true`.
+    unsafe fn enable_interrupts();
+
+    /// What to do when the lock is held by another core.
+    ///
+    /// This is optional, the default is [`OnContention::Spin`].
+    const ON_CONTENTION: OnContention = OnContention::Spin;
+}
+
+/// Behavior of [`Spinlock`] and [`HardwareSpinlock`] when a core tries to acquire the lock while another core holds it.
+///
+/// Waiting keeps every critical section live, but makes the time to enter one depend on what
+/// the other cores do. Applications that need deterministic timing can instead treat
+/// contention as a bug, or handle it with [`with_or_else`](crate::with_or_else).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OnContention {
+    /// Spin until the lock is released, in both [`with`](crate::with) and
+    /// [`with_or_else`](crate::with_or_else). The fallback of `with_or_else` never runs.
+    Spin,
+    /// Panic, in both [`with`](crate::with) and [`with_or_else`](crate::with_or_else).
+    ///
+    /// Interrupts are restored before panicking. The panic handler must not use the critical
+    /// section, since the other core may still hold it.
+    Panic,
+    /// Run the fallback of [`with_or_else`](crate::with_or_else) right away. [`with`](crate::with)
+    /// still spins, since it can't fail.
+    TryFail,
+}
+
+/// Hardware lock shared by all cores, taken by [`HardwareSpinlock`] instead of using atomic
+/// read-modify-write operations.
+///
+/// On the RP2040, this is one of the SIO spinlocks:
+///
+/// ```no_run
+/// use critical_section::multicore::HardwareLock;
+///
+/// struct Sio;
+///
+/// /// Reserved for the critical section, like in `rp2040-hal`.
+/// const SPINLOCK31: *mut u32 = 0xd000_017c as *mut u32;
+///
+/// unsafe impl HardwareLock for Sio {
+///     unsafe fn try_lock() -> bool {
+///         // Reading claims the lock, returning 0 if another core holds it.
+///         SPINLOCK31.read_volatile() != 0
+///     }
+///
+///     unsafe fn unlock() {
+///         SPINLOCK31.write_volatile(1);
+///     }
+/// }
+/// ```
+///
+/// # Safety
+///
+/// Once `try_lock` returns `true` on a core, it must return `false` on every core until that
+/// core calls `unlock`. [`HardwareSpinlock`] takes care of memory ordering.
+pub unsafe trait HardwareLock {
+    /// Tries to take the lock, returning whether it was taken.
+    ///
+    /// # Safety
+    ///
+    /// Only called by [`HardwareSpinlock`], with interrupts disabled on the current core.
+    unsafe fn try_lock() -> bool;
+
+    /// Releases the lock.
+    ///
+    /// # Safety
+    ///
+    /// Only called by [`HardwareSpinlock`], by the core holding the lock.
+    unsafe fn unlock();
+}
+
+/// Critical section implementation based on disabling interrupts and a ticket spinlock.
+///
+/// Only available on targets with atomic read-modify-write operations, see
+/// [`HardwareSpinlock`] for the others. See the [module docs](self) for usage.
+#[cfg(target_has_atomic = "ptr")]
+pub struct Spinlock<P> {
+    _platform: PhantomData<P>,
+}
+
+/// Critical section implementation based on disabling interrupts and a [`HardwareLock`].
+///
+/// Unlike [`Spinlock`], this doesn't queue the cores waiting for the lock, so one of them may
+/// keep losing it to another.
+///
+/// ```no_run
+/// # use critical_section::multicore::{HardwareLock, HardwareSpinlock, Platform};
+/// # struct Sio;
+/// # unsafe impl Platform for Sio {
+/// #     fn core_id() -> usize { 0 }
+/// #     unsafe fn disable_interrupts() -> bool { false }
+/// #     unsafe fn enable_interrupts() {}
+/// # }
+/// # unsafe impl HardwareLock for Sio {
+/// #     unsafe fn try_lock() -> bool { true }
+/// #     unsafe fn unlock() {}
+/// # }
+/// critical_section::set_impl!(HardwareSpinlock<Sio>);
+/// ```
+pub struct HardwareSpinlock<P> {
+    _platform: PhantomData<P>,
+}
+
+/// Restore state bit: interrupts were enabled before acquiring.
+const INTERRUPTS_ENABLED: RawRestoreState = 1 << 0;
+/// Restore state bit: the current core already held the lock.
+const NESTED: RawRestoreState = 1 << 1;
+
+const NO_OWNER: usize = usize::MAX;
+
+static OWNER: AtomicUsize = AtomicUsize::new(NO_OWNER);
+
+/// Lock shared by all cores, only used with interrupts disabled on the current core, when it
+/// doesn't hold the lock already.
+trait Lock {
+    /// Takes the lock, waiting for other cores to release it, returning how many times it spun.
+    unsafe fn lock() -> u32;
+
+    /// Takes the lock if no core holds it or waits for it, without spinning, returning whether
+    /// it was taken.
+    unsafe fn try_lock() -> bool;
+
+    /// Releases the lock.
+    unsafe fn unlock();
+}
+
+/// The ticket spinlock used by [`Spinlock`].
+#[cfg(target_has_atomic = "ptr")]
+struct Ticket;
+
+#[cfg(target_has_atomic = "ptr")]
+static NEXT_TICKET: AtomicUsize = AtomicUsize::new(0);
+#[cfg(target_has_atomic = "ptr")]
+static NOW_SERVING: AtomicUsize = AtomicUsize::new(0);
+
+#[cfg(target_has_atomic = "ptr")]
+impl Lock for Ticket {
+    #[inline]
+    unsafe fn lock() -> u32 {
+        let ticket = NEXT_TICKET.fetch_add(1, Ordering::Relaxed);
+        let mut spins = 0u32;
+        while NOW_SERVING.load(Ordering::Acquire) != ticket {
+            spins = spins.saturating_add(1);
+            spin_loop();
+        }
+        spins
+    }
+
+    #[inline]
+    unsafe fn try_lock() -> bool {
+        // The lock is free when the next ticket is the one being served. Taking that ticket
+        // makes it ours right away, without queueing behind other cores.
+        let serving = NOW_SERVING.load(Ordering::Acquire);
+        NEXT_TICKET
+            .compare_exchange(
+                serving,
+                serving.wrapping_add(1),
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            )
+            .is_ok()
+    }
+
+    #[inline]
+    unsafe fn unlock() {
+        // Only the lock holder writes `NOW_SERVING`, so this doesn't need a read-modify-write.
+        let next = NOW_SERVING.load(Ordering::Relaxed).wrapping_add(1);
+        NOW_SERVING.store(next, Ordering::Release);
+    }
+}
+
+impl<P: HardwareLock> Lock for HardwareSpinlock<P> {
+    #[inline]
+    unsafe fn lock() -> u32 {
+        let mut spins = 0u32;
+        while !P::try_lock() {
+            spins = spins.saturating_add(1);
+            spin_loop();
+        }
+        fence(Ordering::Acquire);
+        spins
+    }
+
+    #[inline]
+    unsafe fn try_lock() -> bool {
+        let taken = P::try_lock();
+        if taken {
+            fence(Ordering::Acquire);
+        }
+        taken
+    }
+
+    #[inline]
+    unsafe fn unlock() {
+        fence(Ordering::Release);
+        P::unlock();
+    }
+}
+
+#[cfg(target_has_atomic = "ptr")]
+unsafe impl<P: Platform> Impl for Spinlock<P> {
+    unsafe fn acquire() -> RawRestoreState {
+        acquire::<P, Ticket>()
+    }
+
+    unsafe fn try_acquire() -> Option<RawRestoreState> {
+        try_acquire::<P, Ticket>()
+    }
+
+    unsafe fn release(restore_state: RawRestoreState) {
+        release::<P, Ticket>(restore_state)
+    }
+}
+
+unsafe impl<P: Platform + HardwareLock> Impl for HardwareSpinlock<P> {
+    unsafe fn acquire() -> RawRestoreState {
+        acquire::<P, Self>()
+    }
+
+    unsafe fn try_acquire() -> Option<RawRestoreState> {
+        try_acquire::<P, Self>()
+    }
+
+    unsafe fn release(restore_state: RawRestoreState) {
+        release::<P, Self>(restore_state)
+    }
+}
+
+#[inline]
+unsafe fn acquire<P: Platform, L: Lock>() -> RawRestoreState {
+    let mut restore_state = 0;
+    if P::disable_interrupts() {
+        restore_state |= INTERRUPTS_ENABLED;
+    }
+
+    // Only the current core ever stores its own ID, and it can't be interrupted now,
+    // so seeing it here means the lock is held by an outer critical section.
+    let core = P::core_id();
+    if OWNER.load(Ordering::Relaxed) == core {
+        return restore_state | NESTED;
+    }
+
+    if P::ON_CONTENTION == OnContention::Panic {
+        if !try_lock::<L>(core) {
+            contended::<P>(restore_state);
+        }
+        return restore_state;
+    }
+
+    let _spins = L::lock();
+    OWNER.store(core, Ordering::Relaxed);
+    #[cfg(feature = "metrics")]
+    crate::metrics::on_spinlock_acquired(_spins);
+
+    restore_state
+}
+
+#[inline]
+unsafe fn try_acquire<P: Platform, L: Lock>() -> Option<RawRestoreState> {
+    if P::ON_CONTENTION == OnContention::Spin {
+        return Some(acquire::<P, L>());
+    }
+
+    let mut restore_state = 0;
+    if P::disable_interrupts() {
+        restore_state |= INTERRUPTS_ENABLED;
+    }
+
+    let core = P::core_id();
+    if OWNER.load(Ordering::Relaxed) == core {
+        return Some(restore_state | NESTED);
+    }
+
+    if try_lock::<L>(core) {
+        return Some(restore_state);
+    }
+    if P::ON_CONTENTION == OnContention::Panic {
+        contended::<P>(restore_state);
+    }
+    if restore_state & INTERRUPTS_ENABLED != 0 {
+        P::enable_interrupts();
+    }
+    None
+}
+
+#[inline]
+unsafe fn release<P: Platform, L: Lock>(restore_state: RawRestoreState) {
+    if restore_state & NESTED == 0 {
+        OWNER.store(NO_OWNER, Ordering::Relaxed);
+        L::unlock();
+    }
+
+    if restore_state & INTERRUPTS_ENABLED != 0 {
+        P::enable_interrupts();
+    }
+}
+
+/// Takes the lock without spinning, returning whether it was taken.
+///
+/// # Safety
+///
+/// Interrupts must be disabled on the current core, and it must not hold the lock.
+#[inline]
+unsafe fn try_lock<L: Lock>(core: usize) -> bool {
+    let taken = L::try_lock();
+    if taken {
+        OWNER.store(core, Ordering::Relaxed);
+        #[cfg(feature = "metrics")]
+        crate::metrics::on_spinlock_acquired(0);
+    }
+    taken
+}
+
+/// Handles contention with [`OnContention::Panic`].
+#[cold]
+#[inline(never)]
+unsafe fn contended<P: Platform>(restore_state: RawRestoreState) -> ! {
+    if restore_state & INTERRUPTS_ENABLED != 0 {
+        P::enable_interrupts();
+    }
+    panic!("critical section held by another core");
+}
@@ -1,5 +1,7 @@
-use super::CriticalSection;
-use core::cell::{Ref, RefCell, RefMut, UnsafeCell};
+use super::{CriticalSection, CsRead, CsWrite};
+use core::cell::{BorrowError, BorrowMutError, Cell, Ref, RefCell, RefMut, UnsafeCell};
+use core::fmt;
+use core::ops::Deref;
 
 /// A mutex based on critical sections.
 ///
@@ -29,25 +31,40 @@ use core::cell::{Ref, RefCell, RefMut, UnsafeCell};
 /// To reduce verbosity when using `Mutex<RefCell<T>>`, we reimplement some of
 /// `RefCell`'s methods on it directly.
 ///
-/// ```
-/// # use critical_section::{CriticalSection, Mutex};
+/// ```no_run
+/// # use critical_section::Mutex;
 /// # use std::cell::RefCell;
 ///
 /// static FOO: Mutex<RefCell<i32>> = Mutex::new(RefCell::new(42));
 ///
 /// fn main() {
-///     let cs = unsafe { CriticalSection::new() };
-///     // Instead of calling this
-///     let _ = FOO.borrow(cs).take();
-///     // Call this
-///     let _ = FOO.take(cs);
-///     // `RefCell::borrow` and `RefCell::borrow_mut` are renamed to
-///     // `borrow_ref` and `borrow_ref_mut` to avoid name collisions
-///     let _: &mut i32 = &mut *FOO.borrow_ref_mut(cs);
+///     critical_section::with(|cs| {
+///         // Instead of calling this
+///         let _ = FOO.borrow(cs).take();
+///         // Call this
+///         let _ = FOO.take(cs);
+///         // `RefCell::borrow` and `RefCell::borrow_mut` are renamed to
+///         // `borrow_ref` and `borrow_ref_mut` to avoid name collisions
+///         let _: &mut i32 = &mut *FOO.borrow_ref_mut(cs);
+///     });
 /// }
 /// ```
 ///
+/// # Interoperability with `bare_metal::Mutex`
+///
+/// Crates from the critical-section 0.2 era use [`bare_metal::Mutex`] instead, which takes a
+/// `bare_metal::CriticalSection` token. With the `bare-metal` Cargo feature enabled, a
+/// [`CriticalSection`] can be converted into one with `.into()`, so a single call to
+/// [`with`](crate::with) can access both kinds of mutexes.
+///
+/// The other way around, a `bare_metal::CriticalSection` is accepted by the unsafe
+/// `borrow_bare_metal`, not by [`borrow`](Mutex::borrow): that token only proves that
+/// interrupts are disabled on the current core, which doesn't keep other cores out of this
+/// `Mutex` on multi-core systems, and doesn't prove that this crate's critical section is held
+/// either. The caller vouches that the two are the same, as on single-core systems.
+///
 /// [`std::sync::Mutex`]: https://doc.rust-lang.org/std/sync/struct.Mutex.html
+/// [`bare_metal::Mutex`]: https://docs.rs/bare-metal/1.0/bare_metal/struct.Mutex.html
 /// [interior mutability]: https://doc.rust-lang.org/reference/interior-mutability.html
 #[derive(Debug)]
 pub struct Mutex<T> {
@@ -74,17 +91,217 @@ impl<T> Mutex<T> {
         unsafe { &mut *self.inner.get() }
     }
 
+    /// Calls `f` with a mutable reference to the contained value when the mutex is already
+    /// uniquely borrowed.
+    ///
+    /// Like [`get_mut`](Self::get_mut), this does not enter a critical section, so it's free
+    /// to use during single-threaded initialization code that owns the mutex.
+    #[inline]
+    pub fn with_mut<R>(&mut self, f: impl FnOnce(&mut T) -> R) -> R {
+        f(self.get_mut())
+    }
+
     /// Unwraps the contained value, consuming the mutex.
     #[inline]
     pub fn into_inner(self) -> T {
         self.inner.into_inner()
     }
 
-    /// Borrows the data for the duration of the critical section.
+    /// Returns a raw pointer to the contained value.
+    ///
+    /// This does not require a critical section, since creating the pointer is safe.
+    /// Dereferencing it is only sound inside a critical section, or when no other context can
+    /// access the data, such as before interrupts are enabled.
+    ///
+    /// Since this is a `const fn`, it can be used to initialize other statics with a pointer to
+    /// the data, for example for DMA descriptors:
+    ///
+    /// ```
+    /// use critical_section::Mutex;
+    /// use std::cell::Cell;
+    ///
+    /// static BUFFER: Mutex<Cell<[u8; 16]>> = Mutex::new(Cell::new([0; 16]));
+    ///
+    /// struct Descriptor(*mut Cell<[u8; 16]>);
+    /// unsafe impl Sync for Descriptor {}
+    ///
+    /// static DESCRIPTOR: Descriptor = Descriptor(BUFFER.as_ptr());
+    /// # assert_eq!(DESCRIPTOR.0, BUFFER.as_ptr());
+    /// ```
     #[inline]
+    pub const fn as_ptr(&self) -> *mut T {
+        self.inner.get()
+    }
+
+    /// Borrows the data for the duration of the critical section.
+    #[inline(always)]
     pub fn borrow<'cs>(&'cs self, _cs: CriticalSection<'cs>) -> &'cs T {
         unsafe { &*self.inner.get() }
     }
+
+    /// Borrows the data with a [`CsRead`] capability.
+    ///
+    /// This is [`borrow`](Self::borrow) for code that was handed a read capability instead of
+    /// the full token.
+    #[inline(always)]
+    pub fn borrow_read<'cs>(&'cs self, _cs: CsRead<'cs>) -> &'cs T {
+        unsafe { &*self.inner.get() }
+    }
+
+    /// Borrows the data with a [`bare_metal::CriticalSection`] token, for code from the
+    /// critical-section 0.2 era that only has one of those.
+    ///
+    /// ```no_run
+    /// use critical_section::Mutex;
+    /// use std::cell::Cell;
+    ///
+    /// static COUNTER: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+    ///
+    /// // Called by a driver written against `bare_metal::Mutex`.
+    /// fn on_tick(cs: bare_metal::CriticalSection) {
+    ///     // Safety: single-core system, where the critical section only disables interrupts.
+    ///     let counter = unsafe { COUNTER.borrow_bare_metal(cs) };
+    ///     counter.set(counter.get() + 1);
+    /// }
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// See [`CriticalSection::upgrade`], which this converts the token with.
+    ///
+    /// [`bare_metal::CriticalSection`]: https://docs.rs/bare-metal/1.0/bare_metal/struct.CriticalSection.html
+    #[cfg(feature = "bare-metal")]
+    #[inline(always)]
+    pub unsafe fn borrow_bare_metal<'cs>(
+        &'cs self,
+        cs: bare_metal::CriticalSection<'cs>,
+    ) -> &'cs T {
+        self.borrow(CriticalSection::upgrade(cs))
+    }
+
+    /// Execute closure `f` with the data, in a critical section entered just for it.
+    ///
+    /// This is a shorthand for `critical_section::with(|cs| f(mutex.borrow(cs)))`, for call
+    /// sites that only access a single mutex. To access several mutexes consistently, enter
+    /// one critical section and use [`borrow`](Self::borrow) instead.
+    ///
+    /// ```no_run
+    /// use critical_section::Mutex;
+    /// use std::cell::Cell;
+    ///
+    /// static COUNTER: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+    ///
+    /// COUNTER.lock(|counter| counter.set(counter.get() + 1));
+    /// ```
+    #[inline]
+    #[cfg_attr(
+        any(feature = "debug", feature = "defmt-trace", feature = "log-trace"),
+        track_caller
+    )]
+    pub fn lock<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        crate::with(|cs| f(self.borrow(cs)))
+    }
+
+    /// Borrows a part of the data for the duration of the critical section.
+    ///
+    /// This lets drivers hand out a narrow view of shared state, such as a single field,
+    /// without exposing the whole struct. The returned [`CsRef`] carries the critical section
+    /// token along, so it can be projected further or used to borrow other mutexes.
+    ///
+    /// ```no_run
+    /// use critical_section::{CsRef, Mutex};
+    /// use std::cell::Cell;
+    ///
+    /// struct Driver {
+    ///     config: u32,
+    ///     rx_count: Cell<u32>,
+    /// }
+    ///
+    /// static DRIVER: Mutex<Driver> = Mutex::new(Driver {
+    ///     config: 0,
+    ///     rx_count: Cell::new(0),
+    /// });
+    ///
+    /// fn rx_count<'cs>(cs: critical_section::CriticalSection<'cs>) -> CsRef<'cs, Cell<u32>> {
+    ///     DRIVER.borrow_map(cs, |driver| &driver.rx_count)
+    /// }
+    ///
+    /// critical_section::with(|cs| rx_count(cs).set(1));
+    /// ```
+    #[inline]
+    pub fn borrow_map<'cs, U: ?Sized>(
+        &'cs self,
+        cs: CriticalSection<'cs>,
+        f: impl FnOnce(&T) -> &U,
+    ) -> CsRef<'cs, U> {
+        CsRef {
+            value: f(self.borrow(cs)),
+            cs,
+        }
+    }
+}
+
+/// A reference to a part of the data of a [`Mutex`], valid for the duration of a critical
+/// section.
+///
+/// Created by [`Mutex::borrow_map`]. Like [`Ref`], the methods are associated functions, so
+/// they don't shadow methods of the data.
+pub struct CsRef<'cs, T: ?Sized> {
+    value: &'cs T,
+    cs: CriticalSection<'cs>,
+}
+
+impl<'cs, T: ?Sized> CsRef<'cs, T> {
+    /// Projects the reference further, to a part of the borrowed data.
+    #[inline]
+    pub fn map<U: ?Sized>(this: Self, f: impl FnOnce(&T) -> &U) -> CsRef<'cs, U> {
+        CsRef {
+            value: f(this.value),
+            cs: this.cs,
+        }
+    }
+
+    /// Returns the underlying reference, bound to the critical section.
+    #[inline]
+    pub fn get(this: Self) -> &'cs T {
+        this.value
+    }
+
+    /// Returns the critical section token the reference is bound to.
+    #[inline]
+    pub fn token(this: &Self) -> CriticalSection<'cs> {
+        this.cs
+    }
+}
+
+impl<T: ?Sized> Clone for CsRef<'_, T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: ?Sized> Copy for CsRef<'_, T> {}
+
+impl<T: ?Sized> Deref for CsRef<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        self.value
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for CsRef<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.value.fmt(f)
+    }
+}
+
+impl<T: ?Sized + fmt::Display> fmt::Display for CsRef<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.value.fmt(f)
+    }
 }
 
 impl<T> Mutex<RefCell<T>> {
@@ -146,6 +363,82 @@ impl<T> Mutex<RefCell<T>> {
     pub fn borrow_ref_mut<'cs>(&'cs self, cs: CriticalSection<'cs>) -> RefMut<'cs, T> {
         self.borrow(cs).borrow_mut()
     }
+
+    /// Borrow the data mutably with a [`CsWrite`] capability.
+    ///
+    /// This is [`borrow_ref_mut`](Self::borrow_ref_mut) for code that was handed a write
+    /// capability instead of the full token.
+    ///
+    /// # Panics
+    ///
+    /// This call could panic. See the documentation for [`RefCell::borrow_mut`]
+    /// for more details.
+    #[inline]
+    #[track_caller]
+    pub fn borrow_mut<'cs>(&'cs self, cs: CsWrite<'cs>) -> RefMut<'cs, T> {
+        self.borrow_read(cs.read()).borrow_mut()
+    }
+
+    /// Execute closure `f` with a mutable reference to the data, in a critical section entered
+    /// just for it.
+    ///
+    /// This is a shorthand for `critical_section::with(|cs| f(&mut mutex.borrow_ref_mut(cs)))`,
+    /// see [`lock`](Mutex::lock).
+    ///
+    /// ```no_run
+    /// use critical_section::Mutex;
+    /// use std::cell::RefCell;
+    ///
+    /// static LOG: Mutex<RefCell<Vec<u8>>> = Mutex::new(RefCell::new(Vec::new()));
+    ///
+    /// LOG.lock_mut(|log| log.push(1));
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if the data is already borrowed, such as when called from inside `f`. See
+    /// [`try_lock_mut`](Self::try_lock_mut) for a non-panicking variant.
+    #[inline]
+    #[cfg_attr(
+        any(feature = "debug", feature = "defmt-trace", feature = "log-trace"),
+        track_caller
+    )]
+    pub fn lock_mut<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        crate::with(|cs| f(&mut self.borrow_ref_mut(cs)))
+    }
+
+    /// Execute closure `f` with a mutable reference to the data, in a critical section entered
+    /// just for it, or return an error if the data is already borrowed.
+    #[inline]
+    #[cfg_attr(
+        any(feature = "debug", feature = "defmt-trace", feature = "log-trace"),
+        track_caller
+    )]
+    pub fn try_lock_mut<R>(&self, f: impl FnOnce(&mut T) -> R) -> Result<R, BorrowMutError> {
+        crate::with(|cs| self.try_borrow_ref_mut(cs).map(|mut data| f(&mut data)))
+    }
+
+    /// Borrow the data and call [`RefCell::try_borrow`]
+    ///
+    /// This is equivalent to `self.borrow(cs).try_borrow()`
+    #[inline]
+    pub fn try_borrow_ref<'cs>(
+        &'cs self,
+        cs: CriticalSection<'cs>,
+    ) -> Result<Ref<'cs, T>, BorrowError> {
+        self.borrow(cs).try_borrow()
+    }
+
+    /// Borrow the data and call [`RefCell::try_borrow_mut`]
+    ///
+    /// This is equivalent to `self.borrow(cs).try_borrow_mut()`
+    #[inline]
+    pub fn try_borrow_ref_mut<'cs>(
+        &'cs self,
+        cs: CriticalSection<'cs>,
+    ) -> Result<RefMut<'cs, T>, BorrowMutError> {
+        self.borrow(cs).try_borrow_mut()
+    }
 }
 
 impl<T: Default> Mutex<RefCell<T>> {
@@ -164,6 +457,170 @@ impl<T: Default> Mutex<RefCell<T>> {
     }
 }
 
+impl<T> Mutex<Cell<T>> {
+    /// Borrow the data and call [`Cell::set`]
+    ///
+    /// This is equivalent to `self.borrow(cs).set(value)`
+    #[inline]
+    pub fn store<'cs>(&'cs self, cs: CriticalSection<'cs>, value: T) {
+        self.borrow(cs).set(value)
+    }
+
+    /// Borrow the data and call [`Cell::replace`], returning the old value.
+    ///
+    /// This is equivalent to `self.borrow(cs).replace(value)`
+    #[inline]
+    pub fn swap<'cs>(&'cs self, cs: CriticalSection<'cs>, value: T) -> T {
+        self.borrow(cs).replace(value)
+    }
+}
+
+impl<T: Copy> Mutex<Cell<T>> {
+    /// Borrow the data and call [`Cell::get`]
+    ///
+    /// This is equivalent to `self.borrow(cs).get()`
+    #[inline]
+    pub fn load<'cs>(&'cs self, cs: impl Into<CsRead<'cs>>) -> T {
+        self.borrow_read(cs.into()).get()
+    }
+
+    /// Replace the data with the result of `f` called with the current value, returning the
+    /// new value.
+    ///
+    /// For `Copy` data shared with interrupt handlers, such as counters and flags, a
+    /// `Mutex<Cell<T>>` with these methods compiles to a plain load and store inside the
+    /// critical section, without the borrow flag of a `Mutex<RefCell<T>>`:
+    ///
+    /// ```no_run
+    /// use critical_section::{CriticalSection, Mutex};
+    /// use std::cell::Cell;
+    ///
+    /// static RX_BYTES: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+    ///
+    /// fn on_rx(cs: CriticalSection, len: u32) -> u32 {
+    ///     RX_BYTES.update(cs, |bytes| bytes.wrapping_add(len))
+    /// }
+    ///
+    /// // In the main loop, take the count.
+    /// let received = critical_section::with(|cs| RX_BYTES.swap(cs, 0));
+    /// # let _ = received;
+    /// ```
+    #[inline]
+    pub fn update<'cs>(&'cs self, cs: CriticalSection<'cs>, f: impl FnOnce(T) -> T) -> T {
+        let cell = self.borrow(cs);
+        let value = f(cell.get());
+        cell.set(value);
+        value
+    }
+}
+
+impl Mutex<()> {
+    /// Mutably borrow the data of several `Mutex<RefCell<_>>` for one call to `f`.
+    ///
+    /// `mutexes` is a tuple of up to 8 references to mutexes, and `f` takes one [`RefMut`]
+    /// for each, in the same order. This saves a `borrow_ref_mut` call and a binding per
+    /// mutex when an operation needs all of them together:
+    ///
+    /// ```no_run
+    /// use critical_section::{CriticalSection, Mutex};
+    /// use std::cell::RefCell;
+    ///
+    /// static TX_QUEUE: Mutex<RefCell<Vec<u8>>> = Mutex::new(RefCell::new(Vec::new()));
+    /// static TX_COUNT: Mutex<RefCell<u32>> = Mutex::new(RefCell::new(0));
+    /// static LAST_BYTE: Mutex<RefCell<Option<u8>>> = Mutex::new(RefCell::new(None));
+    ///
+    /// fn send(cs: CriticalSection, byte: u8) {
+    ///     Mutex::borrow_many(
+    ///         (&TX_QUEUE, &TX_COUNT, &LAST_BYTE),
+    ///         cs,
+    ///         |mut queue, mut count, mut last| {
+    ///             queue.push(byte);
+    ///             *count += 1;
+    ///             *last = Some(byte);
+    ///         },
+    ///     );
+    /// }
+    ///
+    /// critical_section::with(|cs| send(cs, 7));
+    /// ```
+    ///
+    /// This is only implemented for `Mutex<()>`, so it can be called as `Mutex::borrow_many`
+    /// without naming a type.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the same mutex is passed twice, and if one of the mutexes is already borrowed,
+    /// like [`borrow_ref_mut`](Mutex::borrow_ref_mut). Two mutexes of the same type can't be
+    /// told apart at compile time, so their addresses are compared at runtime.
+    #[inline]
+    #[cfg_attr(
+        any(feature = "debug", feature = "defmt-trace", feature = "log-trace"),
+        track_caller
+    )]
+    pub fn borrow_many<'cs, M, F, R>(mutexes: M, cs: CriticalSection<'cs>, f: F) -> R
+    where
+        M: BorrowMany<'cs, F, R>,
+    {
+        mutexes.borrow_many(cs, f)
+    }
+}
+
+/// Tuples of references to `Mutex<RefCell<_>>`, borrowed together by
+/// [`Mutex::borrow_many`].
+///
+/// This is implemented for tuples of up to 8 mutexes, with `F` taking a [`RefMut`] for each.
+pub trait BorrowMany<'cs, F, R> {
+    /// Mutably borrow the data of each mutex, and call `f` with them.
+    fn borrow_many(self, cs: CriticalSection<'cs>, f: F) -> R;
+}
+
+macro_rules! borrow_many {
+    ($($t: ident $m: ident),+) => {
+        impl<'cs, $($t,)+ F, R> BorrowMany<'cs, F, R> for ($(&'cs Mutex<RefCell<$t>>,)+)
+        where
+            F: FnOnce($(RefMut<'cs, $t>),+) -> R,
+        {
+            #[inline]
+            #[cfg_attr(
+                any(feature = "debug", feature = "defmt-trace", feature = "log-trace"),
+                track_caller
+            )]
+            fn borrow_many(self, cs: CriticalSection<'cs>, f: F) -> R {
+                let ($($m,)+) = self;
+                let addresses = [$($m as *const _ as *const ()),+];
+                for (i, address) in addresses.iter().enumerate() {
+                    if addresses[..i].contains(address) {
+                        panic!("Mutex::borrow_many called with the same mutex twice");
+                    }
+                }
+                f($($m.borrow_ref_mut(cs)),+)
+            }
+        }
+    };
+}
+
+borrow_many!(A a);
+borrow_many!(A a, B b);
+borrow_many!(A a, B b, C c);
+borrow_many!(A a, B b, C c, D d);
+borrow_many!(A a, B b, C c, D d, E e);
+borrow_many!(A a, B b, C c, D d, E e, G g);
+borrow_many!(A a, B b, C c, D d, E e, G g, H h);
+borrow_many!(A a, B b, C c, D d, E e, G g, H h, I i);
+
+/// A [`Mutex`] providing interior mutability through a [`RefCell`].
+///
+/// This is the closest analogy to `std::sync::Mutex`. See the [`Mutex`] docs for the helper
+/// methods available on it.
+///
+/// ```
+/// use critical_section::BlockingMutex;
+/// use std::cell::RefCell;
+///
+/// static FOO: BlockingMutex<Vec<u8>> = BlockingMutex::new(RefCell::new(Vec::new()));
+/// ```
+pub type BlockingMutex<T> = Mutex<RefCell<T>>;
+
 // NOTE A `Mutex` can be used as a channel so the protected data must be `Send`
 // to prevent sending non-Sendable stuff (e.g. access tokens) across different
 // threads.
@@ -178,3 +635,14 @@ unsafe impl<T> Sync for Mutex<T> where T: Send {}
 #[allow(dead_code)]
 #[doc(hidden)]
 const GH_6: () = ();
+
+/// ``` compile_fail
+/// use critical_section::{CsRef, Mutex};
+///
+/// static FOO: Mutex<(u32, u32)> = Mutex::new((0, 0));
+///
+/// let leaked: CsRef<u32> = critical_section::with(|cs| FOO.borrow_map(cs, |foo| &foo.0));
+/// ```
+#[allow(dead_code)]
+#[doc(hidden)]
+const BORROW_MAP_ESCAPE: () = ();
@@ -0,0 +1,208 @@
+use core::cell::{Cell, UnsafeCell};
+use core::fmt;
+use core::ops::Deref;
+
+use super::{with, CriticalSection};
+
+/// A cell which can be written to only once, synchronized by the global critical section.
+///
+/// Unlike [`core::cell::OnceCell`], this is [`Sync`], so it can be put in a `static` and
+/// shared between threads and interrupt handlers. Unlike `once_cell::sync::OnceCell`, it
+/// doesn't need atomics, so it works on targets such as AVR and MSP430.
+///
+/// The initialization closure runs inside the critical section, so it should be short.
+///
+/// ```no_run
+/// use critical_section::OnceCell;
+///
+/// static CONFIG: OnceCell<u32> = OnceCell::new();
+///
+/// assert_eq!(CONFIG.get(), None);
+/// assert_eq!(*CONFIG.get_or_init(|| 42), 42);
+/// assert_eq!(CONFIG.set(1), Err(1));
+/// assert_eq!(CONFIG.get(), Some(&42));
+/// ```
+pub struct OnceCell<T> {
+    value: UnsafeCell<Option<T>>,
+    initializing: Cell<bool>,
+}
+
+// NOTE `&T` is handed out to every context sharing the cell, so `T` must be `Sync`, and it
+// might be initialized in one context and dropped in another, so `T` must be `Send`.
+unsafe impl<T: Send + Sync> Sync for OnceCell<T> {}
+
+impl<T> OnceCell<T> {
+    /// Creates a new empty cell.
+    #[inline]
+    pub const fn new() -> Self {
+        OnceCell {
+            value: UnsafeCell::new(None),
+            initializing: Cell::new(false),
+        }
+    }
+
+    /// Gets a reference to the contained value, or `None` if the cell is empty.
+    #[inline]
+    pub fn get(&self) -> Option<&T> {
+        with(|cs| self.get_in(cs))
+    }
+
+    /// Gets a reference to the contained value, from inside a critical section.
+    #[inline]
+    pub fn get_in(&self, _cs: CriticalSection) -> Option<&T> {
+        // Once set, the value is never changed through `&self`, so the reference stays valid
+        // after the critical section ends.
+        unsafe { (*self.value.get()).as_ref() }
+    }
+
+    /// Sets the contents of the cell to `value`.
+    ///
+    /// Returns `Err(value)` if the cell was already initialized.
+    #[inline]
+    pub fn set(&self, value: T) -> Result<(), T> {
+        with(|cs| {
+            if self.get_in(cs).is_some() || self.initializing.get() {
+                return Err(value);
+            }
+            unsafe { *self.value.get() = Some(value) };
+            Ok(())
+        })
+    }
+
+    /// Gets the contents of the cell, initializing it with `f` if the cell was empty.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `f` tries to initialize the same cell. If `f` panics, the cell stays
+    /// uninitialized.
+    #[inline]
+    #[track_caller]
+    pub fn get_or_init(&self, f: impl FnOnce() -> T) -> &T {
+        with(|cs| {
+            if let Some(value) = self.get_in(cs) {
+                return value;
+            }
+            assert!(
+                !self.initializing.replace(true),
+                "reentrant initialization of OnceCell"
+            );
+
+            struct Reset<'a>(&'a Cell<bool>);
+            impl Drop for Reset<'_> {
+                #[inline]
+                fn drop(&mut self) {
+                    self.0.set(false);
+                }
+            }
+
+            let reset = Reset(&self.initializing);
+            let value = f();
+            drop(reset);
+
+            unsafe { (*self.value.get()).get_or_insert(value) }
+        })
+    }
+
+    /// Gets a mutable reference to the contained value when the cell is already uniquely
+    /// borrowed.
+    #[inline]
+    pub fn get_mut(&mut self) -> Option<&mut T> {
+        self.value.get_mut().as_mut()
+    }
+
+    /// Takes the value out of the cell, leaving it empty.
+    #[inline]
+    pub fn take(&mut self) -> Option<T> {
+        self.value.get_mut().take()
+    }
+
+    /// Unwraps the contained value, consuming the cell.
+    #[inline]
+    pub fn into_inner(self) -> Option<T> {
+        self.value.into_inner()
+    }
+}
+
+impl<T> Default for OnceCell<T> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for OnceCell<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("OnceCell").field(&self.get()).finish()
+    }
+}
+
+impl<T> From<T> for OnceCell<T> {
+    #[inline]
+    fn from(value: T) -> Self {
+        OnceCell {
+            value: UnsafeCell::new(Some(value)),
+            initializing: Cell::new(false),
+        }
+    }
+}
+
+/// A value which is initialized on first access, synchronized by the global critical section.
+///
+/// This is the [`Sync`] counterpart of [`core::cell::LazyCell`], built on [`OnceCell`]. The
+/// initialization function runs inside the critical section.
+///
+/// ```no_run
+/// use critical_section::LazyCell;
+///
+/// static TABLE: LazyCell<[u8; 4]> = LazyCell::new(|| [1, 2, 4, 8]);
+///
+/// assert_eq!(TABLE[3], 8);
+/// ```
+pub struct LazyCell<T, F = fn() -> T> {
+    cell: OnceCell<T>,
+    init: Cell<Option<F>>,
+}
+
+// NOTE `init` is only accessed inside the critical section, and might run in any context.
+unsafe impl<T: Send + Sync, F: Send> Sync for LazyCell<T, F> {}
+
+impl<T, F: FnOnce() -> T> LazyCell<T, F> {
+    /// Creates a new lazy value with the given initialization function.
+    #[inline]
+    pub const fn new(f: F) -> Self {
+        LazyCell {
+            cell: OnceCell::new(),
+            init: Cell::new(Some(f)),
+        }
+    }
+
+    /// Forces the evaluation of this lazy value and returns a reference to the result.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the initialization function panicked during a previous evaluation, or tries
+    /// to evaluate the same value.
+    #[inline]
+    #[track_caller]
+    pub fn force(this: &Self) -> &T {
+        this.cell.get_or_init(|| match this.init.take() {
+            Some(f) => f(),
+            None => panic!("LazyCell instance has previously been poisoned"),
+        })
+    }
+}
+
+impl<T, F: FnOnce() -> T> Deref for LazyCell<T, F> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        LazyCell::force(self)
+    }
+}
+
+impl<T: fmt::Debug, F> fmt::Debug for LazyCell<T, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("LazyCell").field(&self.cell.get()).finish()
+    }
+}
@@ -0,0 +1,80 @@
+//! Releasing the critical section from a panic handler.
+
+use crate::state;
+
+/// Release all critical sections the current context is nested in, so a panic handler can
+/// use drivers that enter the critical section themselves.
+///
+/// A panic inside a critical section reaches the panic handler with the critical section
+/// still held. A UART driver or logger used to report the panic still works if it calls
+/// [`with`](crate::with), since that nests, but data it shares with interrupt handlers may
+/// never arrive, and on multi-core systems the other cores hang waiting for the critical
+/// section. This releases every nested critical section from the implementation, and the
+/// outermost one with its restore state, re-enabling interrupts if they were enabled before
+/// it, and resets the nesting depth to `0`. Implementations counting the nesting, such as
+/// FreeRTOS, are released down to zero too.
+///
+/// ```ignore
+/// #[panic_handler]
+/// fn panic(info: &core::panic::PanicInfo) -> ! {
+///     unsafe { critical_section::force_release_on_panic() };
+///     defmt::error!("{}", defmt::Display2Format(info));
+///     loop {}
+/// }
+/// ```
+///
+/// [`set_panic_handler!`](crate::set_panic_handler) defines such a panic handler.
+///
+/// If the current context is not in a critical section, this does nothing.
+///
+/// # Safety
+///
+/// The code that entered the critical sections must never resume. This must only be called
+/// from a panic handler that doesn't return, or from a panic hook with `panic = "abort"`, and
+/// not when the panic unwinds: the guards being dropped would release the critical section
+/// again.
+///
+/// Data protected by the critical section may have been left in an inconsistent state by the
+/// panicking code.
+#[cold]
+pub unsafe fn force_release_on_panic() {
+    let nested = crate::acquire_raw();
+    let depth = state::depth();
+    if depth == 0 {
+        crate::release_raw(nested);
+        return;
+    }
+    // Forget about the nested critical sections, and release every level from the
+    // implementation, including the one just acquired, as `without` does.
+    let outermost = state::outermost();
+    state::suspend();
+    for _ in 0..depth {
+        crate::release_raw(nested);
+    }
+    crate::release_raw(outermost);
+}
+
+/// Define a `#[panic_handler]` that releases the critical section before calling a function.
+///
+/// The function is called with the `&PanicInfo`, after
+/// [`force_release_on_panic`](crate::force_release_on_panic), and must not return. This is only
+/// usable in `#![no_std]` binaries.
+///
+/// ```ignore
+/// fn report_panic(info: &core::panic::PanicInfo) -> ! {
+///     defmt::error!("{}", defmt::Display2Format(info));
+///     loop {}
+/// }
+///
+/// critical_section::set_panic_handler!(report_panic);
+/// ```
+#[macro_export]
+macro_rules! set_panic_handler {
+    ($handler:expr $(,)?) => {
+        #[panic_handler]
+        fn __critical_section_panic(info: &::core::panic::PanicInfo) -> ! {
+            unsafe { $crate::force_release_on_panic() };
+            $handler(info)
+        }
+    };
+}
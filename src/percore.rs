@@ -0,0 +1,114 @@
+//! Per-core storage for multi-core critical section implementations.
+//!
+//! Implementations for multi-core systems often need to keep a small value for each core,
+//! such as the interrupt state to restore or the nesting depth. A single `static` shared by
+//! all cores is racy, since another core can overwrite it at any time. [`PerCore`] keeps one
+//! value per core, selected by a [`CoreId`] provider:
+//!
+//! ```no_run
+//! use core::cell::Cell;
+//! use critical_section::percore::{CoreId, PerCore};
+//!
+//! struct MyChip;
+//!
+//! unsafe impl CoreId for MyChip {
+//!     fn core_id() -> usize {
+//!         // Read the core ID register.
+//!         0
+//!     }
+//! }
+//!
+//! // Whether interrupts were enabled before the outermost critical section, for each core.
+//! static WAS_ENABLED: PerCore<bool, MyChip, 2> = PerCore::new([false; 2]);
+//!
+//! unsafe fn acquire() {
+//!     let was_enabled = disable_interrupts();
+//!     // ... take the cross-core lock.
+//!     // Interrupts are disabled on this core, so nothing else can access its value.
+//!     *WAS_ENABLED.current_mut() = was_enabled;
+//! }
+//! # unsafe fn disable_interrupts() -> bool { false }
+//! ```
+
+use core::cell::UnsafeCell;
+use core::marker::PhantomData;
+
+/// Provides the index of the current core for [`PerCore`].
+///
+/// # Safety
+///
+/// `core_id` must return a different value on each core, and the same value every time it's
+/// called on the same core.
+pub unsafe trait CoreId {
+    /// Returns the index of the current core.
+    fn core_id() -> usize;
+}
+
+/// One value of type `T` for each of the `N` cores, selected by the [`CoreId`] provider `C`.
+///
+/// See the [module docs](self).
+pub struct PerCore<T, C, const N: usize> {
+    values: UnsafeCell<[T; N]>,
+    _core_id: PhantomData<C>,
+}
+
+// NOTE Each value is only accessed from its own core, but might be initialized and dropped on
+// another one, so `T` must be `Send`.
+unsafe impl<T: Send, C, const N: usize> Sync for PerCore<T, C, N> {}
+
+impl<T, C, const N: usize> PerCore<T, C, N> {
+    /// Creates the storage with the initial value for each core.
+    #[inline]
+    pub const fn new(values: [T; N]) -> Self {
+        PerCore {
+            values: UnsafeCell::new(values),
+            _core_id: PhantomData,
+        }
+    }
+
+    /// Gets mutable references to the values of all cores when the storage is already
+    /// uniquely borrowed.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut [T; N] {
+        self.values.get_mut()
+    }
+
+    /// Unwraps the values of all cores, consuming the storage.
+    #[inline]
+    pub fn into_inner(self) -> [T; N] {
+        self.values.into_inner()
+    }
+}
+
+impl<T, C: CoreId, const N: usize> PerCore<T, C, N> {
+    /// Returns a raw pointer to the value of the current core.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the core ID is not below `N`.
+    #[inline]
+    #[track_caller]
+    pub fn current_ptr(&self) -> *mut T {
+        let core = C::core_id();
+        assert!(core < N, "core ID out of range");
+        unsafe { self.values.get().cast::<T>().add(core) }
+    }
+
+    /// Returns a mutable reference to the value of the current core.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the core ID is not below `N`.
+    ///
+    /// # Safety
+    ///
+    /// Nothing else on the current core may access the value while the reference is alive.
+    /// This is usually ensured by disabling interrupts on the current core first, and not
+    /// holding the reference across calls that might access the value again.
+    #[inline]
+    #[track_caller]
+    #[allow(clippy::mut_from_ref)]
+    pub unsafe fn current_mut(&self) -> &mut T {
+        &mut *self.current_ptr()
+    }
+}
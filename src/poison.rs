@@ -0,0 +1,88 @@
+use core::cell::Cell;
+use core::mem;
+
+use super::{enter, CriticalSection, Mutex};
+
+/// Set when a closure passed to [`with`](crate::with) or [`with_checked`] unwinds.
+static POISONED: Mutex<Cell<bool>> = Mutex::new(Cell::new(false));
+
+/// Error returned by [`with_checked`] when the critical section is poisoned.
+///
+/// The critical section is poisoned when a panic unwinds out of a closure passed to
+/// [`with`](crate::with) or [`with_checked`], so data protected by it might be left in
+/// an inconsistent state.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Poisoned;
+
+impl core::fmt::Display for Poisoned {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("critical section poisoned by a panic")
+    }
+}
+
+/// Poisons the critical section when dropped, unless forgotten.
+pub(crate) struct PoisonOnUnwind;
+
+impl Drop for PoisonOnUnwind {
+    #[inline]
+    fn drop(&mut self) {
+        // Only runs while unwinding out of the closure, which is still inside the
+        // critical section.
+        POISONED.borrow(unsafe { CriticalSection::new() }).set(true);
+    }
+}
+
+/// Run `f`, poisoning the critical section if it unwinds.
+#[inline]
+pub(crate) fn run<'cs, R>(
+    cs: CriticalSection<'cs>,
+    f: impl FnOnce(CriticalSection<'cs>) -> R,
+) -> R {
+    let bomb = PoisonOnUnwind;
+    let r = f(cs);
+    mem::forget(bomb);
+    r
+}
+
+/// Execute closure `f` in a critical section, unless the critical section is poisoned.
+///
+/// This is like [`with`](crate::with), but returns [`Poisoned`] instead of panicking when
+/// a previous closure panicked while holding the critical section.
+#[inline]
+#[cfg_attr(
+    any(feature = "debug", feature = "defmt-trace", feature = "log-trace"),
+    track_caller
+)]
+pub fn with_checked<R>(f: impl FnOnce(CriticalSection) -> R) -> Result<R, Poisoned> {
+    let guard = enter();
+    let cs = guard.token();
+    if POISONED.borrow(cs).get() {
+        return Err(Poisoned);
+    }
+    Ok(run(cs, f))
+}
+
+/// Returns whether the critical section is poisoned.
+#[inline]
+pub fn is_poisoned() -> bool {
+    let guard = enter();
+    POISONED.borrow(guard.token()).get()
+}
+
+/// Clears the poisoned state of the critical section.
+///
+/// Call this once the data protected by the critical section has been checked or reset.
+#[inline]
+pub fn clear_poison() {
+    let guard = enter();
+    POISONED.borrow(guard.token()).set(false);
+}
+
+/// Panics if the critical section is poisoned.
+#[inline]
+#[track_caller]
+pub(crate) fn check(cs: CriticalSection) {
+    if POISONED.borrow(cs).get() {
+        panic!("critical section poisoned by a panic");
+    }
+}
@@ -0,0 +1,203 @@
+//! Fixed-capacity object pool shared by interrupt handlers and threads.
+//!
+//! [`Pool`] hands out up to `N` values of type `T` from static storage, without a global
+//! allocator. This is meant for objects that are allocated in one context and freed in
+//! another, such as DMA descriptors or packet buffers passed from a receive interrupt to the
+//! main loop:
+//!
+//! ```no_run
+//! use critical_section::pool::{Pool, PoolBox};
+//! use critical_section::Signal;
+//!
+//! static BUFFERS: Pool<[u8; 64], 4> = Pool::new();
+//! static RECEIVED: Signal<PoolBox<'static, [u8; 64]>> = Signal::new();
+//!
+//! // In the receive interrupt handler: drop the packet if no buffer is free.
+//! fn on_receive(packet: &[u8; 64]) {
+//!     let buffer = critical_section::with(|cs| BUFFERS.alloc(cs, *packet));
+//!     if let Ok(buffer) = buffer {
+//!         RECEIVED.signal(buffer);
+//!     }
+//! }
+//!
+//! // In the main loop.
+//! fn process() {
+//!     if let Some(buffer) = RECEIVED.try_take() {
+//!         // ...
+//! #       let _ = &buffer;
+//!         // Dropping the buffer returns it to the pool.
+//!     }
+//! }
+//! ```
+//!
+//! Allocating and freeing only hold the critical section to update the free list. Values
+//! are moved in with [`Pool::alloc`], and dropped outside of the critical section when their
+//! [`PoolBox`] is dropped.
+
+use core::cell::{Cell, UnsafeCell};
+use core::mem::{self, ManuallyDrop, MaybeUninit};
+use core::ops::{Deref, DerefMut};
+use core::ptr::{self, NonNull};
+
+use super::{with, CriticalSection};
+
+/// A slot of the pool, holding either a value or the next free slot.
+union Slot<T> {
+    value: ManuallyDrop<T>,
+    next: *mut Slot<T>,
+}
+
+/// A fixed-capacity pool of up to `N` values of type `T`.
+///
+/// See the [module docs](crate::pool).
+pub struct Pool<T, const N: usize> {
+    slots: UnsafeCell<MaybeUninit<[Slot<T>; N]>>,
+    /// Number of slots that were ever allocated. The ones after them are free, and not in
+    /// the free list.
+    used: Cell<usize>,
+    /// Freed slots, linked through `Slot::next`.
+    free: Cell<*mut Slot<T>>,
+}
+
+// NOTE Values are moved from the allocating context to the freeing context, so `T` must be
+// `Send`. The free list is only accessed inside the critical section.
+unsafe impl<T: Send, const N: usize> Sync for Pool<T, N> {}
+
+impl<T, const N: usize> Pool<T, N> {
+    /// Creates a new pool, with all `N` slots free.
+    #[inline]
+    pub const fn new() -> Self {
+        Pool {
+            slots: UnsafeCell::new(MaybeUninit::uninit()),
+            used: Cell::new(0),
+            free: Cell::new(ptr::null_mut()),
+        }
+    }
+
+    /// Returns the number of values the pool can hold.
+    #[inline]
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Moves `value` into a free slot, or returns it in `Err` if all slots are allocated.
+    ///
+    /// ```no_run
+    /// use critical_section::pool::Pool;
+    ///
+    /// static DESCRIPTORS: Pool<u32, 1> = Pool::new();
+    ///
+    /// critical_section::with(|cs| {
+    ///     let descriptor = DESCRIPTORS.alloc(cs, 1).unwrap();
+    ///     assert!(DESCRIPTORS.alloc(cs, 2).is_err());
+    ///     descriptor.free(cs);
+    ///     DESCRIPTORS.alloc(cs, 3).unwrap().free(cs);
+    /// });
+    /// ```
+    #[inline]
+    pub fn alloc(&self, _cs: CriticalSection, value: T) -> Result<PoolBox<'_, T>, T> {
+        let slot = match NonNull::new(self.free.get()) {
+            Some(slot) => {
+                self.free.set(unsafe { slot.as_ref().next });
+                slot
+            }
+            None => {
+                let used = self.used.get();
+                if used == N {
+                    return Err(value);
+                }
+                self.used.set(used + 1);
+                let slot = self.slots.get().cast::<Slot<T>>().wrapping_add(used);
+                unsafe { NonNull::new_unchecked(slot) }
+            }
+        };
+        unsafe {
+            ptr::write(
+                slot.as_ptr(),
+                Slot {
+                    value: ManuallyDrop::new(value),
+                },
+            )
+        };
+        Ok(PoolBox {
+            slot,
+            free: &self.free,
+        })
+    }
+}
+
+impl<T, const N: usize> Default for Pool<T, N> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A value allocated from a [`Pool`], returned to it when dropped.
+///
+/// Dropping takes its own short critical section, after dropping the value. Use
+/// [`free`](Self::free) to return it from inside a critical section instead.
+pub struct PoolBox<'a, T> {
+    slot: NonNull<Slot<T>>,
+    free: &'a Cell<*mut Slot<T>>,
+}
+
+// NOTE The box owns its value, and the free list it returns the slot to is only accessed
+// inside the critical section.
+unsafe impl<T: Send> Send for PoolBox<'_, T> {}
+unsafe impl<T: Sync> Sync for PoolBox<'_, T> {}
+
+impl<T> PoolBox<'_, T> {
+    /// Drop the value, and return its slot to the pool from inside a critical section.
+    #[inline]
+    pub fn free(mut self, cs: CriticalSection) {
+        unsafe { ManuallyDrop::drop(&mut self.slot.as_mut().value) };
+        self.release(cs);
+        mem::forget(self);
+    }
+
+    /// Move the value out, and return its slot to the pool.
+    #[inline]
+    pub fn into_inner(mut self) -> T {
+        let value = unsafe { ManuallyDrop::take(&mut self.slot.as_mut().value) };
+        with(|cs| self.release(cs));
+        mem::forget(self);
+        value
+    }
+
+    /// Push the slot onto the free list. The value must be dropped or moved out already.
+    #[inline]
+    fn release(&self, _cs: CriticalSection) {
+        unsafe { (*self.slot.as_ptr()).next = self.free.get() };
+        self.free.set(self.slot.as_ptr());
+    }
+}
+
+impl<T> Deref for PoolBox<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        unsafe { &self.slot.as_ref().value }
+    }
+}
+
+impl<T> DerefMut for PoolBox<'_, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut self.slot.as_mut().value }
+    }
+}
+
+impl<T: core::fmt::Debug> core::fmt::Debug for PoolBox<'_, T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl<T> Drop for PoolBox<'_, T> {
+    fn drop(&mut self) {
+        unsafe { ManuallyDrop::drop(&mut self.slot.as_mut().value) };
+        with(|cs| self.release(cs));
+    }
+}
@@ -0,0 +1,103 @@
+use core::marker::PhantomData;
+
+use super::{RawRestoreState, RestoreState};
+
+/// Token for a critical section that only masks interrupts up to some priority.
+///
+/// Created by [`with_max_priority`]. Unlike a [`CriticalSection`](crate::CriticalSection),
+/// this does not guarantee exclusive access against higher priority interrupts, so it can't be
+/// used with [`Mutex`](crate::Mutex).
+#[derive(Clone, Copy, Debug)]
+pub struct PrioritySection<'cs> {
+    priority: u8,
+    _0: PhantomData<&'cs ()>,
+    _not_send: PhantomData<*mut ()>,
+}
+
+impl<'cs> PrioritySection<'cs> {
+    /// Returns the priority up to which interrupts are masked.
+    #[inline]
+    pub const fn priority(&self) -> u8 {
+        self.priority
+    }
+}
+
+/// Execute closure `f` with interrupts up to `priority` masked.
+///
+/// Interrupts with a logical priority greater than `priority` keep running, so this can be used
+/// to protect data shared with low priority interrupts without delaying high priority ones.
+/// How `priority` maps to hardware priority levels is up to the implementation.
+///
+/// Implementations that don't support masking by priority fall back to acquiring the full
+/// critical section, see [`Impl::acquire_priority`](crate::Impl::acquire_priority). It's then
+/// held like in [`with`](crate::with), and [`is_active`](crate::is_active) returns `true`.
+///
+/// Nesting is allowed, including with [`with`](crate::with).
+#[inline]
+pub fn with_max_priority<R>(priority: u8, f: impl FnOnce(PrioritySection) -> R) -> R {
+    struct Release(Masked);
+
+    impl Drop for Release {
+        #[inline]
+        fn drop(&mut self) {
+            match self.0 {
+                Masked::Priority(restore_state) => unsafe { release_priority(restore_state) },
+                Masked::Full(restore_state) => unsafe { crate::release(restore_state) },
+            }
+        }
+    }
+
+    let _release = Release(unsafe { acquire_priority(priority) });
+    f(PrioritySection {
+        priority,
+        _0: PhantomData,
+        _not_send: PhantomData,
+    })
+}
+
+/// How [`with_max_priority`] masked interrupts.
+#[derive(Clone, Copy)]
+enum Masked {
+    /// Up to the priority, by the implementation.
+    Priority(RestoreState),
+    /// All of them, with the full critical section.
+    Full(RestoreState),
+}
+
+#[inline]
+#[allow(clippy::unit_arg)] // `RawRestoreState` is `()` by default.
+unsafe fn acquire_priority(priority: u8) -> Masked {
+    extern "Rust" {
+        fn _critical_section_1_0_acquire_priority(priority: u8) -> Option<RawRestoreState>;
+    }
+
+    #[cfg(feature = "emergency")]
+    if crate::in_emergency_mode() {
+        return Masked::Priority(RestoreState::invalid());
+    }
+
+    match _critical_section_1_0_acquire_priority(priority) {
+        Some(restore_state) => {
+            crate::barrier();
+            Masked::Priority(RestoreState(restore_state))
+        }
+        None => Masked::Full(crate::acquire()),
+    }
+}
+
+#[inline]
+#[allow(clippy::unit_arg)] // `RawRestoreState` is `()` by default.
+unsafe fn release_priority(restore_state: RestoreState) {
+    extern "Rust" {
+        fn _critical_section_1_0_release_priority(restore_state: RawRestoreState);
+    }
+
+    crate::barrier();
+
+    #[cfg(feature = "emergency")]
+    if crate::in_emergency_mode() {
+        return;
+    }
+
+    _critical_section_1_0_release_priority(restore_state.0)
+}
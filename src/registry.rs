@@ -0,0 +1,253 @@
+//! Registry of critical-section-protected objects, for debuggers and postmortem analysis.
+//!
+//! With the `debug-registry` Cargo feature enabled, [`Mutex`]es can be registered under a
+//! name with [`register!`](macro@crate::registry::register). Registered mutexes are kept in a linked
+//! list, which can be enumerated at runtime with [`iter`], or by a debugger after a hard fault:
+//!
+//! ```no_run
+//! use core::cell::{Cell, RefCell};
+//! use critical_section::{registry, Mutex};
+//!
+//! static TICKS: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+//! static BUFFER: Mutex<RefCell<[u8; 16]>> = Mutex::new(RefCell::new([0; 16]));
+//!
+//! // Early in `main`.
+//! registry::register!(TICKS);
+//! registry::register!(BUFFER: RefCell);
+//!
+//! for entry in registry::iter() {
+//!     critical_section::with(|cs| {
+//!         // Prints `Some(true)` while `BUFFER` is borrowed, and `None` for `TICKS`, whose
+//!         // borrow state isn't tracked.
+//!         println!("{} at {:p}: {:?}", entry.name(), entry.address(), entry.is_borrowed(cs));
+//!     });
+//! }
+//! ```
+//!
+//! A `Mutex` can't register itself when it is created, as `Mutex::new` is a `const fn`: only
+//! mutexes that were registered by the time of the fault are listed.
+//!
+//! # Debugger access
+//!
+//! The list is rooted at the `CRITICAL_SECTION_REGISTRY` symbol, a pointer to the most recently
+//! registered [`Entry`], or null. Entries are `#[repr(C)]`, and made of pointer-sized words
+//! except `line`, so debugger scripts can walk the list by reading memory, without debug info
+//! for this crate:
+//!
+//! | Word | Field                                                |
+//! |------|------------------------------------------------------|
+//! | 0    | Pointer to the next entry, or null                   |
+//! | 1, 2 | Pointer to and length of the UTF-8 name              |
+//! | 3, 4 | Address and size of the mutex                        |
+//! | 5, 6 | Pointer to and length of the UTF-8 source file name  |
+//! | 7    | Source line of the registration, as a `u32`          |
+//!
+//! `scripts/gdb_registry.py` in the crate repository adds a `cs-registry` command to GDB that
+//! does this. Whether a `Mutex<RefCell<T>>` is borrowed can only be checked from Rust, as the
+//! layout of `RefCell` is unspecified.
+
+use core::cell::{Cell, RefCell};
+use core::{mem, slice, str};
+
+use super::{with, CriticalSection, Mutex};
+
+/// Head of the list, the most recently registered entry.
+#[no_mangle]
+static CRITICAL_SECTION_REGISTRY: Mutex<Cell<Option<&'static Entry>>> = Mutex::new(Cell::new(None));
+
+type IsBorrowed = unsafe fn(address: *const (), cs: CriticalSection) -> bool;
+
+/// A registered [`Mutex`], created by [`register!`](macro@crate::registry::register).
+///
+/// See the [module docs](self) for the memory layout.
+#[repr(C)]
+#[derive(Debug)]
+pub struct Entry {
+    next: Cell<Option<&'static Entry>>,
+    name: *const u8,
+    name_len: usize,
+    address: *const (),
+    size: usize,
+    file: *const u8,
+    file_len: usize,
+    line: u32,
+    is_borrowed: Option<IsBorrowed>,
+    registered: Cell<bool>,
+}
+
+// NOTE `next` and `registered` are only written in a critical section, and `next` only before
+// the entry is added to the list. The pointers are all to `'static` data.
+unsafe impl Sync for Entry {}
+
+impl Entry {
+    /// Creates an entry for `mutex`, whose borrow state isn't tracked.
+    ///
+    /// Prefer [`register!`](macro@crate::registry::register), which fills in the name and location.
+    #[inline]
+    pub const fn new<T>(
+        name: &'static str,
+        mutex: &'static Mutex<T>,
+        file: &'static str,
+        line: u32,
+    ) -> Self {
+        Entry {
+            next: Cell::new(None),
+            name: name.as_ptr(),
+            name_len: name.len(),
+            address: mutex as *const Mutex<T> as *const (),
+            size: mem::size_of::<T>(),
+            file: file.as_ptr(),
+            file_len: file.len(),
+            line,
+            is_borrowed: None,
+            registered: Cell::new(false),
+        }
+    }
+
+    /// Creates an entry for `mutex`, which reports whether the `RefCell` is borrowed.
+    #[inline]
+    pub const fn new_ref_cell<T>(
+        name: &'static str,
+        mutex: &'static Mutex<RefCell<T>>,
+        file: &'static str,
+        line: u32,
+    ) -> Self {
+        let mut entry = Self::new(name, mutex, file, line);
+        entry.is_borrowed = Some(ref_cell_is_borrowed::<T> as IsBorrowed);
+        entry
+    }
+
+    /// Name of the mutex.
+    #[inline]
+    pub fn name(&self) -> &'static str {
+        unsafe { str::from_utf8_unchecked(slice::from_raw_parts(self.name, self.name_len)) }
+    }
+
+    /// Address of the mutex.
+    #[inline]
+    pub fn address(&self) -> *const () {
+        self.address
+    }
+
+    /// Size of the data protected by the mutex, in bytes.
+    #[inline]
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Source file where the mutex was registered.
+    #[inline]
+    pub fn file(&self) -> &'static str {
+        unsafe { str::from_utf8_unchecked(slice::from_raw_parts(self.file, self.file_len)) }
+    }
+
+    /// Source line where the mutex was registered.
+    #[inline]
+    pub fn line(&self) -> u32 {
+        self.line
+    }
+
+    /// Whether the mutex is currently borrowed, or `None` if that isn't tracked.
+    ///
+    /// Only a `Mutex<RefCell<T>>` registered with `register!(NAME: RefCell)` tracks this.
+    #[inline]
+    pub fn is_borrowed(&self, cs: CriticalSection) -> Option<bool> {
+        self.is_borrowed
+            .map(|is_borrowed| unsafe { is_borrowed(self.address, cs) })
+    }
+}
+
+/// Serialized as a struct with the `name`, `address` as an integer, `size`, `file` and `line`.
+/// The borrow state needs a critical section, so it isn't included.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Entry {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut entry = serializer.serialize_struct("Entry", 5)?;
+        entry.serialize_field("name", self.name())?;
+        entry.serialize_field("address", &(self.address as usize))?;
+        entry.serialize_field("size", &self.size)?;
+        entry.serialize_field("file", self.file())?;
+        entry.serialize_field("line", &self.line)?;
+        entry.end()
+    }
+}
+
+unsafe fn ref_cell_is_borrowed<T>(address: *const (), cs: CriticalSection) -> bool {
+    let mutex = &*(address as *const Mutex<RefCell<T>>);
+    mutex.borrow(cs).try_borrow_mut().is_err()
+}
+
+/// Add `entry` to the registry.
+///
+/// Registering the same entry again does nothing. Usually called through
+/// [`register!`](macro@crate::registry::register).
+pub fn register(entry: &'static Entry) {
+    with(|cs| {
+        if entry.registered.replace(true) {
+            return;
+        }
+        let head = CRITICAL_SECTION_REGISTRY.borrow(cs);
+        entry.next.set(head.get());
+        head.set(Some(entry));
+    });
+}
+
+/// Iterate over the registered mutexes, most recently registered first.
+///
+/// Mutexes registered while iterating are not included.
+pub fn iter() -> Iter {
+    Iter {
+        next: with(|cs| CRITICAL_SECTION_REGISTRY.borrow(cs).get()),
+    }
+}
+
+/// Iterator over the registered mutexes, returned by [`iter`].
+#[derive(Clone, Debug)]
+pub struct Iter {
+    next: Option<&'static Entry>,
+}
+
+impl Iterator for Iter {
+    type Item = &'static Entry;
+
+    #[inline]
+    fn next(&mut self) -> Option<&'static Entry> {
+        let entry = self.next?;
+        // Entries are only added at the head, the rest of the list never changes.
+        self.next = entry.next.get();
+        Some(entry)
+    }
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __registry_register {
+    ($mutex:path) => {{
+        static ENTRY: $crate::registry::Entry =
+            $crate::registry::Entry::new(stringify!($mutex), &$mutex, file!(), line!());
+        $crate::registry::register(&ENTRY)
+    }};
+    ($mutex:path: RefCell) => {{
+        static ENTRY: $crate::registry::Entry =
+            $crate::registry::Entry::new_ref_cell(stringify!($mutex), &$mutex, file!(), line!());
+        $crate::registry::register(&ENTRY)
+    }};
+}
+
+/// Register a `static` [`Mutex`] under its name, with the location of the call.
+///
+/// Use `register!(NAME: RefCell)` for a `Mutex<RefCell<T>>`, to also track whether it is
+/// borrowed. Each call site registers its mutex once, however often it runs.
+///
+/// ```no_run
+/// use core::cell::Cell;
+/// use critical_section::Mutex;
+///
+/// static TICKS: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+///
+/// critical_section::registry::register!(TICKS);
+/// ```
+#[doc(inline)]
+pub use crate::__registry_register as register;
@@ -0,0 +1,26 @@
+use core::arch::asm;
+
+/// Machine interrupt enable bit of `mstatus`.
+const MSTATUS_MIE: usize = 1 << 3;
+
+struct RiscvSingleHartCriticalSection;
+crate::set_impl!(RiscvSingleHartCriticalSection);
+
+unsafe impl crate::Impl for RiscvSingleHartCriticalSection {
+    #[inline(always)]
+    unsafe fn acquire() -> bool {
+        let mstatus: usize;
+        // Clears MIE and reads the previous `mstatus` in a single instruction, so this can't be
+        // interrupted in between. No `nomem`, so this also acts as a compiler fence.
+        asm!("csrrci {}, mstatus, {mie}", out(reg) mstatus, mie = const MSTATUS_MIE, options(nostack));
+        mstatus & MSTATUS_MIE != 0
+    }
+
+    #[inline(always)]
+    unsafe fn release(was_enabled: bool) {
+        // Only re-enable interrupts if they were enabled before the critical section.
+        if was_enabled {
+            asm!("csrsi mstatus, {mie}", mie = const MSTATUS_MIE, options(nostack));
+        }
+    }
+}
@@ -0,0 +1,203 @@
+//! Data read by threads without masking interrupts, and written by interrupt handlers.
+
+use core::cell::{Cell, UnsafeCell};
+use core::mem;
+
+use super::{with, CriticalSection};
+
+/// Data read in thread context without holding the critical section, and updated rarely,
+/// typically from an interrupt handler.
+///
+/// With a [`Mutex`](crate::Mutex), every read of shared configuration masks interrupts for as
+/// long as the read takes. Here, [`read`](Self::read) only enters the critical section
+/// twice, briefly, to count the reader in and out, and runs its closure with interrupts
+/// enabled. A writer can't wait for readers to finish, since it may have interrupted one,
+/// so [`write`](Self::write) replaces the data right away if nothing is reading it, and
+/// otherwise keeps the new value pending until the last reader leaves:
+///
+/// ```no_run
+/// use critical_section::RwLock;
+///
+/// struct Config {
+///     gain: [i32; 16],
+///     offset: i32,
+/// }
+///
+/// static CONFIG: RwLock<Config> = RwLock::new(Config {
+///     gain: [1; 16],
+///     offset: 0,
+/// });
+///
+/// // In the interrupt handler receiving new settings.
+/// fn on_config_received(gain: [i32; 16], offset: i32) {
+///     critical_section::with(|cs| CONFIG.write(cs, Config { gain, offset }));
+/// }
+///
+/// // In the main loop.
+/// fn filter(samples: &mut [i32; 16]) {
+///     CONFIG.read(|config| {
+///         for (sample, gain) in samples.iter_mut().zip(config.gain) {
+///             *sample = *sample * gain + config.offset;
+///         }
+///     });
+/// }
+/// ```
+///
+/// Readers always see a consistent value, either the one before or the one after a write.
+/// If writes keep coming while readers overlap each other, the pending value is replaced
+/// each time, and only the latest one is applied.
+pub struct RwLock<T> {
+    value: UnsafeCell<T>,
+    /// Number of `read` calls running, in any context.
+    readers: Cell<usize>,
+    /// Whether `try_modify` is running, with a mutable reference to the data.
+    modifying: Cell<bool>,
+    /// Written while readers were running, applied by the last one.
+    pending: Cell<Option<T>>,
+}
+
+// NOTE Readers in different contexts share `&T`, so `T` must be `Sync`, and written values
+// are moved in from other contexts, so it must be `Send`. The counter, the flag and the
+// pending value are only accessed inside the critical section.
+unsafe impl<T: Send + Sync> Sync for RwLock<T> {}
+
+/// Error returned by [`RwLock::try_modify`] when readers are running.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReadLocked;
+
+impl core::fmt::Display for ReadLocked {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("data is being read")
+    }
+}
+
+impl<T> RwLock<T> {
+    /// Creates a new lock holding `value`.
+    #[inline]
+    pub const fn new(value: T) -> Self {
+        RwLock {
+            value: UnsafeCell::new(value),
+            readers: Cell::new(0),
+            modifying: Cell::new(false),
+            pending: Cell::new(None),
+        }
+    }
+
+    /// Execute closure `f` with a reference to the data, without holding the critical section.
+    ///
+    /// Interrupt handlers may run, and write, while `f` runs. Their writes are applied after
+    /// `f` returns, once no other reader is running. Reading from interrupt handlers is fine
+    /// as well, also when they interrupted another reader.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from the closure of [`try_modify`](Self::try_modify), which holds a
+    /// mutable reference to the data.
+    #[inline]
+    pub fn read<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        struct Reader<'a, T>(&'a RwLock<T>);
+
+        impl<T> Drop for Reader<'_, T> {
+            fn drop(&mut self) {
+                let lock = self.0;
+                let old = with(|_| {
+                    let readers = lock.readers.get() - 1;
+                    lock.readers.set(readers);
+                    match lock.pending.take() {
+                        Some(value) if readers == 0 => {
+                            Some(unsafe { mem::replace(&mut *lock.value.get(), value) })
+                        }
+                        pending => {
+                            lock.pending.set(pending);
+                            None
+                        }
+                    }
+                });
+                // Dropped outside of the critical section.
+                drop(old);
+            }
+        }
+
+        let modifying = with(|_| {
+            let modifying = self.modifying.get();
+            if !modifying {
+                self.readers.set(self.readers.get() + 1);
+            }
+            modifying
+        });
+        // Outside of the critical section, so the panic doesn't poison it.
+        assert!(!modifying, "RwLock read while it's being modified");
+        let _reader = Reader(self);
+        f(unsafe { &*self.value.get() })
+    }
+
+    /// Replace the data with `value`, or keep it pending until the running readers are done,
+    /// returning whether it was replaced right away.
+    ///
+    /// A value that was still pending is dropped. Writing from the closure of
+    /// [`try_modify`](Self::try_modify) keeps the value pending until it returns.
+    #[inline]
+    pub fn write(&self, _cs: CriticalSection, value: T) -> bool {
+        if self.readers.get() == 0 && !self.modifying.get() {
+            drop(unsafe { mem::replace(&mut *self.value.get(), value) });
+            true
+        } else {
+            drop(self.pending.replace(Some(value)));
+            false
+        }
+    }
+
+    /// Execute closure `f` with a mutable reference to the data, or return an error if
+    /// readers are running.
+    ///
+    /// This updates the data in place, unlike [`write`](Self::write), and only sees values
+    /// that were already applied, not a pending one. While `f` runs, the lock counts as read
+    /// locked: [`read`](Self::read) panics, `try_modify` returns an error, and a value from
+    /// [`write`](Self::write) is kept pending, then applied when `f` returns.
+    #[inline]
+    pub fn try_modify<R>(
+        &self,
+        cs: CriticalSection,
+        f: impl FnOnce(&mut T) -> R,
+    ) -> Result<R, ReadLocked> {
+        struct Modifier<'a, 'cs, T>(&'a RwLock<T>, CriticalSection<'cs>);
+
+        impl<T> Drop for Modifier<'_, '_, T> {
+            fn drop(&mut self) {
+                let lock = self.0;
+                lock.modifying.set(false);
+                if let Some(value) = lock.pending.take() {
+                    lock.write(self.1, value);
+                }
+            }
+        }
+
+        if self.readers.get() != 0 || self.modifying.get() {
+            return Err(ReadLocked);
+        }
+        self.modifying.set(true);
+        let _modifier = Modifier(self, cs);
+        Ok(f(unsafe { &mut *self.value.get() }))
+    }
+
+    /// Returns a mutable reference to the data.
+    ///
+    /// Since this call borrows the lock mutably, no actual locking takes place, and no reader
+    /// can be running. A pending value is applied first.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        if let Some(value) = self.pending.get_mut().take() {
+            *self.value.get_mut() = value;
+        }
+        self.value.get_mut()
+    }
+
+    /// Consumes the lock, returning the data, with a pending value applied.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        match self.pending.into_inner() {
+            Some(value) => value,
+            None => self.value.into_inner(),
+        }
+    }
+}
@@ -0,0 +1,127 @@
+use super::{acquire, is_active, nesting_depth, release, state, RestoreState};
+
+/// Results of [`self_test`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct SelfTestReport {
+    /// Whether the self-test was started outside of a critical section. The other checks are
+    /// only meaningful if it was.
+    pub started_outside: bool,
+    /// Whether nested critical sections were tracked correctly, and all were released at the
+    /// end.
+    pub nesting: bool,
+    /// Whether two outermost critical sections acquired in a row, from the same state,
+    /// returned the same restore state, so releasing the first one restored that state.
+    pub round_trip: bool,
+    /// Whether the probe reported interrupts as masked inside the outer and the nested
+    /// critical section, or `None` without a probe.
+    pub masked: Option<bool>,
+    /// Whether the probe reported interrupts as still masked after releasing the nested
+    /// critical section, and as unmasked again after releasing the outer one, or `None`
+    /// without a probe.
+    pub restored: Option<bool>,
+}
+
+impl SelfTestReport {
+    /// Returns whether all checks that ran passed.
+    pub fn passed(&self) -> bool {
+        self.started_outside
+            && self.nesting
+            && self.round_trip
+            && self.masked != Some(false)
+            && self.restored != Some(false)
+    }
+}
+
+impl core::fmt::Display for SelfTestReport {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        fn result(check: Option<bool>) -> &'static str {
+            match check {
+                Some(true) => "ok",
+                Some(false) => "FAILED",
+                None => "skipped",
+            }
+        }
+
+        write!(
+            f,
+            "started outside: {}, nesting: {}, round trip: {}, masked: {}, restored: {}",
+            result(Some(self.started_outside)),
+            result(self.started_outside.then_some(self.nesting)),
+            result(self.started_outside.then_some(self.round_trip)),
+            result(self.masked),
+            result(self.restored)
+        )
+    }
+}
+
+/// Check that the installed critical section implementation behaves as expected.
+///
+/// This acquires the critical section, acquires it again nested, and releases both, checking
+/// the bookkeeping of this crate at each step. It then acquires it once more, checking that
+/// the restore state is the same as the first time. It's meant for board bring-up, where a wrong
+/// or missing implementation otherwise only shows up as rare data corruption.
+///
+/// `masked_probe`, if given, must return whether interrupts are masked right now, for
+/// example by reading PRIMASK on Cortex-M, or by pending a software interrupt and checking
+/// that its handler didn't run. It's called before, between and after the steps, to check
+/// that the critical section actually masks interrupts, and that releasing it restores the
+/// previous state. Interrupts must be enabled when this is called, or the last check fails.
+///
+/// ```no_run
+/// # fn primask_set() -> bool { true }
+/// let report = critical_section::self_test(Some(primask_set));
+/// assert!(report.passed(), "critical section self-test: {}", report);
+/// ```
+///
+/// This must be called outside of any critical section, otherwise only
+/// [`started_outside`](SelfTestReport::started_outside) is checked.
+pub fn self_test(masked_probe: Option<fn() -> bool>) -> SelfTestReport {
+    let mut report = SelfTestReport {
+        started_outside: !is_active(),
+        nesting: false,
+        round_trip: false,
+        masked: None,
+        restored: None,
+    };
+    if !report.started_outside {
+        return report;
+    }
+
+    let probe = |check: &mut Option<bool>, expected: bool| {
+        if let Some(probe) = masked_probe {
+            *check = Some(check.unwrap_or(true) && probe() == expected);
+        }
+    };
+
+    let mut nesting = true;
+    probe(&mut report.restored, false);
+    unsafe {
+        let outer = acquire();
+        nesting &= state::depth() == 1;
+        probe(&mut report.masked, true);
+
+        let inner = acquire();
+        nesting &= state::depth() == 2;
+        probe(&mut report.masked, true);
+
+        release(inner);
+        nesting &= state::depth() == 1;
+        probe(&mut report.restored, true);
+
+        release(outer);
+        probe(&mut report.restored, false);
+
+        let again = acquire();
+        report.round_trip = same(again, outer);
+        release(again);
+    }
+    nesting &= nesting_depth() == 0;
+    report.nesting = nesting;
+    report
+}
+
+#[allow(clippy::unit_cmp)] // `RawRestoreState` is `()` by default.
+fn same(a: RestoreState, b: RestoreState) -> bool {
+    a.0 == b.0
+}
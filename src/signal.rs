@@ -0,0 +1,159 @@
+//! One-shot notification from interrupt handlers.
+
+use core::cell::Cell;
+#[cfg(feature = "async")]
+use core::cell::RefCell;
+#[cfg(feature = "async")]
+use core::task::{Context, Poll, Waker};
+
+use super::{with, CriticalSection, Mutex};
+
+/// A value signaled by one context, such as an interrupt handler, and taken by another.
+///
+/// Signaling again before the value is taken replaces it, so only the latest value is kept.
+/// This is meant for notifications where only the latest state matters, such as "new data
+/// available" or the latest sensor reading.
+///
+/// ```no_run
+/// use critical_section::Signal;
+///
+/// static CONVERSION_DONE: Signal<u16> = Signal::new();
+///
+/// # fn read_adc_result() -> u16 { 0 }
+/// fn adc_interrupt() {
+///     CONVERSION_DONE.signal(read_adc_result());
+/// }
+///
+/// fn main() {
+///     // Start a conversion...
+///     let sample = CONVERSION_DONE.wait();
+/// #   let _ = sample;
+/// }
+/// ```
+///
+/// With the `async` Cargo feature, `wait_async` waits without blocking,
+/// and `signal` wakes the waiting task.
+pub struct Signal<T> {
+    value: Mutex<Cell<Option<T>>>,
+    #[cfg(feature = "async")]
+    waker: Mutex<RefCell<Option<Waker>>>,
+}
+
+/// A [`Signal`] without a value, for plain notifications.
+pub type Event = Signal<()>;
+
+impl<T> Signal<T> {
+    /// Creates a new signal, not signaled.
+    #[inline]
+    pub const fn new() -> Self {
+        Signal {
+            value: Mutex::new(Cell::new(None)),
+            #[cfg(feature = "async")]
+            waker: Mutex::new(RefCell::new(None)),
+        }
+    }
+
+    /// Signal `value`, replacing the value signaled before if it wasn't taken yet.
+    #[inline]
+    pub fn signal(&self, value: T) {
+        #[cfg(feature = "async")]
+        let waker = with(|cs| self.signal_in(cs, value));
+        #[cfg(not(feature = "async"))]
+        with(|cs| self.signal_in(cs, value));
+
+        // Woken outside of the critical section, since executors may do more work there.
+        #[cfg(feature = "async")]
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+
+    #[cfg(feature = "async")]
+    fn signal_in(&self, cs: CriticalSection, value: T) -> Option<Waker> {
+        drop(self.value.borrow(cs).replace(Some(value)));
+        self.waker.borrow_ref_mut(cs).take()
+    }
+
+    #[cfg(not(feature = "async"))]
+    fn signal_in(&self, cs: CriticalSection, value: T) {
+        drop(self.value.borrow(cs).replace(Some(value)));
+    }
+
+    /// Take the signaled value, if any.
+    #[inline]
+    pub fn try_take(&self) -> Option<T> {
+        with(|cs| self.value.borrow(cs).take())
+    }
+
+    /// Wait for a value to be signaled, and take it.
+    ///
+    /// This spins, releasing the critical section between checks so the interrupt handler
+    /// can signal. It must not be called from a context that the signaling one can't
+    /// preempt, such as an interrupt handler of the same or a higher priority.
+    pub fn wait(&self) -> T {
+        loop {
+            if let Some(value) = self.try_take() {
+                return value;
+            }
+            core::hint::spin_loop();
+        }
+    }
+
+    /// Returns whether a value is signaled and not taken yet.
+    #[inline]
+    pub fn signaled(&self) -> bool {
+        with(|cs| {
+            let value = self.value.borrow(cs);
+            let current = value.take();
+            let signaled = current.is_some();
+            value.set(current);
+            signaled
+        })
+    }
+
+    /// Drop the signaled value, if any.
+    #[inline]
+    pub fn reset(&self) {
+        drop(self.try_take());
+    }
+
+    /// Wait for a value to be signaled, and take it, without blocking.
+    ///
+    /// Only one waker is stored, so when several tasks wait at the same time, they wake each
+    /// other up until a value is signaled. This is meant for a single waiting task.
+    #[cfg(feature = "async")]
+    pub async fn wait_async(&self) -> T {
+        core::future::poll_fn(|cx| self.poll_wait(cx)).await
+    }
+
+    /// Poll for a signaled value, registering the waker of `cx` if none is signaled.
+    ///
+    /// This is [`wait_async`](Self::wait_async) for hand-written futures.
+    #[cfg(feature = "async")]
+    pub fn poll_wait(&self, cx: &mut Context<'_>) -> Poll<T> {
+        with(|cs| match self.value.borrow(cs).take() {
+            Some(value) => Poll::Ready(value),
+            None => {
+                let mut waker = self.waker.borrow_ref_mut(cs);
+                // A task that was already waiting is woken instead of dropping its waker, so
+                // it's never left waiting forever.
+                match &*waker {
+                    Some(waker) if waker.will_wake(cx.waker()) => {}
+                    _ => {
+                        if let Some(old) = waker.replace(cx.waker().clone()) {
+                            old.wake();
+                        }
+                    }
+                }
+                Poll::Pending
+            }
+        })
+    }
+}
+
+impl<T> Default for Signal<T> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,130 @@
+//! Deterministic simulated interrupts, for doctests of interrupt interactions.
+//!
+//! With the `sim` Cargo feature enabled, an [`Isr`] is a closure standing in for an interrupt
+//! handler. Unlike the threads of the `test_harness` module, it runs on the thread that
+//! triggers it, at exactly the point chosen by the test, except that it never runs inside a
+//! critical section: triggered inside one, it's pending until the outermost critical section
+//! is released, like a masked interrupt. This makes every interleaving reproducible:
+//!
+//! ```
+//! use critical_section::sim::Isr;
+//! use critical_section::Mutex;
+//! use std::cell::Cell;
+//!
+//! static RECEIVED: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+//!
+//! let uart_rx = Isr::new(|cs| {
+//!     let received = RECEIVED.borrow(cs);
+//!     received.set(received.get() + 1);
+//! });
+//!
+//! // Outside of critical sections, the handler runs right away.
+//! uart_rx.trigger();
+//! assert_eq!(uart_rx.run_count(), 1);
+//!
+//! critical_section::with(|cs| {
+//!     // The driver reads the counter, and the interrupt fires...
+//!     let before = RECEIVED.borrow(cs).get();
+//!     uart_rx.trigger();
+//!     // ...but can't change it before the critical section is released.
+//!     assert_eq!(RECEIVED.borrow(cs).get(), before);
+//!     assert!(uart_rx.is_pending());
+//! });
+//! assert_eq!(uart_rx.run_count(), 2);
+//! ```
+//!
+//! Like interrupts on a single core, handlers don't preempt each other: one triggered by a
+//! handler runs after it returns. Pending handlers run in the order they were triggered, and
+//! triggering a pending handler again has no effect, like a pending bit. Handlers and the
+//! pending queue belong to the thread that created them, so tests running in parallel don't
+//! affect each other.
+
+extern crate std;
+
+use std::boxed::Box;
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+use super::CriticalSection;
+
+type Handler = Box<dyn FnMut(CriticalSection)>;
+
+struct State {
+    handler: RefCell<Handler>,
+    pending: Cell<bool>,
+    runs: Cell<usize>,
+}
+
+std::thread_local! {
+    static PENDING: RefCell<VecDeque<Rc<State>>> = const { RefCell::new(VecDeque::new()) };
+    /// Set while a handler runs on this thread.
+    static RUNNING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// A simulated interrupt handler.
+///
+/// See the [module docs](self).
+pub struct Isr {
+    state: Rc<State>,
+}
+
+impl Isr {
+    /// Creates a handler running `handler` in a critical section each time it's triggered.
+    pub fn new(handler: impl FnMut(CriticalSection) + 'static) -> Self {
+        Isr {
+            state: Rc::new(State {
+                handler: RefCell::new(Box::new(handler)),
+                pending: Cell::new(false),
+                runs: Cell::new(0),
+            }),
+        }
+    }
+
+    /// Triggers the interrupt.
+    ///
+    /// The handler runs before this returns, unless the current thread is in a critical
+    /// section or a handler. Then it runs when the outermost critical section is released, or
+    /// when the handler returns.
+    pub fn trigger(&self) {
+        if !self.state.pending.replace(true) {
+            PENDING.with(|pending| pending.borrow_mut().push_back(self.state.clone()));
+        }
+        if !crate::is_active() {
+            run_pending();
+        }
+    }
+
+    /// Returns whether the interrupt was triggered, and the handler didn't run yet.
+    pub fn is_pending(&self) -> bool {
+        self.state.pending.get()
+    }
+
+    /// Returns how many times the handler ran.
+    pub fn run_count(&self) -> usize {
+        self.state.runs.get()
+    }
+}
+
+/// Runs the pending handlers, unless a handler is already running on this thread.
+///
+/// Called by `release` when the outermost critical section is released.
+pub(crate) fn run_pending() {
+    struct Running;
+
+    impl Drop for Running {
+        fn drop(&mut self) {
+            RUNNING.with(|running| running.set(false));
+        }
+    }
+
+    if RUNNING.with(|running| running.replace(true)) {
+        return;
+    }
+    let _running = Running;
+    while let Some(state) = PENDING.with(|pending| pending.borrow_mut().pop_front()) {
+        state.pending.set(false);
+        crate::with(|cs| (state.handler.borrow_mut())(cs));
+        state.runs.set(state.runs.get() + 1);
+    }
+}
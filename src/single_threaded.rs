@@ -0,0 +1,18 @@
+use core::sync::atomic::{compiler_fence, Ordering};
+
+struct SingleThreadedCriticalSection;
+crate::set_impl!(SingleThreadedCriticalSection);
+
+unsafe impl crate::Impl for SingleThreadedCriticalSection {
+    #[inline(always)]
+    unsafe fn acquire() {
+        // Nothing can run concurrently, but keep the compiler from moving accesses out of
+        // the critical section in case of signal handlers.
+        compiler_fence(Ordering::SeqCst);
+    }
+
+    #[inline(always)]
+    unsafe fn release(_: ()) {
+        compiler_fence(Ordering::SeqCst);
+    }
+}
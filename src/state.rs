@@ -0,0 +1,272 @@
+//! Bookkeeping done by this crate on top of the critical section implementation.
+//!
+//! Everything in here is only ever touched while the critical section is held, so plain
+//! `Cell`s behind a [`Mutex`] are enough, and no atomics are needed.
+//!
+//! The bookkeeping describing the critical section of the current context, the nesting depth
+//! and the restore state of the outermost critical section, is exposed as [`FullState`], so
+//! kernels switching contexts can save and restore it along with the rest of the context.
+//! The watermark and the tracing state are statistics, and aren't part of it.
+
+use core::cell::Cell;
+
+use crate::{CriticalSection, Mutex, RestoreState};
+
+/// Number of currently active (nested) critical sections.
+static NESTING_DEPTH: Mutex<Cell<usize>> = Mutex::new(Cell::new(0));
+
+/// Restore state of the outermost critical section.
+///
+/// Kept here so [`crate::without`] can release and re-acquire the critical section from the
+/// inside. The outermost release then uses this instead of the restore state it was given.
+static OUTERMOST: Mutex<Cell<RestoreState>> = Mutex::new(Cell::new(RestoreState::invalid()));
+
+/// Deepest nesting reached since startup or the last reset.
+static WATERMARK: Mutex<Cell<usize>> = Mutex::new(Cell::new(0));
+
+/// Deepest nesting reached since entering the outermost critical section.
+#[cfg(any(feature = "defmt-trace", feature = "log-trace"))]
+static MAX_DEPTH: Mutex<Cell<usize>> = Mutex::new(Cell::new(0));
+
+/// The critical section bookkeeping of a context, returned by [`save`].
+///
+/// An RTOS kernel switching tasks while the critical section is held has to switch this too:
+/// the task switched to may be outside of a critical section, or nested in a different number
+/// of them, and its outermost release must use its own restore state.
+///
+/// ```no_run
+/// use critical_section::FullState;
+///
+/// struct Task {
+///     // Registers, stack pointer...
+///     critical_section: FullState,
+/// }
+///
+/// /// Called while the critical section is held by the current task.
+/// unsafe fn switch(from: &mut Task, to: &Task) {
+///     from.critical_section = critical_section::save();
+///     // Switch the registers, stack pointer and interrupt mask...
+///     critical_section::restore(to.critical_section);
+/// }
+/// ```
+///
+/// New tasks start with [`FullState::new`], outside of any critical section.
+#[derive(Clone, Copy, Debug)]
+pub struct FullState {
+    depth: usize,
+    outermost: RestoreState,
+}
+
+impl FullState {
+    /// The state of a context outside of any critical section.
+    #[inline]
+    pub const fn new() -> Self {
+        FullState {
+            depth: 0,
+            outermost: RestoreState::invalid(),
+        }
+    }
+
+    /// Returns how many critical sections the context is nested in.
+    #[inline]
+    pub const fn depth(&self) -> usize {
+        self.depth
+    }
+}
+
+impl Default for FullState {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns the critical section bookkeeping of the current context.
+///
+/// See [`FullState`]. Like [`nesting_depth`](crate::nesting_depth), this briefly acquires the
+/// critical section itself.
+#[inline]
+pub fn save() -> FullState {
+    unsafe {
+        let restore_state = crate::acquire_raw();
+        let state = FullState {
+            depth: depth(),
+            outermost: outermost(),
+        };
+        crate::release_raw(restore_state);
+        state
+    }
+}
+
+/// Replace the critical section bookkeeping of the current context with `state`.
+///
+/// See [`FullState`]. This doesn't acquire or release the critical section, it only changes
+/// what this crate considers to be held, so that the nesting depth and the outermost release
+/// match the context being switched to.
+///
+/// # Safety
+///
+/// `state` must describe the current context after the switch: it must have been returned by
+/// [`save`] when switching away from it, or be [`FullState::new`] for a context that never
+/// ran. If its depth is not `0`, the critical section must be held with its outermost restore
+/// state, as it was when it was saved.
+#[inline]
+pub unsafe fn restore(state: FullState) {
+    let restore_state = crate::acquire_raw();
+    NESTING_DEPTH
+        .borrow(CriticalSection::new_unchecked())
+        .set(state.depth);
+    if state.depth != 0 {
+        set_outermost(state.outermost);
+    }
+    crate::release_raw(restore_state);
+}
+
+/// Record entry into a critical section, returning the nesting depth before entering.
+///
+/// # Safety
+///
+/// Must be called right after acquiring the critical section.
+#[inline(always)]
+#[cfg_attr(
+    any(feature = "debug", feature = "defmt-trace", feature = "log-trace"),
+    track_caller
+)]
+pub(crate) unsafe fn enter() -> usize {
+    let depth = NESTING_DEPTH.borrow(CriticalSection::new_unchecked());
+    let prev = depth.get();
+    depth.set(prev + 1);
+
+    let watermark = WATERMARK.borrow(CriticalSection::new_unchecked());
+    if prev + 1 > watermark.get() {
+        watermark.set(prev + 1);
+    }
+
+    if prev == 0 {
+        #[cfg(feature = "instrument")]
+        crate::instrument::on_enter();
+
+        #[cfg(feature = "debug")]
+        crate::debug::on_enter();
+
+        #[cfg(any(feature = "defmt-trace", feature = "log-trace"))]
+        crate::trace::on_enter();
+    }
+
+    // Set after tracing, so the logger's own nested critical section isn't counted.
+    #[cfg(any(feature = "defmt-trace", feature = "log-trace"))]
+    {
+        let max_depth = MAX_DEPTH.borrow(CriticalSection::new_unchecked());
+        if prev == 0 || prev + 1 > max_depth.get() {
+            max_depth.set(prev + 1);
+        }
+    }
+
+    // Last, so the time spent in hooks and tracing isn't measured.
+    #[cfg(feature = "metrics")]
+    crate::metrics::on_enter(prev);
+
+    prev
+}
+
+/// Record exit from a critical section, returning the nesting depth before exiting.
+///
+/// # Safety
+///
+/// Must be called right before releasing the critical section.
+#[inline(always)]
+pub(crate) unsafe fn exit() -> usize {
+    let depth = NESTING_DEPTH.borrow(CriticalSection::new_unchecked());
+    let prev = depth.get();
+
+    #[cfg(feature = "metrics")]
+    crate::metrics::on_exit(prev);
+
+    if prev == 1 {
+        #[cfg(feature = "instrument")]
+        crate::instrument::on_exit();
+
+        #[cfg(any(feature = "defmt-trace", feature = "log-trace"))]
+        crate::trace::on_exit(MAX_DEPTH.borrow(CriticalSection::new_unchecked()).get());
+    }
+
+    depth.set(prev - 1);
+    prev
+}
+
+/// Current nesting depth.
+///
+/// # Safety
+///
+/// Must be called while the critical section is held.
+#[inline(always)]
+pub(crate) unsafe fn depth() -> usize {
+    NESTING_DEPTH.borrow(CriticalSection::new_unchecked()).get()
+}
+
+/// Deepest nesting reached since startup or the last [`reset_max_depth`].
+///
+/// # Safety
+///
+/// Must be called while the critical section is held.
+#[inline]
+pub(crate) unsafe fn max_depth() -> usize {
+    WATERMARK.borrow(CriticalSection::new_unchecked()).get()
+}
+
+/// Reset the deepest nesting reached.
+///
+/// # Safety
+///
+/// Must be called while the critical section is held.
+#[inline]
+pub(crate) unsafe fn reset_max_depth() {
+    WATERMARK.borrow(CriticalSection::new_unchecked()).set(0)
+}
+
+/// Record the restore state of the outermost critical section.
+///
+/// # Safety
+///
+/// Must be called right after acquiring the outermost critical section.
+#[inline(always)]
+pub(crate) unsafe fn set_outermost(restore_state: RestoreState) {
+    OUTERMOST
+        .borrow(CriticalSection::new_unchecked())
+        .set(restore_state)
+}
+
+/// Restore state of the outermost critical section.
+///
+/// # Safety
+///
+/// Must be called while the critical section is held.
+#[inline(always)]
+pub(crate) unsafe fn outermost() -> RestoreState {
+    OUTERMOST.borrow(CriticalSection::new_unchecked()).get()
+}
+
+/// Forget about all the nested critical sections, returning the previous nesting depth.
+///
+/// # Safety
+///
+/// Must be called right before releasing the outermost critical section with the state from
+/// [`outermost`], while the nested ones are suspended.
+#[inline]
+pub(crate) unsafe fn suspend() -> usize {
+    NESTING_DEPTH
+        .borrow(CriticalSection::new_unchecked())
+        .replace(0)
+}
+
+/// Restore the nesting depth saved by [`suspend`].
+///
+/// # Safety
+///
+/// Must be called right after re-acquiring the outermost critical section.
+#[inline]
+pub(crate) unsafe fn resume(depth: usize) {
+    NESTING_DEPTH
+        .borrow(CriticalSection::new_unchecked())
+        .set(depth)
+}
@@ -0,0 +1,92 @@
+extern crate std;
+
+#[cfg(loom)]
+use loom::sync::{Mutex, MutexGuard};
+use std::cell::{Cell, UnsafeCell};
+use std::mem::MaybeUninit;
+#[cfg(not(loom))]
+use std::sync::{Mutex, MutexGuard};
+
+#[cfg(not(loom))]
+static GLOBAL_MUTEX: Mutex<()> = Mutex::new(());
+
+// loom primitives can't be created in a `const` context, and must be created anew for
+// each execution loom explores.
+#[cfg(loom)]
+loom::lazy_static! {
+    static ref GLOBAL_MUTEX: Mutex<()> = Mutex::new(());
+}
+
+/// Guard of `GLOBAL_MUTEX`, kept alive while the critical section is held.
+///
+/// Only ever accessed by the thread holding `GLOBAL_MUTEX`.
+struct GlobalGuard(UnsafeCell<MaybeUninit<MutexGuard<'static, ()>>>);
+
+unsafe impl Sync for GlobalGuard {}
+
+static GLOBAL_GUARD: GlobalGuard = GlobalGuard(UnsafeCell::new(MaybeUninit::uninit()));
+
+#[cfg(not(loom))]
+std::thread_local!(static IS_LOCKED: Cell<bool> = const { Cell::new(false) });
+#[cfg(loom)]
+loom::thread_local!(static IS_LOCKED: Cell<bool> = Cell::new(false));
+
+// Signal handlers run on the interrupted thread, where `IS_LOCKED` would make them look like
+// a nested critical section. All signals are blocked while the critical section is held so
+// they can't run, and the previous signal mask of the outermost critical section is saved
+// here. This is only accessed by the current thread, with signals blocked.
+#[cfg(all(feature = "unix-signal-safe", not(loom)))]
+std::thread_local!(static SIGNAL_MASK: Cell<MaybeUninit<libc::sigset_t>> = const { Cell::new(MaybeUninit::uninit()) });
+
+/// Blocks all signals in the current thread, returning the previous signal mask.
+#[cfg(all(feature = "unix-signal-safe", not(loom)))]
+unsafe fn block_signals() -> libc::sigset_t {
+    let mut all = MaybeUninit::uninit();
+    let mut old = MaybeUninit::uninit();
+    libc::sigfillset(all.as_mut_ptr());
+    libc::pthread_sigmask(libc::SIG_BLOCK, all.as_ptr(), old.as_mut_ptr());
+    old.assume_init()
+}
+
+#[cfg(all(feature = "unix-signal-safe", not(loom)))]
+unsafe fn restore_signals(mask: &libc::sigset_t) {
+    libc::pthread_sigmask(libc::SIG_SETMASK, mask, std::ptr::null_mut());
+}
+
+struct StdCriticalSection;
+crate::set_impl!(StdCriticalSection);
+
+unsafe impl crate::Impl for StdCriticalSection {
+    unsafe fn acquire() -> bool {
+        #[cfg(all(feature = "unix-signal-safe", not(loom)))]
+        let mask = block_signals();
+
+        // Allow reentrancy by checking thread local state
+        IS_LOCKED.with(|l| {
+            if l.get() {
+                // CS already acquired in the current thread.
+                #[cfg(all(feature = "unix-signal-safe", not(loom)))]
+                restore_signals(&mask);
+                return true;
+            }
+
+            // Note: it is fine to recover the guard from a poisoned mutex. The mutex itself
+            // protects no data, a panic inside the critical section is the user's business.
+            let guard = GLOBAL_MUTEX.lock().unwrap_or_else(|e| e.into_inner());
+            (*GLOBAL_GUARD.0.get()).write(guard);
+            l.set(true);
+            #[cfg(all(feature = "unix-signal-safe", not(loom)))]
+            SIGNAL_MASK.with(|m| m.set(MaybeUninit::new(mask)));
+            false
+        })
+    }
+
+    unsafe fn release(nested_cs: bool) {
+        if !nested_cs {
+            (*GLOBAL_GUARD.0.get()).assume_init_drop();
+            IS_LOCKED.with(|l| l.set(false));
+            #[cfg(all(feature = "unix-signal-safe", not(loom)))]
+            restore_signals(&SIGNAL_MASK.with(|m| m.get()).assume_init());
+        }
+    }
+}
@@ -0,0 +1,173 @@
+//! Drop-in replacements for `heapless` collections, synchronized by the critical section.
+//!
+//! [`Queue`] has the API of `heapless::spsc::Queue`, but every operation takes `&self` and
+//! runs in a short critical section, so a `static` queue can be shared by interrupt handlers
+//! and the main loop without `static mut` or splitting it first:
+//!
+//! ```no_run
+//! use critical_section::sync::Queue;
+//!
+//! static EVENTS: Queue<u8, 8> = Queue::new();
+//!
+//! // In an interrupt handler.
+//! fn on_button_interrupt() {
+//!     let _ = EVENTS.enqueue(1);
+//! }
+//!
+//! // In the main loop.
+//! fn handle_events() {
+//!     while let Some(event) = EVENTS.dequeue() {
+//!         // ...
+//! #       let _ = event;
+//!     }
+//! }
+//! ```
+//!
+//! Code written for `heapless` can keep using [`Queue::split`] to get a [`Producer`] and a
+//! [`Consumer`]. Unlike with `heapless`, any number of contexts may enqueue and dequeue.
+//!
+//! The queue is implemented with [`Channel`], which it wraps.
+
+use crate::channel::{Channel, Drain};
+
+/// A fixed-capacity queue holding up to `N` values of type `T`.
+///
+/// See the [module docs](self).
+pub struct Queue<T, const N: usize> {
+    channel: Channel<T, N>,
+}
+
+impl<T, const N: usize> Queue<T, N> {
+    /// Creates a new empty queue.
+    #[inline]
+    pub const fn new() -> Self {
+        Queue {
+            channel: Channel::new(),
+        }
+    }
+
+    /// Returns the maximum number of values the queue can hold.
+    #[inline]
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Adds `value` to the end of the queue, or returns it in `Err` if the queue is full.
+    #[inline]
+    pub fn enqueue(&self, value: T) -> Result<(), T> {
+        self.channel.try_send(value)
+    }
+
+    /// Removes the value at the front of the queue, or returns `None` if the queue is empty.
+    #[inline]
+    pub fn dequeue(&self) -> Option<T> {
+        self.channel.try_recv()
+    }
+
+    /// Returns an iterator dequeuing values until the queue is empty, each in its own short
+    /// critical section. See [`Channel::drain`].
+    #[inline]
+    pub fn drain(&self) -> Drain<'_, T, N> {
+        self.channel.drain()
+    }
+
+    /// Returns the number of values in the queue.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.channel.len()
+    }
+
+    /// Returns whether the queue is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.channel.is_empty()
+    }
+
+    /// Returns whether the queue is full.
+    #[inline]
+    pub fn is_full(&self) -> bool {
+        self.channel.is_full()
+    }
+
+    /// Splits the queue into producer and consumer handles, like `heapless::spsc::Queue::split`.
+    #[inline]
+    pub fn split(&mut self) -> (Producer<'_, T, N>, Consumer<'_, T, N>) {
+        let queue = &*self;
+        (Producer { queue }, Consumer { queue })
+    }
+}
+
+impl<T, const N: usize> Default for Queue<T, N> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The producer handle of a [`Queue`], created by [`Queue::split`].
+pub struct Producer<'a, T, const N: usize> {
+    queue: &'a Queue<T, N>,
+}
+
+impl<T, const N: usize> Producer<'_, T, N> {
+    /// Adds `value` to the end of the queue, or returns it in `Err` if the queue is full.
+    #[inline]
+    pub fn enqueue(&mut self, value: T) -> Result<(), T> {
+        self.queue.enqueue(value)
+    }
+
+    /// Returns whether there is room for another value.
+    #[inline]
+    pub fn ready(&self) -> bool {
+        !self.queue.is_full()
+    }
+
+    /// Returns the number of values in the queue.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Returns whether the queue is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+}
+
+/// The consumer handle of a [`Queue`], created by [`Queue::split`].
+pub struct Consumer<'a, T, const N: usize> {
+    queue: &'a Queue<T, N>,
+}
+
+impl<T, const N: usize> Consumer<'_, T, N> {
+    /// Removes the value at the front of the queue, or returns `None` if the queue is empty.
+    #[inline]
+    pub fn dequeue(&mut self) -> Option<T> {
+        self.queue.dequeue()
+    }
+
+    /// Returns an iterator dequeuing values until the queue is empty, see [`Queue::drain`].
+    #[inline]
+    pub fn drain(&mut self) -> Drain<'_, T, N> {
+        self.queue.drain()
+    }
+
+    /// Returns whether there is a value to dequeue.
+    #[inline]
+    pub fn ready(&self) -> bool {
+        !self.queue.is_empty()
+    }
+
+    /// Returns the number of values in the queue.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Returns whether the queue is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+}
@@ -0,0 +1,186 @@
+//! Simulated interrupts for host tests.
+//!
+//! With the `test-harness` Cargo feature enabled, [`Isr`] runs a handler on its own thread,
+//! standing in for an interrupt handler. A triggered handler only runs while no critical
+//! section is held, and holds the critical section while it runs, so the code under test
+//! sees the same interleavings as with a real interrupt on a single-core chip:
+//!
+//! ```
+//! use critical_section::test_harness::Isr;
+//! use critical_section::Mutex;
+//! use std::cell::Cell;
+//!
+//! static COUNTER: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+//!
+//! let isr = Isr::spawn(|cs| {
+//!     let counter = COUNTER.borrow(cs);
+//!     counter.set(counter.get() + 1);
+//! });
+//!
+//! critical_section::with(|cs| {
+//!     // The interrupt fires inside the critical section...
+//!     isr.trigger();
+//!     // ...but doesn't run until it's released.
+//!     assert_eq!(COUNTER.borrow(cs).get(), 0);
+//! });
+//!
+//! isr.wait_idle();
+//! assert_eq!(critical_section::with(|cs| COUNTER.borrow(cs).get()), 1);
+//!
+//! // Inject the interrupt at a specific point, outside of critical sections.
+//! isr.trigger_and_wait();
+//! assert_eq!(isr.run_count(), 2);
+//! ```
+//!
+//! Code running outside of critical sections keeps running while the handler runs, unlike
+//! with a real interrupt. Tests should only rely on the ordering of critical sections.
+
+extern crate std;
+
+use std::boxed::Box;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+
+use super::CriticalSection;
+
+type Handler = Box<dyn FnMut(CriticalSection) + Send>;
+type Panic = Box<dyn std::any::Any + Send>;
+
+struct State {
+    /// Number of triggers, including the ones the handler already ran for.
+    triggered: usize,
+    /// Number of times the handler ran.
+    completed: usize,
+    stop: bool,
+    /// Payload of a panic in the handler, not reported yet.
+    panic: Option<Panic>,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    changed: Condvar,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// A simulated interrupt handler, running on its own thread.
+///
+/// See the [module docs](self). The thread is stopped when this is dropped.
+pub struct Isr {
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Isr {
+    /// Spawns a thread running `handler` each time the interrupt is triggered.
+    ///
+    /// The handler runs inside a critical section.
+    pub fn spawn(handler: impl FnMut(CriticalSection) + Send + 'static) -> Self {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                triggered: 0,
+                completed: 0,
+                stop: false,
+                panic: None,
+            }),
+            changed: Condvar::new(),
+        });
+        let mut handler: Handler = Box::new(handler);
+        let thread = {
+            let shared = shared.clone();
+            thread::spawn(move || loop {
+                {
+                    let mut state = shared.lock();
+                    while state.triggered == state.completed && !state.stop {
+                        state = shared
+                            .changed
+                            .wait(state)
+                            .unwrap_or_else(|e| e.into_inner());
+                    }
+                    if state.stop {
+                        return;
+                    }
+                }
+
+                let result = panic::catch_unwind(AssertUnwindSafe(|| crate::with(&mut handler)));
+
+                let mut state = shared.lock();
+                state.completed += 1;
+                if let Err(payload) = result {
+                    state.panic.get_or_insert(payload);
+                }
+                shared.changed.notify_all();
+            })
+        };
+        Isr {
+            shared,
+            thread: Some(thread),
+        }
+    }
+
+    /// Triggers the interrupt, without waiting for the handler to run.
+    ///
+    /// The handler runs once for each trigger, as soon as no critical section is held.
+    pub fn trigger(&self) {
+        self.shared.lock().triggered += 1;
+        self.shared.changed.notify_all();
+    }
+
+    /// Triggers the interrupt and waits for the handler to run.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called inside a critical section, since the handler couldn't run, and if the
+    /// handler panicked.
+    #[track_caller]
+    pub fn trigger_and_wait(&self) {
+        self.trigger();
+        self.wait_idle();
+    }
+
+    /// Waits until the handler ran for all triggers so far.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called inside a critical section, since the handler couldn't run, and if the
+    /// handler panicked.
+    #[track_caller]
+    pub fn wait_idle(&self) {
+        assert!(
+            !crate::is_active(),
+            "waiting for a simulated interrupt inside a critical section would deadlock"
+        );
+        let mut state = self.shared.lock();
+        while state.completed != state.triggered {
+            state = self
+                .shared
+                .changed
+                .wait(state)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+        if let Some(payload) = state.panic.take() {
+            drop(state);
+            panic::resume_unwind(payload);
+        }
+    }
+
+    /// Returns the number of times the handler ran.
+    pub fn run_count(&self) -> usize {
+        self.shared.lock().completed
+    }
+}
+
+impl Drop for Isr {
+    fn drop(&mut self) {
+        self.shared.lock().stop = true;
+        self.shared.changed.notify_all();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
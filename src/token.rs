@@ -0,0 +1,153 @@
+//! Read-only and read-write capabilities derived from a critical section token.
+
+use core::marker::PhantomData;
+
+use super::CriticalSection;
+
+/// Capability to read data protected by the critical section.
+///
+/// Derived from a [`CriticalSection`] with [`CriticalSection::read`], or from a [`CsWrite`].
+/// Functions taking a `CsRead` instead of a `CriticalSection` state that they only read shared
+/// state: [`Mutex::borrow_read`](crate::Mutex::borrow_read) accepts it, but
+/// [`Mutex::borrow_mut`](crate::Mutex::borrow_mut) doesn't, and it can't be turned back into a
+/// `CriticalSection`.
+///
+/// ```no_run
+/// use critical_section::{CsRead, Mutex};
+/// use std::cell::RefCell;
+///
+/// static CONFIG: Mutex<RefCell<[u8; 4]>> = Mutex::new(RefCell::new([0; 4]));
+///
+/// fn checksum(cs: CsRead) -> u8 {
+///     CONFIG.borrow_read(cs).borrow().iter().fold(0, |sum, byte| sum.wrapping_add(*byte))
+/// }
+///
+/// let sum = critical_section::with(|cs| checksum(cs.read()));
+/// # let _ = sum;
+/// ```
+///
+/// The distinction is only as strong as the protected types make it: a `&Cell` obtained with
+/// either token can still be written to.
+#[derive(Clone, Copy, Debug)]
+pub struct CsRead<'cs> {
+    _0: PhantomData<&'cs ()>,
+    _not_send: PhantomData<*mut ()>,
+}
+
+/// Capability to read and write data protected by the critical section.
+///
+/// Derived from a [`CriticalSection`] with [`CriticalSection::write`]. It's needed for
+/// [`Mutex::borrow_mut`](crate::Mutex::borrow_mut), and can be narrowed to a [`CsRead`] with
+/// [`CsWrite::read`].
+#[derive(Clone, Copy, Debug)]
+pub struct CsWrite<'cs> {
+    _0: PhantomData<&'cs ()>,
+    _not_send: PhantomData<*mut ()>,
+}
+
+impl<'cs> CriticalSection<'cs> {
+    /// Returns a read-only capability for this critical section.
+    #[inline(always)]
+    pub fn read(self) -> CsRead<'cs> {
+        CsRead {
+            _0: PhantomData,
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Returns a read-write capability for this critical section.
+    #[inline(always)]
+    pub fn write(self) -> CsWrite<'cs> {
+        CsWrite {
+            _0: PhantomData,
+            _not_send: PhantomData,
+        }
+    }
+}
+
+impl<'cs> CsWrite<'cs> {
+    /// Returns a read-only capability for the same critical section.
+    #[inline(always)]
+    pub fn read(self) -> CsRead<'cs> {
+        CsRead {
+            _0: PhantomData,
+            _not_send: PhantomData,
+        }
+    }
+}
+
+impl<'cs> From<CriticalSection<'cs>> for CsRead<'cs> {
+    #[inline(always)]
+    fn from(cs: CriticalSection<'cs>) -> Self {
+        cs.read()
+    }
+}
+
+impl<'cs> From<CsWrite<'cs>> for CsRead<'cs> {
+    #[inline(always)]
+    fn from(cs: CsWrite<'cs>) -> Self {
+        cs.read()
+    }
+}
+
+impl<'cs> From<CriticalSection<'cs>> for CsWrite<'cs> {
+    #[inline(always)]
+    fn from(cs: CriticalSection<'cs>) -> Self {
+        cs.write()
+    }
+}
+
+/// Fails compilation if the future `$future` holds a critical section token across an
+/// `.await`.
+///
+/// A critical section must not be held while a task is suspended: the executor would run
+/// other tasks, and interrupt handlers would stay masked, with the token still in the
+/// future. Tokens aren't `Send`, so this checks that the future is `Send`, and evaluates to
+/// it unchanged:
+///
+/// ```
+/// use core::cell::Cell;
+/// use critical_section::{CriticalSection, Mutex};
+///
+/// static COUNTER: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+///
+/// # async fn yield_now() {}
+/// let task = critical_section::assert_not_across_await!(async {
+///     {
+///         let cs = unsafe { CriticalSection::new() };
+///         COUNTER.borrow(cs).set(1);
+///     }
+///     yield_now().await;
+/// });
+/// # drop(task);
+/// ```
+///
+/// Keeping the token until after the `.await` doesn't compile:
+///
+/// ```compile_fail
+/// use core::cell::Cell;
+/// use critical_section::{CriticalSection, Mutex};
+///
+/// static COUNTER: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+///
+/// # async fn yield_now() {}
+/// let task = critical_section::assert_not_across_await!(async {
+///     let cs = unsafe { CriticalSection::new() };
+///     yield_now().await;
+///     COUNTER.borrow(cs).set(1);
+/// });
+/// ```
+///
+/// Futures that aren't `Send` for other reasons, such as holding an `Rc`, are rejected as
+/// well, so this is meant for futures that are otherwise `Send`.
+#[macro_export]
+macro_rules! assert_not_across_await {
+    ($future: expr $(,)?) => {{
+        fn assert_not_across_await<F: ::core::future::Future + ::core::marker::Send>(
+            future: F,
+        ) -> F {
+            future
+        }
+        assert_not_across_await($future)
+    }};
+}
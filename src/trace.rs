@@ -0,0 +1,41 @@
+//! Tracing of the outermost critical section, for the `defmt-trace` and `log-trace` features.
+//!
+//! Loggers commonly take a critical section themselves. These are only called at the
+//! outermost transitions, so the logger's own critical section is nested and not traced.
+
+use core::panic::Location;
+
+/// Called right after entering the outermost critical section.
+#[inline]
+#[track_caller]
+pub(crate) fn on_enter() {
+    let location = Location::caller();
+
+    #[cfg(feature = "defmt-trace")]
+    defmt::trace!(
+        "critical section entered at {=str}:{=u32}",
+        location.file(),
+        location.line()
+    );
+
+    #[cfg(feature = "log-trace")]
+    log::trace!(target: "critical_section", "critical section entered at {}", location);
+}
+
+/// Called right before exiting the outermost critical section, with the deepest nesting
+/// reached while it was held.
+#[inline]
+pub(crate) fn on_exit(max_depth: usize) {
+    #[cfg(feature = "defmt-trace")]
+    defmt::trace!(
+        "critical section exited, max nesting depth {=usize}",
+        max_depth
+    );
+
+    #[cfg(feature = "log-trace")]
+    log::trace!(
+        target: "critical_section",
+        "critical section exited, max nesting depth {}",
+        max_depth
+    );
+}
@@ -0,0 +1,25 @@
+//! No-op implementation for the `trusted-single-context` feature.
+//!
+//! With this feature, [`acquire`](crate::acquire) and [`release`](crate::release) don't call
+//! the implementation at all. It's still defined, so that linking with another
+//! implementation fails with a duplicate symbol error instead of silently ignoring it.
+
+use crate::RawRestoreState;
+
+struct TrustedSingleContext;
+crate::set_impl!(TrustedSingleContext);
+
+unsafe impl crate::Impl for TrustedSingleContext {
+    #[inline(always)]
+    unsafe fn acquire() -> RawRestoreState {
+        crate::RestoreState::invalid().0
+    }
+
+    #[inline(always)]
+    unsafe fn release(_: RawRestoreState) {}
+}
+
+/// Keeps the symbols above in the same object as the crate's bookkeeping, so the linker
+/// always sees them.
+#[used]
+static LINK_GUARD: unsafe fn() -> RawRestoreState = _critical_section_1_0_acquire;
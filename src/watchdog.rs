@@ -0,0 +1,98 @@
+use core::cell::Cell;
+
+use super::{with, CriticalSection, Mutex};
+
+type Kick = fn();
+type CycleCounter = fn() -> u32;
+
+struct Watchdog {
+    kick: Cell<Option<Kick>>,
+    counter: Cell<Option<CycleCounter>>,
+    threshold: Cell<u32>,
+    /// Cycle count when the outermost critical section was entered, or the watchdog was last
+    /// kicked inside it.
+    start: Cell<u32>,
+}
+
+static WATCHDOG: Mutex<Watchdog> = Mutex::new(Watchdog {
+    kick: Cell::new(None),
+    counter: Cell::new(None),
+    threshold: Cell::new(0),
+    start: Cell::new(0),
+});
+
+/// Kick the watchdog with `kick` when a critical section has been held for more than
+/// `threshold` cycles.
+///
+/// Hardware watchdogs with a short window reset the system if the main loop doesn't kick them
+/// in time, which a long but legitimate critical section, such as a flash erase, can prevent.
+/// Once this is configured, the watchdog is kicked from inside the critical section when it
+/// reaches a checkpoint after running for more than `threshold` cycles, and the count starts
+/// again. Checkpoints are:
+///
+/// - [`Guard::yield_point`](crate::Guard::yield_point), also when nested and nothing is
+///   released,
+/// - each item of [`with_chunks`](crate::with_chunks), which matters when it's nested in
+///   another critical section,
+/// - explicit calls to [`watchdog_checkpoint`].
+///
+/// `counter` should return a free-running cycle counter, which may wrap around, such as the
+/// DWT cycle counter on Cortex-M. Both functions are called inside the critical section, so
+/// `threshold` should leave enough margin for the longest stretch between two checkpoints.
+///
+/// ```no_run
+/// // The reload request register RR[0] of the nRF52 watchdog, and the DWT cycle counter.
+/// const WDT_RR0: *mut u32 = 0x4001_0600 as *mut u32;
+/// const DWT_CYCCNT: *const u32 = 0xe000_1004 as *const u32;
+///
+/// fn kick_watchdog() {
+///     unsafe { WDT_RR0.write_volatile(0x6e52_4635) };
+/// }
+///
+/// fn read_cycle_counter() -> u32 {
+///     unsafe { DWT_CYCCNT.read_volatile() }
+/// }
+///
+/// // The watchdog window is 1ms at 64 MHz, kick it well before that.
+/// critical_section::configure_watchdog(kick_watchdog, read_cycle_counter, 32_000);
+/// ```
+pub fn configure_watchdog(kick: fn(), counter: fn() -> u32, threshold: u32) {
+    with(|cs| {
+        let watchdog = WATCHDOG.borrow(cs);
+        watchdog.kick.set(Some(kick));
+        watchdog.counter.set(Some(counter));
+        watchdog.threshold.set(threshold);
+        // This critical section is already being measured.
+        watchdog.start.set(counter());
+    });
+}
+
+/// Kick the watchdog set with [`configure_watchdog`], if the critical section has been held
+/// for longer than its threshold.
+///
+/// Call this in long loops inside a critical section that don't go through the other
+/// checkpoints. It does nothing if no watchdog is configured.
+#[inline]
+pub fn watchdog_checkpoint(cs: CriticalSection) {
+    let watchdog = WATCHDOG.borrow(cs);
+    if let (Some(kick), Some(counter)) = (watchdog.kick.get(), watchdog.counter.get()) {
+        let now = counter();
+        if now.wrapping_sub(watchdog.start.get()) > watchdog.threshold.get() {
+            kick();
+            watchdog.start.set(now);
+        }
+    }
+}
+
+/// Start measuring the outermost critical section.
+///
+/// # Safety
+///
+/// Must be called while the critical section is held.
+#[inline]
+pub(crate) unsafe fn start() {
+    let watchdog = WATCHDOG.borrow(CriticalSection::new_unchecked());
+    if let Some(counter) = watchdog.counter.get() {
+        watchdog.start.set(counter());
+    }
+}
@@ -0,0 +1,32 @@
+use core::arch::asm;
+
+/// `PS.INTLEVEL` field of the processor state register.
+const PS_INTLEVEL: u32 = 0xf;
+/// Interrupt level masked by the critical section. Like esp-hal, this leaves the debug
+/// (level 6) and NMI (level 7) interrupts enabled, which must not use the critical section.
+const LEVEL: u32 = 5;
+
+struct XtensaCriticalSection;
+crate::set_impl!(XtensaCriticalSection);
+
+unsafe impl crate::Impl for XtensaCriticalSection {
+    #[inline(always)]
+    unsafe fn acquire() -> u32 {
+        let ps: u32;
+        asm!("rsr.ps {}", out(reg) ps, options(nomem, nostack, preserves_flags));
+        // `rsil` sets `PS.INTLEVEL` unconditionally, so skip it when already running at a
+        // higher level, such as in a high priority interrupt handler. Otherwise, no `nomem`,
+        // so this also acts as a compiler fence.
+        if ps & PS_INTLEVEL < LEVEL {
+            asm!("rsil {}, {level}", out(reg) _, level = const LEVEL, options(nostack));
+        }
+        ps
+    }
+
+    #[inline(always)]
+    unsafe fn release(ps: u32) {
+        // Restores the previous `PS.INTLEVEL`, which is unchanged when nested. `rsync` makes
+        // sure the write has taken effect before continuing.
+        asm!("wsr.ps {}", "rsync", in(reg) ps, options(nostack));
+    }
+}
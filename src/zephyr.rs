@@ -0,0 +1,67 @@
+//! Critical section implementation for Zephyr.
+//!
+//! With the `zephyr` Cargo feature enabled, the critical section is implemented with
+//! `irq_lock`/`irq_unlock`. These are `static inline` functions, so the application must
+//! provide these `extern "C"` wrappers:
+//!
+//! ```c
+//! #include <zephyr/irq.h>
+//!
+//! unsigned int critical_section_zephyr_irq_lock(void) { return irq_lock(); }
+//! void critical_section_zephyr_irq_unlock(unsigned int key) { irq_unlock(key); }
+//! ```
+//!
+//! [`suspend`](crate::suspend) is supported with `k_sched_lock` and `k_sched_unlock`, which
+//! are regular functions and don't need wrappers. They only prevent preemption by other
+//! threads on the current CPU, and can't be called from interrupt handlers, where `suspend`
+//! falls back to a critical section, as checked with `k_is_in_isr`.
+//!
+//! `irq_lock` nests, with the key returned by each call kept in the restore state. On SMP
+//! systems, it also takes a global spinlock, so it excludes the other CPUs too. This
+//! implementation uses a `u32` restore state, so enabling the `zephyr` Cargo feature also
+//! enables `restore-state-u32`.
+
+use core::ffi::c_uint;
+
+extern "C" {
+    fn critical_section_zephyr_irq_lock() -> c_uint;
+    fn critical_section_zephyr_irq_unlock(key: c_uint);
+    fn k_is_in_isr() -> bool;
+    fn k_sched_lock();
+    fn k_sched_unlock();
+}
+
+struct ZephyrCriticalSection;
+crate::set_impl!(ZephyrCriticalSection);
+
+unsafe impl crate::Impl for ZephyrCriticalSection {
+    #[inline]
+    unsafe fn acquire() -> u32 {
+        critical_section_zephyr_irq_lock() as u32
+    }
+
+    #[inline]
+    unsafe fn release(key: u32) {
+        critical_section_zephyr_irq_unlock(key as c_uint)
+    }
+
+    #[inline]
+    unsafe fn suspend_scheduler() -> bool {
+        // The scheduler can't be locked from interrupts, fall back to a critical section.
+        if k_is_in_isr() {
+            return false;
+        }
+        k_sched_lock();
+        true
+    }
+
+    #[inline]
+    unsafe fn resume_scheduler() {
+        k_sched_unlock()
+    }
+
+    #[inline]
+    unsafe fn in_isr() -> bool {
+        k_is_in_isr()
+    }
+}
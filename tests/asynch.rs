@@ -0,0 +1,88 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+
+use critical_section::asynch::Mutex;
+
+/// Counts how many times it was woken.
+#[derive(Default)]
+struct Counter(AtomicUsize);
+
+impl Counter {
+    fn get(&self) -> usize {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+impl Wake for Counter {
+    fn wake(self: Arc<Self>) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+fn poll<F: Future>(future: Pin<&mut F>, counter: &Arc<Counter>) -> Poll<F::Output> {
+    let waker = Waker::from(counter.clone());
+    future.poll(&mut Context::from_waker(&waker))
+}
+
+#[test]
+#[cfg_attr(miri, ignore)] // Miri gives every waker clone its own vtable, so `will_wake` never matches.
+fn waiters_sleep_until_unlocked() {
+    let mutex = Mutex::<u32>::new(0);
+    let guard = mutex.try_lock().unwrap();
+
+    let (a, b) = (Arc::new(Counter::default()), Arc::new(Counter::default()));
+    let mut lock_a = Box::pin(mutex.lock());
+    let mut lock_b = Box::pin(mutex.lock());
+    for _ in 0..3 {
+        assert!(poll(lock_a.as_mut(), &a).is_pending());
+        assert!(poll(lock_b.as_mut(), &b).is_pending());
+    }
+    // Two waiting tasks don't wake each other up.
+    assert_eq!((a.get(), b.get()), (0, 0));
+
+    drop(guard);
+    assert_eq!((a.get(), b.get()), (1, 1));
+
+    let mut guard = match poll(lock_b.as_mut(), &b) {
+        Poll::Ready(guard) => guard,
+        Poll::Pending => panic!("mutex still locked"),
+    };
+    *guard += 1;
+    assert!(poll(lock_a.as_mut(), &a).is_pending());
+    drop(guard);
+    assert_eq!(a.get(), 2);
+    assert!(matches!(poll(lock_a.as_mut(), &a), Poll::Ready(guard) if *guard == 1));
+}
+
+#[test]
+fn oldest_waiter_is_displaced_beyond_capacity() {
+    let mutex = Mutex::<u32, 1>::new(0);
+    let _guard = mutex.try_lock().unwrap();
+
+    let (a, b) = (Arc::new(Counter::default()), Arc::new(Counter::default()));
+    let mut lock_a = Box::pin(mutex.lock());
+    let mut lock_b = Box::pin(mutex.lock());
+    assert!(poll(lock_a.as_mut(), &a).is_pending());
+    assert!(poll(lock_b.as_mut(), &b).is_pending());
+    assert_eq!((a.get(), b.get()), (1, 0));
+}
+
+#[test]
+fn cancelled_waiter_doesnt_keep_others_waiting() {
+    let mutex = Mutex::<u32>::new(0);
+    let guard = mutex.try_lock().unwrap();
+
+    let (a, b) = (Arc::new(Counter::default()), Arc::new(Counter::default()));
+    let mut lock_a = Box::pin(mutex.lock());
+    let mut lock_b = Box::pin(mutex.lock());
+    assert!(poll(lock_a.as_mut(), &a).is_pending());
+    assert!(poll(lock_b.as_mut(), &b).is_pending());
+    drop(lock_a);
+
+    drop(guard);
+    assert_eq!(b.get(), 1);
+    assert!(poll(lock_b.as_mut(), &b).is_ready());
+}
@@ -0,0 +1,112 @@
+//! Interleavings of interrupt handlers and the main loop on a `SharedCell`.
+
+use std::rc::Rc;
+use std::sync::Arc;
+
+use critical_section::cell::SharedCell;
+use critical_section::{sim, test_harness};
+
+/// Small xorshift generator, so every run checks the same interleavings.
+struct Rng(u32);
+
+impl Rng {
+    fn next(&mut self) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0
+    }
+}
+
+#[test]
+fn no_update_is_lost() {
+    for seed in 1..=64 {
+        let mut rng = Rng(seed);
+        let ticks = Rc::new(SharedCell::new(0u32));
+        let timer = {
+            let ticks = ticks.clone();
+            sim::Isr::new(move |cs| {
+                ticks.update(cs, |ticks| ticks + 1);
+            })
+        };
+
+        let mut updates = 0;
+        for _ in 0..200 {
+            match rng.next() % 4 {
+                // The interrupt fires outside of a critical section.
+                0 => timer.trigger(),
+                // The interrupt fires between the load and the store of the main loop, and
+                // doesn't run until the critical section is released.
+                1 => critical_section::with(|cs| {
+                    let before = ticks.get(cs);
+                    timer.trigger();
+                    assert_eq!(ticks.get(cs), before);
+                    ticks.set(cs, before + 1);
+                    updates += 1;
+                }),
+                2 => critical_section::with(|cs| {
+                    ticks.update(cs, |ticks| ticks + 1);
+                    updates += 1;
+                }),
+                // Nested critical sections don't let it run either.
+                _ => critical_section::with(|cs| {
+                    critical_section::with(|_| timer.trigger());
+                    assert!(timer.is_pending());
+                    ticks.update(cs, |ticks| ticks + 1);
+                    updates += 1;
+                }),
+            }
+            assert!(!timer.is_pending());
+        }
+
+        let total = critical_section::with(|cs| ticks.get(cs));
+        assert_eq!(total as usize, updates + timer.run_count(), "seed {}", seed);
+    }
+}
+
+#[test]
+#[cfg_attr(miri, ignore)] // The no-op implementation doesn't exclude other threads.
+fn no_update_is_lost_with_threads() {
+    let ticks = Arc::new(SharedCell::new(0u32));
+    let timer = {
+        let ticks = ticks.clone();
+        test_harness::Isr::spawn(move |cs| {
+            ticks.update(cs, |ticks| ticks + 1);
+        })
+    };
+
+    let mut rng = Rng(7);
+    for _ in 0..500 {
+        critical_section::with(|cs| {
+            let before = ticks.get(cs);
+            if rng.next() & 1 == 0 {
+                timer.trigger();
+            }
+            ticks.set(cs, before + 1);
+        });
+    }
+    timer.wait_idle();
+
+    let total = critical_section::with(|cs| ticks.get(cs));
+    assert_eq!(total as usize, 500 + timer.run_count());
+}
+
+#[test]
+#[cfg_attr(miri, ignore)] // The no-op implementation doesn't exclude other threads.
+fn read_mostly_reads_whole_writes() {
+    use critical_section::cell::ReadMostly;
+
+    static LATEST: ReadMostly<[u32; 4]> = ReadMostly::new([0; 4]);
+
+    let adc = test_harness::Isr::spawn(|cs| {
+        let [sample, ..] = LATEST.read();
+        LATEST.write(cs, [sample + 1; 4]);
+    });
+    for _ in 0..200 {
+        adc.trigger();
+        let sample = LATEST.read();
+        assert!(sample.iter().all(|value| *value == sample[0]));
+    }
+    adc.wait_idle();
+    assert_eq!(LATEST.read(), [adc.run_count() as u32; 4]);
+}
@@ -0,0 +1,65 @@
+use std::rc::Rc;
+
+use critical_section::channel::Channel;
+
+#[test]
+fn send_and_receive_in_order() {
+    let channel = Channel::<u32, 4>::new();
+    assert!(channel.is_empty());
+    assert_eq!(channel.capacity(), 4);
+
+    for i in 0..4 {
+        channel.try_send(i).unwrap();
+    }
+    assert!(channel.is_full());
+    assert_eq!(channel.try_send(4), Err(4));
+
+    assert_eq!(channel.try_recv(), Some(0));
+    assert_eq!(channel.try_recv(), Some(1));
+    // Wrap around the end of the buffer.
+    channel.try_send(4).unwrap();
+    channel.try_send(5).unwrap();
+    assert_eq!(channel.len(), 4);
+    assert_eq!(channel.drain().collect::<Vec<_>>(), [2, 3, 4, 5]);
+    assert_eq!(channel.try_recv(), None);
+}
+
+#[test]
+fn drain_receives_values_sent_during_the_loop() {
+    let channel = Channel::<u32, 2>::new();
+    channel.try_send(0).unwrap();
+
+    let mut received = Vec::new();
+    for value in channel.drain() {
+        received.push(value);
+        if value < 5 {
+            // Sent by an interrupt handler while the value is processed.
+            channel.try_send(value + 1).unwrap();
+        }
+    }
+    assert_eq!(received, [0, 1, 2, 3, 4, 5]);
+}
+
+#[test]
+fn drain_resumes_after_none() {
+    let channel = Channel::<u32, 2>::new();
+    let mut drain = channel.drain();
+    assert_eq!(drain.next(), None);
+    channel.try_send(1).unwrap();
+    assert_eq!(drain.next(), Some(1));
+    assert_eq!(drain.next(), None);
+}
+
+#[test]
+fn drops_values_left_in_the_channel() {
+    let value = Rc::new(());
+    {
+        let channel = Channel::<Rc<()>, 3>::new();
+        for _ in 0..3 {
+            channel.try_send(value.clone()).unwrap();
+        }
+        drop(channel.try_recv());
+        assert_eq!(Rc::strong_count(&value), 3);
+    }
+    assert_eq!(Rc::strong_count(&value), 1);
+}
@@ -0,0 +1,84 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::Duration;
+
+use critical_section::sim::Isr;
+
+#[test]
+fn splits_into_chunks() {
+    let mut chunks = Vec::new();
+    critical_section::delay_in_sections(
+        None,
+        Duration::from_micros(480),
+        Duration::from_micros(100),
+        |chunk| {
+            assert_eq!(critical_section::nesting_depth(), 1);
+            chunks.push(chunk.as_micros());
+        },
+    );
+    assert_eq!(chunks, [100, 100, 100, 100, 80]);
+    assert!(!critical_section::is_active());
+}
+
+#[test]
+fn zero_total_doesnt_delay() {
+    critical_section::delay_in_sections(None, Duration::ZERO, Duration::ZERO, |_| {
+        panic!("delayed")
+    });
+}
+
+#[test]
+fn interrupts_run_between_chunks() {
+    for with_guard in [false, true] {
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let isr = {
+            let events = events.clone();
+            Isr::new(move |_| events.borrow_mut().push("isr"))
+        };
+
+        let mut delay = |_| {
+            // Fires while masked, and only runs in the gap after this chunk.
+            isr.trigger();
+            events.borrow_mut().push("chunk");
+        };
+        let (total, max_masked) = (Duration::from_micros(30), Duration::from_micros(10));
+        if with_guard {
+            let mut guard = critical_section::enter();
+            critical_section::delay_in_sections(Some(&mut guard), total, max_masked, &mut delay);
+        } else {
+            critical_section::delay_in_sections(None, total, max_masked, &mut delay);
+        }
+
+        assert_eq!(
+            *events.borrow(),
+            ["chunk", "isr", "chunk", "isr", "chunk", "isr"]
+        );
+    }
+}
+
+#[test]
+#[should_panic(expected = "delay_in_sections without a guard inside a critical section")]
+fn nested_without_guard_panics() {
+    // A guard rather than `with`, so the panic doesn't poison the critical section for the
+    // other tests.
+    let _guard = critical_section::enter();
+    critical_section::delay_in_sections(
+        None,
+        Duration::from_micros(20),
+        Duration::from_micros(10),
+        |_| {},
+    );
+}
+
+#[test]
+#[should_panic(expected = "delay_in_sections with a guard nested in another critical section")]
+fn nested_guard_panics() {
+    let _outer = critical_section::enter();
+    let mut inner = critical_section::enter();
+    critical_section::delay_in_sections(
+        Some(&mut inner),
+        Duration::from_micros(20),
+        Duration::from_micros(10),
+        |_| {},
+    );
+}
@@ -0,0 +1,55 @@
+//! Emergency mode can't be left, so this is a single test, in its own test binary.
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+
+/// Whether the implementation's lock is held. It doesn't nest, which is enough here.
+static LOCKED: AtomicBool = AtomicBool::new(false);
+/// Number of calls into the implementation.
+static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe extern "C" fn enter() {
+    CALLS.fetch_add(1, Ordering::Relaxed);
+    while LOCKED.swap(true, Ordering::Acquire) {
+        thread::yield_now();
+    }
+}
+
+unsafe extern "C" fn exit() {
+    CALLS.fetch_add(1, Ordering::Relaxed);
+    LOCKED.store(false, Ordering::Release);
+}
+
+fn calls() -> usize {
+    CALLS.load(Ordering::Relaxed)
+}
+
+#[test]
+fn emergency_mode() {
+    critical_section::extern_fns::set(enter, exit);
+
+    critical_section::with(|_| {});
+    assert_eq!(calls(), 2);
+    assert!(!critical_section::in_emergency_mode());
+
+    // The crash handler runs while a critical section is held.
+    let guard = critical_section::enter();
+    assert_eq!(calls(), 3);
+    unsafe { critical_section::enter_emergency_mode() };
+    assert!(critical_section::in_emergency_mode());
+
+    // Critical sections no longer call the implementation, but still do the bookkeeping.
+    critical_section::with(|_| assert_eq!(critical_section::nesting_depth(), 2));
+    drop(guard);
+    assert_eq!(calls(), 3);
+    assert!(!critical_section::is_active());
+
+    // The critical section held when entering emergency mode was never released to the
+    // implementation, so its lock is still taken, and waiting for it would deadlock.
+    assert!(LOCKED.load(Ordering::Relaxed));
+    critical_section::with(|_| assert_eq!(critical_section::nesting_depth(), 1));
+    thread::spawn(|| critical_section::with(|_| {}))
+        .join()
+        .unwrap();
+    assert_eq!(calls(), 3);
+}
@@ -0,0 +1,120 @@
+//! The crate on top of an implementation that counts the nesting itself, like most RTOSes do.
+
+use std::cell::Cell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Once};
+use std::thread;
+
+/// Thread holding the lock, as the address of its `DEPTH`, or `0`.
+static OWNER: AtomicUsize = AtomicUsize::new(0);
+
+thread_local!(static DEPTH: Cell<usize> = const { Cell::new(0) });
+
+/// Takes the lock on the first call, counting the nested ones.
+unsafe extern "C" fn enter() {
+    DEPTH.with(|depth| {
+        if depth.get() == 0 {
+            let id = depth as *const _ as usize;
+            while OWNER
+                .compare_exchange(0, id, Ordering::Acquire, Ordering::Relaxed)
+                .is_err()
+            {
+                thread::yield_now();
+            }
+        }
+        depth.set(depth.get() + 1);
+    })
+}
+
+/// Releases the lock when the count drops to zero.
+unsafe extern "C" fn exit() {
+    DEPTH.with(|depth| {
+        depth.set(depth.get() - 1);
+        if depth.get() == 0 {
+            OWNER.store(0, Ordering::Release);
+        }
+    })
+}
+
+fn setup() {
+    static SET: Once = Once::new();
+    SET.call_once(|| critical_section::extern_fns::set(enter, exit));
+}
+
+fn depth() -> usize {
+    DEPTH.with(Cell::get)
+}
+
+#[test]
+fn nests() {
+    setup();
+    critical_section::with(|_| {
+        critical_section::with(|_| assert_eq!(depth(), 2));
+        assert_eq!(depth(), 1);
+    });
+    assert_eq!(depth(), 0);
+}
+
+#[test]
+fn without_releases_every_level() {
+    setup();
+    critical_section::with(|_| {
+        critical_section::with(|_| {
+            critical_section::with(|_| {
+                unsafe {
+                    critical_section::without(|| {
+                        assert_eq!(depth(), 0);
+                        assert!(!critical_section::is_active());
+                        // Another thread can enter the critical section in the window.
+                        let (tx, rx) = mpsc::channel();
+                        thread::spawn(move || critical_section::with(|_| tx.send(()).unwrap()));
+                        rx.recv().unwrap();
+                    })
+                };
+                assert_eq!(depth(), 3);
+                assert_eq!(critical_section::nesting_depth(), 3);
+            });
+            assert_eq!(depth(), 2);
+        });
+    });
+    assert_eq!(depth(), 0);
+}
+
+#[test]
+fn max_priority_falls_back_to_the_critical_section() {
+    setup();
+    critical_section::with_max_priority(1, |_| {
+        assert_eq!(depth(), 1);
+        critical_section::with(|_| {
+            assert!(critical_section::is_active());
+            assert_eq!(critical_section::nesting_depth(), 2);
+            // The fallback is the outermost critical section, so this releases it too.
+            unsafe { critical_section::without(|| assert_eq!(depth(), 0)) };
+            assert_eq!(depth(), 2);
+        });
+    });
+    assert_eq!(depth(), 0);
+}
+
+#[test]
+fn yield_point_releases_every_level() {
+    setup();
+    let mut guard = critical_section::enter();
+    assert!(guard.yield_point());
+    assert_eq!(depth(), 1);
+    drop(guard);
+    assert_eq!(depth(), 0);
+}
+
+#[test]
+#[cfg(feature = "panic-release")]
+fn force_release_on_panic_releases_every_level() {
+    setup();
+    // The panicking code never resumes, so its restore states are forgotten.
+    let _outer = unsafe { critical_section::acquire() };
+    let _inner = unsafe { critical_section::acquire() };
+    assert_eq!(depth(), 2);
+    unsafe { critical_section::force_release_on_panic() };
+    assert_eq!(depth(), 0);
+    assert!(!critical_section::is_active());
+}
@@ -0,0 +1,22 @@
+use std::panic;
+
+use critical_section::Guard;
+
+#[test]
+fn out_of_order_drop_while_unwinding() {
+    // Fields are dropped in declaration order, so the outer guard is dropped first.
+    struct OutOfOrder {
+        _outer: Guard,
+        _inner: Guard,
+    }
+
+    let result = panic::catch_unwind(|| {
+        let _guards = OutOfOrder {
+            _outer: critical_section::enter(),
+            _inner: critical_section::enter(),
+        };
+        panic!("unwinding");
+    });
+    assert!(result.is_err());
+    assert_eq!(critical_section::nesting_depth(), 0);
+}
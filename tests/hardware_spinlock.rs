@@ -0,0 +1,66 @@
+//! `multicore::HardwareSpinlock` on host threads, each standing in for a core, with an atomic
+//! flag standing in for the hardware lock.
+
+use std::cell::{Cell, RefCell};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+
+use critical_section::multicore::{HardwareLock, HardwareSpinlock, Platform};
+use critical_section::Mutex;
+
+struct HostCores;
+
+static NEXT_CORE: AtomicUsize = AtomicUsize::new(0);
+static LOCKED: AtomicBool = AtomicBool::new(false);
+
+thread_local!(static CORE: usize = NEXT_CORE.fetch_add(1, Ordering::Relaxed));
+thread_local!(static INTERRUPTS_ENABLED: Cell<bool> = const { Cell::new(true) });
+
+unsafe impl Platform for HostCores {
+    fn core_id() -> usize {
+        CORE.with(|core| *core)
+    }
+
+    unsafe fn disable_interrupts() -> bool {
+        INTERRUPTS_ENABLED.with(|enabled| enabled.replace(false))
+    }
+
+    unsafe fn enable_interrupts() {
+        INTERRUPTS_ENABLED.with(|enabled| enabled.set(true))
+    }
+}
+
+unsafe impl HardwareLock for HostCores {
+    unsafe fn try_lock() -> bool {
+        !LOCKED.swap(true, Ordering::Relaxed)
+    }
+
+    unsafe fn unlock() {
+        LOCKED.store(false, Ordering::Relaxed);
+    }
+}
+
+critical_section::set_impl!(HardwareSpinlock<HostCores>);
+
+#[test]
+#[cfg_attr(miri, ignore)] // The no-op implementation doesn't exclude other threads.
+fn excludes_other_cores() {
+    static COUNT: Mutex<RefCell<u32>> = Mutex::new(RefCell::new(0));
+
+    thread::scope(|scope| {
+        for _ in 0..4 {
+            scope.spawn(|| {
+                for _ in 0..1000 {
+                    critical_section::with(|cs| {
+                        // Nested on the same core, without waiting for itself.
+                        critical_section::with(|_| {});
+                        *COUNT.borrow_ref_mut(cs) += 1;
+                    });
+                }
+                assert!(INTERRUPTS_ENABLED.with(Cell::get));
+            });
+        }
+    });
+
+    assert_eq!(critical_section::with(|cs| *COUNT.borrow_ref(cs)), 4000);
+}
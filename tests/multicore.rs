@@ -0,0 +1,95 @@
+//! `multicore::Spinlock` on host threads, each standing in for a core, with contention
+//! handled by `with_or_else`.
+
+use std::cell::Cell;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use critical_section::multicore::{OnContention, Platform, Spinlock};
+
+struct HostCores;
+
+static NEXT_CORE: AtomicUsize = AtomicUsize::new(0);
+
+thread_local!(static CORE: usize = NEXT_CORE.fetch_add(1, Ordering::Relaxed));
+thread_local!(static INTERRUPTS_ENABLED: Cell<bool> = const { Cell::new(true) });
+
+unsafe impl Platform for HostCores {
+    fn core_id() -> usize {
+        CORE.with(|core| *core)
+    }
+
+    unsafe fn disable_interrupts() -> bool {
+        INTERRUPTS_ENABLED.with(|enabled| enabled.replace(false))
+    }
+
+    unsafe fn enable_interrupts() {
+        INTERRUPTS_ENABLED.with(|enabled| enabled.set(true))
+    }
+
+    const ON_CONTENTION: OnContention = OnContention::TryFail;
+}
+
+critical_section::set_impl!(Spinlock<HostCores>);
+
+fn interrupts_enabled() -> bool {
+    INTERRUPTS_ENABLED.with(Cell::get)
+}
+
+// A single test, since the spinlock is shared by the whole test binary, and tests running in
+// parallel would make `with_or_else` fall back at random.
+#[test]
+#[cfg_attr(miri, ignore)] // The no-op implementation doesn't exclude other threads.
+fn with_or_else() {
+    // Uncontended, and nested in the current core.
+    let ran = critical_section::with_or_else(
+        |_| {
+            assert!(!interrupts_enabled());
+            critical_section::with_or_else(|_| true, || false)
+        },
+        || false,
+    );
+    assert!(ran);
+    assert!(interrupts_enabled());
+
+    // Held by another core.
+    let (locked_tx, locked_rx) = mpsc::channel();
+    let (unlock_tx, unlock_rx) = mpsc::channel::<()>();
+    let released = &AtomicBool::new(false);
+    thread::scope(|scope| {
+        scope.spawn(move || {
+            critical_section::with(|_| {
+                locked_tx.send(()).unwrap();
+                unlock_rx.recv().unwrap();
+                released.store(true, Ordering::Relaxed);
+            })
+        });
+        locked_rx.recv().unwrap();
+
+        let ran = critical_section::with_or_else(|_| true, || false);
+        assert!(!ran, "with_or_else waited for the other core");
+        // The fallback runs outside of the critical section, with interrupts restored.
+        assert!(interrupts_enabled());
+
+        // `with` still waits for the other core.
+        let unlock = scope.spawn(move || {
+            thread::sleep(Duration::from_millis(10));
+            unlock_tx.send(()).unwrap();
+        });
+        critical_section::with(|_| assert!(released.load(Ordering::Relaxed)));
+        unlock.join().unwrap();
+    });
+
+    let ran = critical_section::with_or_else(|_| true, || false);
+    assert!(ran);
+
+    // Only the `with` above had to wait.
+    #[cfg(feature = "metrics")]
+    {
+        let contention = critical_section::metrics::contention();
+        assert_eq!(contention.contended, 1);
+        assert!(contention.max_spins > 0);
+    }
+}
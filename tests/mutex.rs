@@ -0,0 +1,83 @@
+use std::cell::{Cell, RefCell};
+
+use critical_section::Mutex;
+
+#[test]
+fn cell_methods() {
+    let counter = Mutex::new(Cell::new(0u32));
+    critical_section::with(|cs| {
+        assert_eq!(counter.update(cs, |value| value + 3), 3);
+        assert_eq!(counter.load(cs), 3);
+        assert_eq!(counter.swap(cs, 7), 3);
+        counter.store(cs, 9);
+        assert_eq!(counter.load(cs), 9);
+    });
+}
+
+#[test]
+fn borrow_many() {
+    let queue = Mutex::new(RefCell::new(Vec::new()));
+    let count = Mutex::new(RefCell::new(0));
+    critical_section::with(|cs| {
+        let len = Mutex::borrow_many((&queue, &count), cs, |mut queue, mut count| {
+            queue.push(7u8);
+            *count += 1;
+            queue.len()
+        });
+        assert_eq!(len, 1);
+        assert_eq!(*count.borrow_ref(cs), 1);
+    });
+}
+
+#[test]
+fn borrow_map() {
+    struct Driver {
+        config: u32,
+        rx_count: Cell<u32>,
+    }
+
+    let driver = Mutex::new(Driver {
+        config: 5,
+        rx_count: Cell::new(0),
+    });
+    critical_section::with(|cs| {
+        driver.borrow_map(cs, |driver| &driver.rx_count).set(1);
+        let config = driver.borrow_map(cs, |driver| &driver.config);
+        assert_eq!(*config, 5);
+        assert_eq!(driver.borrow(cs).rx_count.get(), 1);
+    });
+}
+
+#[test]
+fn read_and_write_tokens() {
+    let config = Mutex::new(RefCell::new([0u8; 4]));
+    critical_section::with(|cs| {
+        config.borrow_mut(cs.write())[0] = 3;
+        assert_eq!(config.borrow_read(cs.read()).borrow()[0], 3);
+        assert_eq!(config.borrow_read(cs.write().read()).borrow()[0], 3);
+    });
+}
+
+#[test]
+#[should_panic(expected = "Mutex::borrow_many called with the same mutex twice")]
+fn borrow_many_same_mutex_panics() {
+    let queue = Mutex::new(RefCell::new(Vec::<u8>::new()));
+    let count = Mutex::new(RefCell::new(0));
+    // A guard rather than `with`, so the panic doesn't poison the critical section for the
+    // other tests.
+    let guard = critical_section::enter();
+    Mutex::borrow_many((&queue, &count, &queue), guard.token(), |_, _, _| ());
+}
+
+#[test]
+#[cfg(feature = "bare-metal")]
+fn borrow_bare_metal() {
+    let counter = Mutex::new(Cell::new(0u32));
+    critical_section::with(|cs| counter.borrow(cs).set(1));
+    critical_section::with(|cs| {
+        // Safety: the token was downgraded from this crate's critical section, which is held.
+        let counter = unsafe { counter.borrow_bare_metal(cs.downgrade()) };
+        counter.set(counter.get() + 1);
+    });
+    assert_eq!(critical_section::with(|cs| counter.borrow(cs).get()), 2);
+}
@@ -0,0 +1,79 @@
+use std::rc::Rc;
+use std::sync::mpsc;
+use std::thread;
+
+use critical_section::pool::Pool;
+
+#[test]
+fn alloc_until_full() {
+    let pool: Pool<u32, 2> = Pool::new();
+    critical_section::with(|cs| {
+        let a = pool.alloc(cs, 1).unwrap();
+        let b = pool.alloc(cs, 2).unwrap();
+        assert_eq!(pool.alloc(cs, 3).unwrap_err(), 3);
+        assert_eq!((*a, *b), (1, 2));
+        a.free(cs);
+        let c = pool.alloc(cs, 4).unwrap();
+        assert_eq!((*b, *c), (2, 4));
+        b.free(cs);
+        c.free(cs);
+    });
+}
+
+#[test]
+fn drop_and_into_inner_return_the_slot() {
+    let pool: Pool<String, 1> = Pool::new();
+    let value = critical_section::with(|cs| pool.alloc(cs, String::from("rx"))).unwrap();
+    assert_eq!(value.into_inner(), "rx");
+
+    let mut value = critical_section::with(|cs| pool.alloc(cs, String::from("tx"))).unwrap();
+    value.push('!');
+    assert_eq!(*value, "tx!");
+    drop(value);
+
+    critical_section::with(|cs| pool.alloc(cs, String::new()).unwrap().free(cs));
+}
+
+#[test]
+fn drops_values_once() {
+    let pool: Pool<Rc<()>, 3> = Pool::new();
+    let counted = Rc::new(());
+    let boxes: Vec<_> = critical_section::with(|cs| {
+        (0..3)
+            .map(|_| pool.alloc(cs, counted.clone()).unwrap())
+            .collect()
+    });
+    assert_eq!(Rc::strong_count(&counted), 4);
+
+    let mut boxes = boxes.into_iter();
+    critical_section::with(|cs| boxes.next().unwrap().free(cs));
+    drop(boxes.next());
+    let inner = boxes.next().unwrap().into_inner();
+    assert_eq!(Rc::strong_count(&counted), 2);
+    drop(inner);
+    assert_eq!(Rc::strong_count(&counted), 1);
+}
+
+#[test]
+#[cfg_attr(miri, ignore)] // The no-op implementation doesn't exclude other threads.
+fn alloc_in_one_context_and_free_in_another() {
+    static BUFFERS: Pool<[u8; 16], 4> = Pool::new();
+
+    let (tx, rx) = mpsc::sync_channel(4);
+    let producer = thread::spawn(move || {
+        let mut sent = 0u32;
+        while sent < 1000 {
+            // Drop the packet if no buffer is free, like a receive interrupt would.
+            let buffer = critical_section::with(|cs| BUFFERS.alloc(cs, [sent as u8; 16]));
+            if let Ok(buffer) = buffer {
+                tx.send(buffer).unwrap();
+                sent += 1;
+            }
+        }
+    });
+    for expected in 0..1000u32 {
+        let buffer = rx.recv().unwrap();
+        assert_eq!(*buffer, [expected as u8; 16]);
+    }
+    producer.join().unwrap();
+}
@@ -0,0 +1,129 @@
+use std::cell::Cell;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+
+use critical_section::{ReadLocked, RwLock};
+
+#[test]
+fn write_without_readers_applies() {
+    let lock = RwLock::new(1);
+    assert!(critical_section::with(|cs| lock.write(cs, 2)));
+    assert_eq!(lock.read(|value| *value), 2);
+}
+
+#[test]
+fn write_while_reading_is_pending() {
+    let lock = RwLock::new(1);
+    lock.read(|value| {
+        // An interrupt handler writing while the main loop reads.
+        assert!(!critical_section::with(|cs| lock.write(cs, 2)));
+        assert!(!critical_section::with(|cs| lock.write(cs, 3)));
+        assert_eq!(*value, 1);
+    });
+    // Only the latest pending value is applied.
+    assert_eq!(lock.read(|value| *value), 3);
+}
+
+#[test]
+fn pending_write_waits_for_the_last_reader() {
+    let lock = RwLock::new(1);
+    lock.read(|outer| {
+        lock.read(|inner| {
+            critical_section::with(|cs| lock.write(cs, 2));
+            assert_eq!(*inner, 1);
+        });
+        assert_eq!(*outer, 1);
+    });
+    assert_eq!(lock.read(|value| *value), 2);
+}
+
+#[test]
+fn try_modify_while_reading_fails() {
+    let lock = RwLock::new(1);
+    lock.read(|_| {
+        let result = critical_section::with(|cs| lock.try_modify(cs, |value| *value = 2));
+        assert_eq!(result, Err(ReadLocked));
+    });
+    assert_eq!(lock.read(|value| *value), 1);
+}
+
+#[test]
+fn write_from_try_modify_is_pending() {
+    let lock = RwLock::new(1);
+    critical_section::with(|cs| {
+        lock.try_modify(cs, |value| {
+            assert!(!lock.write(cs, 5));
+            // Still the value being modified, not replaced under the reference.
+            assert_eq!(*value, 1);
+            *value = 2;
+        })
+        .unwrap();
+    });
+    assert_eq!(lock.read(|value| *value), 5);
+}
+
+#[test]
+fn try_modify_from_try_modify_fails() {
+    let lock = RwLock::new(1);
+    critical_section::with(|cs| {
+        lock.try_modify(cs, |value| {
+            assert_eq!(lock.try_modify(cs, |_| ()), Err(ReadLocked));
+            *value = 2;
+        })
+        .unwrap();
+    });
+    assert_eq!(lock.read(|value| *value), 2);
+}
+
+#[test]
+#[should_panic(expected = "RwLock read while it's being modified")]
+fn read_from_try_modify_panics() {
+    let lock = RwLock::new(1);
+    // A guard rather than `with`, so the panic doesn't poison the critical section for the
+    // other tests.
+    let guard = critical_section::enter();
+    let _ = lock.try_modify(guard.token(), |value| lock.read(|_| *value = 5));
+}
+
+#[test]
+fn drops_replaced_values_once() {
+    struct Counted<'a>(&'a Cell<u32>);
+
+    impl Drop for Counted<'_> {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    let drops = Cell::new(0);
+    let mut lock = RwLock::new(Counted(&drops));
+    lock.read(|_| {
+        let guard = critical_section::enter();
+        lock.write(guard.token(), Counted(&drops));
+        // Replaces the pending value.
+        lock.write(guard.token(), Counted(&drops));
+    });
+    assert_eq!(drops.get(), 2);
+    lock.get_mut();
+    drop(lock);
+    assert_eq!(drops.get(), 3);
+}
+
+#[test]
+#[cfg_attr(miri, ignore)] // The no-op implementation doesn't exclude other threads.
+fn readers_see_consistent_values() {
+    static LOCK: RwLock<[u32; 8]> = RwLock::new([0; 8]);
+    static DONE: AtomicBool = AtomicBool::new(false);
+
+    let writer = thread::spawn(|| {
+        for i in 1..=1000 {
+            critical_section::with(|cs| LOCK.write(cs, [i; 8]));
+        }
+        DONE.store(true, Ordering::Relaxed);
+    });
+    while !DONE.load(Ordering::Relaxed) {
+        LOCK.read(|values| assert!(values.iter().all(|value| *value == values[0])));
+    }
+    writer.join().unwrap();
+    assert_eq!(LOCK.read(|values| *values), [1000; 8]);
+}
@@ -0,0 +1,27 @@
+use critical_section::self_test;
+
+#[test]
+fn passes_with_std_impl() {
+    let report = self_test(None);
+    assert!(report.passed(), "{}", report);
+    assert!(report.round_trip);
+    assert_eq!(report.masked, None);
+    assert_eq!(report.restored, None);
+}
+
+#[test]
+fn probes_interrupts() {
+    // The `std` implementation doesn't mask anything.
+    let report = self_test(Some(|| false));
+    assert_eq!(report.masked, Some(false));
+    assert_eq!(report.restored, Some(false));
+    assert!(!report.passed());
+}
+
+#[test]
+fn fails_inside_critical_section() {
+    let _guard = critical_section::enter();
+    let report = self_test(None);
+    assert!(!report.started_outside);
+    assert!(!report.passed());
+}
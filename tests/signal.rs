@@ -0,0 +1,97 @@
+use std::thread;
+use std::time::Duration;
+
+use critical_section::{Event, Signal};
+
+#[test]
+fn signal_and_take() {
+    let signal = Signal::new();
+    assert!(!signal.signaled());
+    assert_eq!(signal.try_take(), None);
+
+    signal.signal(1);
+    assert!(signal.signaled());
+    // Checking doesn't take the value.
+    assert!(signal.signaled());
+    assert_eq!(signal.try_take(), Some(1));
+    assert_eq!(signal.try_take(), None);
+}
+
+#[test]
+fn keeps_latest_value() {
+    let signal = Signal::new();
+    signal.signal(1);
+    signal.signal(2);
+    assert_eq!(signal.try_take(), Some(2));
+    assert_eq!(signal.try_take(), None);
+}
+
+#[test]
+fn reset() {
+    let event = Event::new();
+    event.signal(());
+    event.reset();
+    assert!(!event.signaled());
+}
+
+#[test]
+#[cfg_attr(miri, ignore)] // The no-op implementation doesn't exclude other threads.
+fn wait_for_other_thread() {
+    static SIGNAL: Signal<u32> = Signal::new();
+
+    let sender = thread::spawn(|| {
+        thread::sleep(Duration::from_millis(10));
+        SIGNAL.signal(42);
+    });
+    assert_eq!(SIGNAL.wait(), 42);
+    sender.join().unwrap();
+}
+
+#[cfg(feature = "async")]
+mod wait_async {
+    use std::future::Future;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Wake, Waker};
+
+    use critical_section::Signal;
+
+    /// Counts how many times it was woken.
+    #[derive(Default)]
+    struct Counter(AtomicUsize);
+
+    impl Wake for Counter {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)] // Miri gives every waker clone its own vtable, so `will_wake` never matches.
+    fn signal_wakes_waiting_task() {
+        let signal = Signal::new();
+        let counter = Arc::new(Counter::default());
+        let waker = Waker::from(counter.clone());
+        let mut cx = Context::from_waker(&waker);
+
+        let mut wait = Box::pin(signal.wait_async());
+        assert!(wait.as_mut().poll(&mut cx).is_pending());
+        assert!(wait.as_mut().poll(&mut cx).is_pending());
+        assert_eq!(counter.0.load(Ordering::Relaxed), 0);
+
+        signal.signal(7);
+        assert_eq!(counter.0.load(Ordering::Relaxed), 1);
+        assert_eq!(wait.as_mut().poll(&mut cx), Poll::Ready(7));
+    }
+
+    #[test]
+    fn poll_wait_takes_signaled_value() {
+        let signal = Signal::new();
+        let waker = Waker::from(Arc::new(Counter::default()));
+        let mut cx = Context::from_waker(&waker);
+
+        signal.signal(3);
+        assert_eq!(signal.poll_wait(&mut cx), Poll::Ready(3));
+        assert_eq!(signal.poll_wait(&mut cx), Poll::Pending);
+    }
+}
@@ -0,0 +1,70 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use critical_section::sim::Isr;
+
+type Log = Rc<RefCell<Vec<&'static str>>>;
+
+fn logging_isr(log: &Log, name: &'static str) -> Isr {
+    let log = log.clone();
+    Isr::new(move |_| log.borrow_mut().push(name))
+}
+
+#[test]
+fn runs_right_away_outside_of_critical_sections() {
+    let isr = Isr::new(|_| assert_eq!(critical_section::nesting_depth(), 1));
+    isr.trigger();
+    assert!(!isr.is_pending());
+    assert_eq!(isr.run_count(), 1);
+}
+
+#[test]
+fn pending_until_outermost_release() {
+    let isr = Isr::new(|_| {});
+    critical_section::with(|_| {
+        critical_section::with(|_| isr.trigger());
+        // Releasing the nested critical section keeps it masked.
+        assert!(isr.is_pending());
+        assert_eq!(isr.run_count(), 0);
+    });
+    assert!(!isr.is_pending());
+    assert_eq!(isr.run_count(), 1);
+}
+
+#[test]
+fn triggering_pending_handler_again_has_no_effect() {
+    let isr = Isr::new(|_| {});
+    critical_section::with(|_| {
+        isr.trigger();
+        isr.trigger();
+    });
+    assert_eq!(isr.run_count(), 1);
+}
+
+#[test]
+fn pending_handlers_run_in_trigger_order() {
+    let log = Log::default();
+    let (a, b) = (logging_isr(&log, "a"), logging_isr(&log, "b"));
+    critical_section::with(|_| {
+        b.trigger();
+        a.trigger();
+    });
+    assert_eq!(*log.borrow(), ["b", "a"]);
+}
+
+#[test]
+fn handlers_dont_preempt_each_other() {
+    let log = Log::default();
+    let inner = Rc::new(logging_isr(&log, "inner"));
+    let outer = {
+        let (log, inner) = (log.clone(), inner.clone());
+        Isr::new(move |_| {
+            log.borrow_mut().push("outer start");
+            inner.trigger();
+            log.borrow_mut().push("outer end");
+        })
+    };
+    outer.trigger();
+    assert_eq!(*log.borrow(), ["outer start", "outer end", "inner"]);
+    assert_eq!(inner.run_count(), 1);
+}
@@ -0,0 +1,37 @@
+use std::sync::mpsc;
+use std::thread;
+
+use critical_section::CriticalSection;
+
+#[test]
+fn new_inside_critical_section() {
+    critical_section::with(|_| {
+        let _cs = unsafe { CriticalSection::new() };
+    });
+}
+
+#[test]
+#[should_panic(expected = "CriticalSection::new() called outside of a critical section")]
+fn new_outside_critical_section_panics() {
+    let _cs = unsafe { CriticalSection::new() };
+}
+
+#[test]
+#[cfg_attr(miri, ignore)] // The no-op implementation doesn't exclude other threads.
+fn new_while_another_thread_holds_it_panics() {
+    let (entered_tx, entered_rx) = mpsc::channel();
+    let (release_tx, release_rx) = mpsc::channel::<()>();
+    let holder = thread::spawn(move || {
+        critical_section::with(|_| {
+            entered_tx.send(()).unwrap();
+            let _ = release_rx.recv();
+        })
+    });
+    entered_rx.recv().unwrap();
+    let forger = thread::spawn(|| {
+        let _cs = unsafe { CriticalSection::new() };
+    });
+    drop(release_tx);
+    holder.join().unwrap();
+    assert!(forger.join().is_err());
+}